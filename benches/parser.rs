@@ -1,3 +1,6 @@
+// Benchmarks rely on the unstable `test` crate:
+// `RUSTFLAGS="--cfg nightly" cargo +nightly bench`.
+#![cfg(nightly)]
 #![feature(test)]
 
 extern crate nom_bibtex;
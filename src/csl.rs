@@ -0,0 +1,344 @@
+//! Export of bibliographies to *CSL-JSON*, the input format of citation
+//! processors such as citeproc.
+//!
+//! Each bibliography is converted to a *CSL* item whose `id` is the citation
+//! key. Names are split with the `name` module, dates are given as
+//! `date-parts` and values are converted to plain text. Tags without *CSL*
+//! equivalent are appended to the `note` as `key: value` lines.
+//!
+//! `Bibtex::from_csl_json` converts *CSL-JSON* items back to bibliographies.
+//!
+//! ```
+//! use nom_bibtex::Bibtex;
+//! use serde_json::json;
+//!
+//! let bibtex = Bibtex::parse(
+//!     "@article{ key,
+//!         author = {Einstein, Albert},
+//!         title = {On the electrodynamics of moving bodies},
+//!         year = 1905
+//!     }",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(
+//!     bibtex.to_csl_json(),
+//!     json!([{
+//!         "id": "key",
+//!         "type": "article-journal",
+//!         "author": [{ "family": "Einstein", "given": "Albert" }],
+//!         "title": "On the electrodynamics of moving bodies",
+//!         "issued": { "date-parts": [[1905]] }
+//!     }])
+//! );
+//! ```
+
+use crate::error::BibtexError;
+use crate::key::{self, KeyScheme};
+use crate::latex::plain_text;
+use crate::model::{self, BibDate, Bibliography, Bibtex, DatePart, EntryType, Month, Pages};
+use crate::name::{self, Name};
+use crate::parser;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::result;
+
+type Result<T> = result::Result<T, BibtexError>;
+
+// Tags written with the same value, by lowercase key.
+const TABLE_TAGS: [(&str, &str); 19] = [
+    ("title", "title"),
+    ("journal", "container-title"),
+    ("booktitle", "container-title"),
+    ("series", "collection-title"),
+    ("volume", "volume"),
+    ("number", "issue"),
+    ("chapter", "chapter-number"),
+    ("edition", "edition"),
+    ("publisher", "publisher"),
+    ("school", "publisher"),
+    ("institution", "publisher"),
+    ("organization", "publisher"),
+    ("address", "publisher-place"),
+    ("doi", "DOI"),
+    ("url", "URL"),
+    ("isbn", "ISBN"),
+    ("issn", "ISSN"),
+    ("abstract", "abstract"),
+    ("language", "language"),
+];
+
+/// Convert all the bibliographies to an array of *CSL-JSON* items.
+pub fn to_csl_json(bibtex: &Bibtex) -> Value {
+    Value::Array(bibtex.bibliographies().iter().map(to_csl_item).collect())
+}
+
+// Convert a bibliography to a *CSL-JSON* item, see `Bibliography::to_csl_json`.
+pub(crate) fn to_csl_item(biblio: &Bibliography) -> Value {
+    let mut item = Map::new();
+    let entry_type = biblio.entry_type_enum();
+    item.insert("id".into(), biblio.citation_key().into());
+    item.insert("type".into(), csl_type(&entry_type).into());
+    match entry_type {
+        EntryType::MastersThesis => item.insert("genre".into(), "Master's thesis".into()),
+        EntryType::PhdThesis => item.insert("genre".into(), "PhD thesis".into()),
+        _ => None,
+    };
+
+    let mut notes = vec![];
+    for (key, value) in biblio.iter_tags() {
+        match key {
+            "author" | "editor" => {
+                let names = match name::parse_names(value) {
+                    Ok(names) => names.iter().map(csl_name).collect(),
+                    Err(_) => vec![json!({ "literal": plain_text(value) })],
+                };
+                item.insert(key.into(), Value::Array(names));
+            }
+            "year" | "date" | "month" => {
+                if let Some(date) = biblio.date() {
+                    item.insert("issued".into(), csl_date(&date));
+                }
+            }
+            "pages" => {
+                if let Some(pages) = Pages::parse(value) {
+                    let pages = match pages {
+                        Pages::Range { start, end } => format!("{}-{}", start, end),
+                        Pages::Single(page) | Pages::Other(page) => page,
+                    };
+                    item.insert("page".into(), pages.into());
+                }
+            }
+            "keywords" => {
+                item.insert("keyword".into(), plain_text(value).into());
+            }
+            "note" => notes.insert(0, plain_text(value)),
+            "crossref" => {}
+            key => match TABLE_TAGS.iter().find(|(k, _)| *k == key) {
+                Some((_, field)) => {
+                    item.insert(field.to_string(), plain_text(value).into());
+                }
+                None => notes.push(format!("{}: {}", key, plain_text(value))),
+            },
+        }
+    }
+    if !notes.is_empty() {
+        item.insert("note".into(), notes.join("\n").into());
+    }
+    Value::Object(item)
+}
+
+fn csl_type(entry_type: &EntryType) -> &'static str {
+    match entry_type {
+        EntryType::Article => "article-journal",
+        EntryType::Book | EntryType::Manual | EntryType::Proceedings => "book",
+        EntryType::Booklet => "pamphlet",
+        EntryType::InBook | EntryType::InCollection => "chapter",
+        EntryType::Conference | EntryType::InProceedings => "paper-conference",
+        EntryType::MastersThesis | EntryType::PhdThesis => "thesis",
+        EntryType::TechReport => "report",
+        EntryType::Unpublished => "manuscript",
+        EntryType::Misc | EntryType::Other(_) => "document",
+    }
+}
+
+fn csl_name(name: &Name) -> Value {
+    let mut out = Map::new();
+    let parts = [
+        ("family", &name.last),
+        ("given", &name.first),
+        ("non-dropping-particle", &name.von),
+        ("suffix", &name.jr),
+    ];
+    for (field, part) in parts {
+        let part = plain_text(part);
+        if !part.is_empty() {
+            out.insert(field.into(), part.into());
+        }
+    }
+    Value::Object(out)
+}
+
+fn csl_date(date: &BibDate) -> Value {
+    let parts = |date: &DatePart| {
+        let mut parts = vec![Value::from(date.year)];
+        parts.extend(date.month.map(Value::from));
+        parts.extend(date.month.and(date.day).map(Value::from));
+        Value::Array(parts)
+    };
+    match date {
+        BibDate::Single(date) => json!({ "date-parts": [parts(date)] }),
+        BibDate::Range { start, end } => {
+            let parts = [start, end]
+                .iter()
+                .filter_map(|date| date.as_ref().map(parts))
+                .collect::<Vec<_>>();
+            json!({ "date-parts": parts })
+        }
+        BibDate::Raw(value) => json!({ "literal": value }),
+    }
+}
+
+// Build a Bibtex from an array of *CSL-JSON* items, see
+// `Bibtex::from_csl_json`.
+pub(crate) fn from_csl_json(json: &str) -> Result<Bibtex> {
+    let invalid = |descr: &str| BibtexError::InvalidCslJson(descr.into());
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| BibtexError::InvalidCslJson(e.to_string()))?;
+    let items = value
+        .as_array()
+        .ok_or_else(|| invalid("expected an array of items"))?;
+
+    let mut bibtex = Bibtex::default();
+    let mut citation_keys = HashSet::new();
+    for item in items {
+        let item = item
+            .as_object()
+            .ok_or_else(|| invalid("expected an item object"))?;
+        let biblio = from_csl_item(item, &mut citation_keys)?;
+        bibtex.push_bibliography(biblio);
+    }
+    Ok(bibtex)
+}
+
+fn from_csl_item(
+    item: &Map<String, Value>,
+    citation_keys: &mut HashSet<String>,
+) -> Result<Bibliography> {
+    let csl_type = item.get("type").and_then(Value::as_str).unwrap_or("");
+    let genre = item.get("genre").and_then(Value::as_str).unwrap_or("");
+    let entry_type = bibtex_type(csl_type, genre, item.contains_key("container-title"));
+
+    let mut tags = vec![];
+    for (field, value) in item {
+        let key = match field.as_str() {
+            "author" | "editor" => {
+                if let Some(names) = value.as_array() {
+                    let names = names.iter().filter_map(bibtex_name).collect::<Vec<_>>();
+                    tags.push((field.clone(), names.join(" and ")));
+                }
+                continue;
+            }
+            "issued" => {
+                tags.extend(bibtex_date(value));
+                continue;
+            }
+            "page" => {
+                let pages = text(value).map(|pages| match Pages::parse(&pages) {
+                    Some(pages @ Pages::Range { .. }) => pages.display_bibtex(),
+                    _ => pages,
+                });
+                tags.extend(pages.map(|pages| ("pages".into(), pages)));
+                continue;
+            }
+            "container-title" if entry_type == EntryType::Article => "journal",
+            "container-title" => "booktitle",
+            "publisher" => match entry_type {
+                EntryType::MastersThesis | EntryType::PhdThesis => "school",
+                EntryType::TechReport => "institution",
+                _ => "publisher",
+            },
+            "keyword" => "keywords",
+            "note" => "note",
+            field => match TABLE_TAGS.iter().find(|(_, f)| *f == field) {
+                Some((key, _)) => key,
+                None => continue,
+            },
+        };
+        tags.extend(text(value).map(|value| (key.to_string(), value)));
+    }
+
+    let mut biblio = Bibliography::new(entry_type.to_string(), String::new(), tags);
+    let citation_key = match item.get("id").and_then(text) {
+        Some(id) if parser::is_valid_citation_key(&id) && !citation_keys.contains(&id) => id,
+        _ => key::unique_key(biblio.generate_key(&KeyScheme::new()), citation_keys),
+    };
+    for (key, value) in biblio.iter_tags() {
+        model::check_tag(&citation_key, key, value)?;
+    }
+    citation_keys.insert(citation_key.clone());
+    biblio.set_citation_key(citation_key);
+    Ok(biblio)
+}
+
+fn bibtex_type(csl_type: &str, genre: &str, has_container: bool) -> EntryType {
+    match csl_type {
+        "article" | "article-journal" | "article-magazine" | "article-newspaper" => {
+            EntryType::Article
+        }
+        "book" => EntryType::Book,
+        "chapter" if has_container => EntryType::InCollection,
+        "chapter" => EntryType::InBook,
+        "paper-conference" => EntryType::InProceedings,
+        "thesis" if genre.to_lowercase().contains("master") => EntryType::MastersThesis,
+        "thesis" => EntryType::PhdThesis,
+        "report" => EntryType::TechReport,
+        "manuscript" => EntryType::Unpublished,
+        "pamphlet" => EntryType::Booklet,
+        _ => EntryType::Misc,
+    }
+}
+
+// Format a name as `von Last, Jr, First`, literal names being protected by
+// brackets.
+fn bibtex_name(name: &Value) -> Option<String> {
+    let part = |field| name.get(field).and_then(text).filter(|p| !p.is_empty());
+    if let Some(literal) = part("literal") {
+        return Some(format!("{{{}}}", literal));
+    }
+
+    let mut out = [part("non-dropping-particle"), part("family")]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if out.is_empty() {
+        return part("given");
+    }
+    for part in [part("suffix"), part("given")].into_iter().flatten() {
+        out.push_str(", ");
+        out.push_str(&part);
+    }
+    Some(out)
+}
+
+// Convert the start of a date to the `year` and `month` tags.
+fn bibtex_date(date: &Value) -> Vec<(String, String)> {
+    let start = date
+        .get("date-parts")
+        .and_then(|parts| parts.get(0))
+        .and_then(Value::as_array);
+    let start = match start {
+        Some(start) => start,
+        None => {
+            let literal = date.get("literal").or_else(|| date.get("raw"));
+            return literal
+                .and_then(text)
+                .map(|literal| vec![("year".into(), literal)])
+                .unwrap_or_default();
+        }
+    };
+
+    let mut tags = vec![];
+    tags.extend(
+        start
+            .first()
+            .and_then(text)
+            .map(|year| ("year".into(), year)),
+    );
+    let month = start
+        .get(1)
+        .and_then(text)
+        .and_then(|month| Month::from_number(month.parse().ok()?));
+    tags.extend(month.map(|month| ("month".into(), month.full_name().into())));
+    tags
+}
+
+// The text of a string or a number.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
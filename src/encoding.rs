@@ -0,0 +1,139 @@
+//! Decoding of *BibTeX* files which may not be UTF-8, see
+//! `Bibtex::parse_bytes` and `Bibtex::parse_bytes_as`.
+
+use crate::error::BibtexError;
+use std::borrow::Cow;
+use std::result;
+use std::str;
+
+type Result<T> = result::Result<T, BibtexError>;
+
+/// The encoding a content was decoded from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Encoding {
+    /// UTF-8, with or without a byte order mark.
+    Utf8,
+    /// Windows-1252, the superset of Latin-1 used by most old files.
+    Windows1252,
+    /// ISO-8859-1, also known as Latin-1, each byte being the character of
+    /// the same code point. It is never detected by `decode`.
+    Latin1,
+}
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+// Characters of the bytes 0x80 to 0x9F, the other bytes being the same as
+// in Latin-1. `None` is a byte without character.
+#[rustfmt::skip]
+const WINDOWS_1252: [Option<char>; 32] = [
+    Some('€'), None, Some('‚'), Some('ƒ'), Some('„'), Some('…'), Some('†'), Some('‡'),
+    Some('ˆ'), Some('‰'), Some('Š'), Some('‹'), Some('Œ'), None, Some('Ž'), None,
+    None, Some('‘'), Some('’'), Some('“'), Some('”'), Some('•'), Some('–'), Some('—'),
+    Some('˜'), Some('™'), Some('š'), Some('›'), Some('œ'), None, Some('ž'), Some('Ÿ'),
+];
+
+/// Decode a content from UTF-8 or, when it is not valid UTF-8, from
+/// Windows-1252.
+///
+/// A UTF-8 byte order mark is removed. Fails on UTF-16 contents and on the
+/// bytes which are not defined in Windows-1252.
+pub fn decode(bytes: &[u8]) -> Result<(Cow<'_, str>, Encoding)> {
+    if bytes.starts_with(b"\xff\xfe") || bytes.starts_with(b"\xfe\xff") {
+        return Err(BibtexError::Encoding("UTF-16 is not supported".into()));
+    }
+    match decode_as(bytes, Encoding::Utf8) {
+        Ok(content) => Ok((content, Encoding::Utf8)),
+        Err(_) => Ok((
+            decode_as(bytes, Encoding::Windows1252)?,
+            Encoding::Windows1252,
+        )),
+    }
+}
+
+/// Decode a content from a given encoding.
+///
+/// A UTF-8 byte order mark is removed from UTF-8 contents. Fails on the
+/// invalid UTF-8 sequences and on the bytes which are not defined in
+/// Windows-1252, all the bytes being valid Latin-1.
+pub fn decode_as(bytes: &[u8], encoding: Encoding) -> Result<Cow<'_, str>> {
+    let content = match encoding {
+        Encoding::Utf8 => {
+            let content = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+            let content = str::from_utf8(content).map_err(|e| {
+                let offset = bytes.len() - content.len() + e.valid_up_to();
+                BibtexError::Encoding(format!("invalid UTF-8 at offset {}", offset))
+            })?;
+            return Ok(Cow::Borrowed(content));
+        }
+        Encoding::Windows1252 => bytes
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| match b {
+                0x80..=0x9f => WINDOWS_1252[usize::from(b - 0x80)].ok_or_else(|| {
+                    BibtexError::Encoding(format!("invalid byte {:#04x} at offset {}", b, i))
+                }),
+                _ => Ok(char::from(b)),
+            })
+            .collect::<Result<String>>()?,
+        Encoding::Latin1 => bytes.iter().map(|&b| char::from(b)).collect(),
+    };
+    Ok(Cow::Owned(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode("@misc{ k, title = {é} }".as_bytes()).unwrap(),
+            (Cow::Borrowed("@misc{ k, title = {é} }"), Encoding::Utf8)
+        );
+        assert_eq!(
+            decode(b"\xef\xbb\xbf@misc").unwrap(),
+            (Cow::Borrowed("@misc"), Encoding::Utf8)
+        );
+        assert_eq!(
+            decode(b"M\xfcller \x80 \x93quoted\x94").unwrap(),
+            (
+                Cow::Owned("Müller € “quoted”".into()),
+                Encoding::Windows1252
+            )
+        );
+    }
+
+    #[test]
+    fn test_decode_as() {
+        assert_eq!(
+            decode_as(b"\xef\xbb\xbfM\xc3\xbcller", Encoding::Utf8).unwrap(),
+            "Müller"
+        );
+        assert_eq!(
+            decode_as(b"M\xfcller \x80", Encoding::Windows1252).unwrap(),
+            "Müller €"
+        );
+        assert_eq!(
+            decode_as(b"M\xfcller \x80", Encoding::Latin1).unwrap(),
+            "Müller \u{80}"
+        );
+        assert_eq!(
+            decode_as(b"\xef\xbb\xbfM\xfcller", Encoding::Utf8),
+            Err(BibtexError::Encoding("invalid UTF-8 at offset 4".into()))
+        );
+    }
+
+    #[test]
+    fn test_undecodable() {
+        assert_eq!(
+            decode(b"\xe9\x81"),
+            Err(BibtexError::Encoding(
+                "invalid byte 0x81 at offset 1".into()
+            ))
+        );
+        assert_eq!(
+            decode(b"\xff\xfe@\x00"),
+            Err(BibtexError::Encoding("UTF-16 is not supported".into()))
+        );
+    }
+}
@@ -1,23 +1,290 @@
-use crate::parser::Span;
-use nom::error::{convert_error, ErrorKind, VerboseError};
+use crate::model::SourceLocation;
+use crate::parser::{self, mkspan, Span};
+use nom::error::{ErrorKind, VerboseError, VerboseErrorKind};
 use nom::Err;
 use quick_error::quick_error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Where an unknown string variable is used.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VariableUse {
+    /// In the tag `field` of the bibliography `citation_key`.
+    Tag { citation_key: String, field: String },
+    /// In the definition of a string variable.
+    Variable(String),
+    /// In a preamble, numbered from 1 in the order of the input.
+    Preamble(usize),
+}
+
+impl fmt::Display for VariableUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VariableUse::Tag {
+                citation_key,
+                field,
+            } => write!(f, "field {} of entry {}", field, citation_key),
+            VariableUse::Variable(name) => write!(f, "string variable {}", name),
+            VariableUse::Preamble(n) => write!(f, "preamble #{}", n),
+        }
+    }
+}
 
 quick_error! {
-    #[derive(Debug, PartialEq, Eq)]
+    #[derive(Debug)]
     pub enum BibtexError {
         Parsing (descr: String) {
             display(me) -> ("Parsing error. Reason: {}", descr)
         }
-        StringVariableNotFound (var: String) {
-            display(me) -> ("String variable not found: {}", var)
+        ParsingError { line: u32, column: usize, message: String, source_line: String } {
+            display(me) -> ("Parsing error at line {}, column {}: {}\n{}", line, column, message, caret_snippet(source_line, *column))
+        }
+        UnclosedBracket { entry: String, line: u32, column: usize } {
+            display(me) -> ("Unclosed bracket in {} at line {}, column {}", entry, line, column)
+        }
+        InvalidEntry { entry: String, line: u32, column: usize } {
+            display(me) -> ("Invalid entry {} at line {}, column {}", entry, line, column)
+        }
+        InvalidFieldName { entry: String, name: String, line: u32, column: usize } {
+            display(me) -> ("Invalid field name `{}` in {} at line {}, column {}", name, entry, line, column)
+        }
+        UnbalancedBraces { entry: String, field: String, line: u32, column: usize } {
+            display(me) -> ("Unbalanced braces in field {} of {} at line {}, column {}", field, entry, line, column)
+        }
+        InvalidTagName { entry: String, name: String } {
+            display(me) -> ("Invalid tag name `{}` in {}", name, entry)
+        }
+        UnbalancedTagValue { entry: String, key: String } {
+            display(me) -> ("Unbalanced braces in tag {} of {}", key, entry)
+        }
+        NestingTooDeep { entry: String, line: u32, column: usize, max_depth: usize } {
+            display(me) -> ("Brackets nested deeper than {} levels in {} at line {}, column {}", max_depth, entry, line, column)
+        }
+        StringVariableNotFound { var: String, used_in: VariableUse } {
+            display(me) -> ("String variable not found: {} (used in {})", var, used_in)
+        }
+        CyclicStringVariable (var: String) {
+            display(me) -> ("String variable defined from itself: {}", var)
+        }
+        ConflictingStringVariable (var: String) {
+            display(me) -> ("String variable defined with different values: {}", var)
+        }
+        NameParsing (descr: String) {
+            display(me) -> ("Name parsing error. Reason: {}", descr)
+        }
+        DuplicateCitationKey { key: String, first: Option<SourceLocation>, duplicate: Option<SourceLocation> } {
+            display(me) -> ("Duplicate citation key: {}{}", key, duplicate_lines(first, duplicate))
+        }
+        CrossrefNotFound { citation_key: String, crossref: String } {
+            display(me) -> ("Crossref of {} not found: {}", citation_key, crossref)
+        }
+        CyclicCrossref (key: String) {
+            display(me) -> ("Cyclic crossref from: {}", key)
+        }
+        XdataNotFound { citation_key: String, xdata: String } {
+            display(me) -> ("Xdata entry of {} not found: {}", citation_key, xdata)
+        }
+        CyclicXdata (key: String) {
+            display(me) -> ("Cyclic xdata from: {}", key)
+        }
+        EmptyCitationKey { entry_type: String, line: Option<u32> } {
+            display(me) -> ("Empty citation key for a {} entry{}", entry_type, line_suffix(line))
+        }
+        InvalidCslJson (descr: String) {
+            display(me) -> ("Invalid CSL-JSON: {}", descr)
+        }
+        Encoding (descr: String) {
+            display(me) -> ("Encoding error: {}", descr)
+        }
+        EmptyInput {
+            display("No entry found in the input")
+        }
+        File { path: PathBuf, err: Box<BibtexError> } {
+            source(&**err)
+            display(me) -> ("{}: {}", path.display(), err)
+        }
+        Io (err: io::Error) {
+            from()
+            source(err)
+            display(me) -> ("IO error: {}", err)
+        }
+    }
+}
+
+// IO errors are not comparable, they are equal when they have the same kind
+// and message.
+impl PartialEq for BibtexError {
+    fn eq(&self, other: &Self) -> bool {
+        use BibtexError::*;
+        match (self, other) {
+            (Parsing(a), Parsing(b)) => a == b,
+            (
+                ParsingError {
+                    line,
+                    column,
+                    message,
+                    source_line,
+                },
+                ParsingError {
+                    line: line_,
+                    column: column_,
+                    message: message_,
+                    source_line: source_line_,
+                },
+            ) => (line, column, message, source_line) == (line_, column_, message_, source_line_),
+            (
+                UnclosedBracket {
+                    entry,
+                    line,
+                    column,
+                },
+                UnclosedBracket {
+                    entry: entry_,
+                    line: line_,
+                    column: column_,
+                },
+            )
+            | (
+                InvalidEntry {
+                    entry,
+                    line,
+                    column,
+                },
+                InvalidEntry {
+                    entry: entry_,
+                    line: line_,
+                    column: column_,
+                },
+            ) => (entry, line, column) == (entry_, line_, column_),
+            (
+                InvalidFieldName {
+                    entry,
+                    name,
+                    line,
+                    column,
+                },
+                InvalidFieldName {
+                    entry: entry_,
+                    name: name_,
+                    line: line_,
+                    column: column_,
+                },
+            )
+            | (
+                UnbalancedBraces {
+                    entry,
+                    field: name,
+                    line,
+                    column,
+                },
+                UnbalancedBraces {
+                    entry: entry_,
+                    field: name_,
+                    line: line_,
+                    column: column_,
+                },
+            ) => (entry, name, line, column) == (entry_, name_, line_, column_),
+            (
+                InvalidTagName { entry, name },
+                InvalidTagName {
+                    entry: entry_,
+                    name: name_,
+                },
+            )
+            | (
+                UnbalancedTagValue { entry, key: name },
+                UnbalancedTagValue {
+                    entry: entry_,
+                    key: name_,
+                },
+            ) => (entry, name) == (entry_, name_),
+            (
+                NestingTooDeep {
+                    entry,
+                    line,
+                    column,
+                    max_depth,
+                },
+                NestingTooDeep {
+                    entry: entry_,
+                    line: line_,
+                    column: column_,
+                    max_depth: max_depth_,
+                },
+            ) => (entry, line, column, max_depth) == (entry_, line_, column_, max_depth_),
+            (
+                StringVariableNotFound { var, used_in },
+                StringVariableNotFound {
+                    var: var_,
+                    used_in: used_in_,
+                },
+            ) => (var, used_in) == (var_, used_in_),
+            (CyclicStringVariable(a), CyclicStringVariable(b))
+            | (ConflictingStringVariable(a), ConflictingStringVariable(b)) => a == b,
+            (NameParsing(a), NameParsing(b)) => a == b,
+            (
+                DuplicateCitationKey {
+                    key,
+                    first,
+                    duplicate,
+                },
+                DuplicateCitationKey {
+                    key: key_,
+                    first: first_,
+                    duplicate: duplicate_,
+                },
+            ) => (key, first, duplicate) == (key_, first_, duplicate_),
+            (
+                CrossrefNotFound {
+                    citation_key,
+                    crossref,
+                },
+                CrossrefNotFound {
+                    citation_key: citation_key_,
+                    crossref: crossref_,
+                },
+            ) => (citation_key, crossref) == (citation_key_, crossref_),
+            (CyclicCrossref(a), CyclicCrossref(b)) => a == b,
+            (
+                XdataNotFound {
+                    citation_key,
+                    xdata,
+                },
+                XdataNotFound {
+                    citation_key: citation_key_,
+                    xdata: xdata_,
+                },
+            ) => (citation_key, xdata) == (citation_key_, xdata_),
+            (CyclicXdata(a), CyclicXdata(b)) => a == b,
+            (
+                EmptyCitationKey { entry_type, line },
+                EmptyCitationKey {
+                    entry_type: entry_type_,
+                    line: line_,
+                },
+            ) => (entry_type, line) == (entry_type_, line_),
+            (InvalidCslJson(a), InvalidCslJson(b)) => a == b,
+            (Encoding(a), Encoding(b)) => a == b,
+            (EmptyInput, EmptyInput) => true,
+            (
+                File { path, err },
+                File {
+                    path: path_,
+                    err: err_,
+                },
+            ) => (path, err) == (path_, err_),
+            (Io(a), Io(b)) => a.kind() == b.kind() && a.to_string() == b.to_string(),
+            _ => false,
         }
     }
 }
 
+impl Eq for BibtexError {}
+
 // We cannot use the from() from quick_error, because we need to put lifetimes that we didn't
 // define.
-impl<'a> From<Err<(&str, ErrorKind)>> for BibtexError {
+impl From<Err<(&str, ErrorKind)>> for BibtexError {
     fn from(err: Err<(&str, ErrorKind)>) -> BibtexError {
         let descr = match err {
             Err::Incomplete(e) => format!("Incomplete: {:?}", e),
@@ -27,24 +294,132 @@ impl<'a> From<Err<(&str, ErrorKind)>> for BibtexError {
     }
 }
 
+// The line of a parsing error followed by a caret under its column, the
+// tabulations being kept so that the caret is aligned.
+fn caret_snippet(source_line: &str, column: usize) -> String {
+    let padding = source_line
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    format!("{}\n{}^", source_line, padding)
+}
+
+// The line of an error, when it is known.
+fn line_suffix(line: &Option<u32>) -> String {
+    match line {
+        Some(line) => format!(" at line {}", line),
+        None => String::new(),
+    }
+}
+
+// The lines of a duplicated citation key, when both are known.
+fn duplicate_lines(first: &Option<SourceLocation>, duplicate: &Option<SourceLocation>) -> String {
+    match (first, duplicate) {
+        (Some(first), Some(duplicate)) => format!(" (lines {} and {})", first.line, duplicate.line),
+        _ => String::new(),
+    }
+}
+
 impl BibtexError {
+    /// Build an error from a parser failure.
+    ///
+    /// The failure is located where the parser explains what it expected,
+    /// with the line of the input which contains it, except for unclosed
+    /// brackets which are located at the bracket.
     pub fn with_context(input: &str, err: Err<VerboseError<Span>>) -> BibtexError {
-        let descr = match err {
-            Err::Incomplete(e) => format!("Incomplete: {:?}", e),
+        match err {
+            Err::Incomplete(e) => BibtexError::Parsing(format!("Incomplete: {:?}", e)),
             Err::Error(e) | Err::Failure(e) => {
-                // Convert_error does not like spans, so we need to
-                // convert the error
-                let e_ = VerboseError {
-                    errors: e
-                        .errors
-                        .into_iter()
-                        .map(|(span, kind)| (*span.fragment(), kind))
-                        .collect(),
+                if let Some(bracket) = Self::find_context(&e, parser::UNCLOSED_BRACKET) {
+                    let entry = match Self::find_context(&e, parser::ENTRY) {
+                        Some(entry) => parser::entry_name(entry.fragment()),
+                        None => "@".into(),
+                    };
+                    return BibtexError::UnclosedBracket {
+                        entry,
+                        line: bracket.location_line(),
+                        column: bracket.get_utf8_column(),
+                    };
+                }
+
+                let entry = Self::find_context(&e, parser::ENTRY);
+                let entry_name = || match entry {
+                    Some(entry) => parser::entry_name(entry.fragment()),
+                    None => "@".into(),
                 };
-                convert_error(input, e_)
+                if let Some(braces) = Self::find_context(&e, parser::UNBALANCED_BRACES) {
+                    if let Some(field) = Self::find_context(&e, parser::FIELD) {
+                        return BibtexError::UnbalancedBraces {
+                            entry: entry_name(),
+                            field: parser::field_name(field.fragment()).to_lowercase(),
+                            line: braces.location_line(),
+                            column: braces.get_utf8_column(),
+                        };
+                    }
+                }
+                if let Some(name) = Self::find_context(&e, parser::INVALID_FIELD_NAME) {
+                    return BibtexError::InvalidFieldName {
+                        entry: entry_name(),
+                        name: parser::field_name(name.fragment()),
+                        line: name.location_line(),
+                        column: name.get_utf8_column(),
+                    };
+                }
+                if let Some(key) = Self::find_context(&e, parser::EMPTY_CITATION_KEY) {
+                    return BibtexError::EmptyCitationKey {
+                        entry_type: entry
+                            .map(|entry| parser::entry_type_name(entry.fragment()))
+                            .unwrap_or_default(),
+                        line: Some(key.location_line()),
+                    };
+                }
+
+                // The innermost context explains the failure, otherwise it
+                // is located at the innermost error.
+                let failure = e.errors.iter().find_map(|(span, kind)| match kind {
+                    VerboseErrorKind::Context(c)
+                        if ![parser::ENTRY, parser::UNCLOSED_BRACKET, parser::FIELD]
+                            .contains(c) =>
+                    {
+                        Some((*span, *c))
+                    }
+                    _ => None,
+                });
+                let (span, message) = match failure {
+                    Some(failure) => failure,
+                    None => match e.errors.first() {
+                        Some((span, _)) => (*span, "invalid syntax"),
+                        None => (mkspan(input), "invalid syntax"),
+                    },
+                };
+                BibtexError::ParsingError {
+                    line: span.location_line(),
+                    column: span.get_utf8_column(),
+                    message: message.into(),
+                    source_line: Self::source_line(input, span.location_offset()),
+                }
             }
-        };
-        BibtexError::Parsing(descr)
+        }
+    }
+
+    // The line of the input which contains the byte `offset`, without its
+    // line ending.
+    fn source_line(input: &str, offset: usize) -> String {
+        let offset = offset.min(input.len());
+        let start = input[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = input[offset..]
+            .find('\n')
+            .map_or(input.len(), |i| offset + i);
+        input[start..end].trim_end_matches('\r').into()
+    }
+
+    // Find the innermost span where a context was added.
+    fn find_context<'a>(e: &VerboseError<Span<'a>>, context: &str) -> Option<Span<'a>> {
+        e.errors.iter().find_map(|(span, kind)| match kind {
+            VerboseErrorKind::Context(c) if *c == context => Some(*span),
+            _ => None,
+        })
     }
 }
 
@@ -57,7 +432,189 @@ mod tests {
         let err = BibtexError::Parsing("<some reason>".into());
         assert_eq!(format!("{}", err), "Parsing error. Reason: <some reason>");
 
-        let err = BibtexError::StringVariableNotFound("<variable>".into());
-        assert_eq!(format!("{}", err), "String variable not found: <variable>");
+        let err = BibtexError::ParsingError {
+            line: 3,
+            column: 7,
+            message: "<some reason>".into(),
+            source_line: "\ttitle = {T}".into(),
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Parsing error at line 3, column 7: <some reason>\n\ttitle = {T}\n\t     ^"
+        );
+
+        let err = BibtexError::UnclosedBracket {
+            entry: "<key>".into(),
+            line: 3,
+            column: 7,
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Unclosed bracket in <key> at line 3, column 7"
+        );
+
+        let err = BibtexError::InvalidEntry {
+            entry: "<key>".into(),
+            line: 3,
+            column: 7,
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Invalid entry <key> at line 3, column 7"
+        );
+
+        let err = BibtexError::InvalidFieldName {
+            entry: "<key>".into(),
+            name: "<name>".into(),
+            line: 3,
+            column: 7,
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Invalid field name `<name>` in <key> at line 3, column 7"
+        );
+
+        let err = BibtexError::UnbalancedBraces {
+            entry: "<key>".into(),
+            field: "<field>".into(),
+            line: 3,
+            column: 7,
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Unbalanced braces in field <field> of <key> at line 3, column 7"
+        );
+
+        let err = BibtexError::NestingTooDeep {
+            entry: "<key>".into(),
+            line: 3,
+            column: 7,
+            max_depth: 2,
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Brackets nested deeper than 2 levels in <key> at line 3, column 7"
+        );
+
+        let err = BibtexError::StringVariableNotFound {
+            var: "<variable>".into(),
+            used_in: VariableUse::Tag {
+                citation_key: "<key>".into(),
+                field: "<field>".into(),
+            },
+        };
+        assert_eq!(
+            format!("{}", err),
+            "String variable not found: <variable> (used in field <field> of entry <key>)"
+        );
+
+        let err = BibtexError::StringVariableNotFound {
+            var: "<variable>".into(),
+            used_in: VariableUse::Variable("<other>".into()),
+        };
+        assert_eq!(
+            format!("{}", err),
+            "String variable not found: <variable> (used in string variable <other>)"
+        );
+
+        let err = BibtexError::StringVariableNotFound {
+            var: "<variable>".into(),
+            used_in: VariableUse::Preamble(2),
+        };
+        assert_eq!(
+            format!("{}", err),
+            "String variable not found: <variable> (used in preamble #2)"
+        );
+
+        let err = BibtexError::CyclicStringVariable("<variable>".into());
+        assert_eq!(
+            format!("{}", err),
+            "String variable defined from itself: <variable>"
+        );
+
+        let err = BibtexError::ConflictingStringVariable("<variable>".into());
+        assert_eq!(
+            format!("{}", err),
+            "String variable defined with different values: <variable>"
+        );
+
+        let err = BibtexError::NameParsing("<some reason>".into());
+        assert_eq!(
+            format!("{}", err),
+            "Name parsing error. Reason: <some reason>"
+        );
+
+        let err = BibtexError::DuplicateCitationKey {
+            key: "<key>".into(),
+            first: None,
+            duplicate: None,
+        };
+        assert_eq!(format!("{}", err), "Duplicate citation key: <key>");
+
+        let location = |line| SourceLocation {
+            line,
+            column: 1,
+            offset: 0,
+        };
+        let err = BibtexError::DuplicateCitationKey {
+            key: "<key>".into(),
+            first: Some(location(1)),
+            duplicate: Some(location(7)),
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Duplicate citation key: <key> (lines 1 and 7)"
+        );
+
+        let err = BibtexError::CrossrefNotFound {
+            citation_key: "<key>".into(),
+            crossref: "<parent>".into(),
+        };
+        assert_eq!(format!("{}", err), "Crossref of <key> not found: <parent>");
+
+        let err = BibtexError::CyclicCrossref("<key>".into());
+        assert_eq!(format!("{}", err), "Cyclic crossref from: <key>");
+
+        let err = BibtexError::XdataNotFound {
+            citation_key: "<key>".into(),
+            xdata: "<data>".into(),
+        };
+        assert_eq!(format!("{}", err), "Xdata entry of <key> not found: <data>");
+
+        let err = BibtexError::CyclicXdata("<key>".into());
+        assert_eq!(format!("{}", err), "Cyclic xdata from: <key>");
+
+        let err = BibtexError::EmptyCitationKey {
+            entry_type: "<type>".into(),
+            line: None,
+        };
+        assert_eq!(format!("{}", err), "Empty citation key for a <type> entry");
+
+        let err = BibtexError::EmptyCitationKey {
+            entry_type: "<type>".into(),
+            line: Some(3),
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Empty citation key for a <type> entry at line 3"
+        );
+
+        let err = BibtexError::InvalidCslJson("<some reason>".into());
+        assert_eq!(format!("{}", err), "Invalid CSL-JSON: <some reason>");
+
+        let err = BibtexError::Encoding("<some reason>".into());
+        assert_eq!(format!("{}", err), "Encoding error: <some reason>");
+
+        let err = BibtexError::EmptyInput;
+        assert_eq!(format!("{}", err), "No entry found in the input");
+
+        let err = BibtexError::File {
+            path: "<path>".into(),
+            err: Box::new(BibtexError::EmptyInput),
+        };
+        assert_eq!(format!("{}", err), "<path>: No entry found in the input");
+
+        let err = BibtexError::Io(io::Error::other("<some reason>"));
+        assert_eq!(format!("{}", err), "IO error: <some reason>");
     }
 }
@@ -0,0 +1,182 @@
+//! Generation of citation keys such as `smith2020`, see
+//! `Bibliography::generate_key` and `Bibtex::regenerate_keys`.
+//!
+//! ```
+//! use nom_bibtex::key::KeyScheme;
+//! use nom_bibtex::Bibtex;
+//!
+//! let bibtex = Bibtex::parse(
+//!     "@article{ key,
+//!         author = {de la Vall{\'e}e Poussin, Charles-Jean},
+//!         title = {The Analytic Theory of Numbers},
+//!         year = 1896
+//!     }",
+//! )
+//! .unwrap();
+//!
+//! let biblio = bibtex.get("key").unwrap();
+//! assert_eq!(biblio.generate_key(&KeyScheme::new()), "valleepoussin1896");
+//! assert_eq!(
+//!     biblio.generate_key(&KeyScheme::new().title_word(true)),
+//!     "valleepoussin1896analytic"
+//! );
+//! ```
+
+use crate::latex;
+use crate::model::Bibliography;
+use std::collections::HashSet;
+
+// Leading words of a title which are skipped.
+const STOP_WORDS: &[&str] = &["a", "an", "the", "on", "of"];
+
+/// The components of the generated citation keys.
+///
+/// Keys are made of the last name of the first author, or of the first
+/// editor, followed by the year and by the first word of the title. The
+/// components are lowercase and folded to ASCII, and a bibliography without
+/// any of them is named `item`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyScheme {
+    pub(crate) year: bool,
+    pub(crate) title_word: bool,
+}
+
+impl Default for KeyScheme {
+    fn default() -> Self {
+        KeyScheme {
+            year: true,
+            title_word: false,
+        }
+    }
+}
+
+impl KeyScheme {
+    pub fn new() -> KeyScheme {
+        Self::default()
+    }
+
+    /// Add the year, as given by `Bibliography::year`, true by default.
+    pub fn year(mut self, year: bool) -> Self {
+        self.year = year;
+        self
+    }
+
+    /// Add the first word of the title, false by default.
+    ///
+    /// Leading articles such as `The` are skipped.
+    pub fn title_word(mut self, title_word: bool) -> Self {
+        self.title_word = title_word;
+        self
+    }
+}
+
+// Generate the key of a bibliography, without collision suffix.
+pub(crate) fn generate_key(biblio: &Bibliography, scheme: &KeyScheme) -> String {
+    let names = biblio
+        .authors()
+        .ok()
+        .filter(|names| !names.is_empty())
+        .or_else(|| biblio.editors().ok());
+    let mut key = names
+        .and_then(|names| names.into_iter().next())
+        .map(|name| fold(&name.last))
+        .unwrap_or_default();
+    if scheme.year {
+        if let Some(year) = biblio.year() {
+            key.push_str(&year.to_string());
+        }
+    }
+    if scheme.title_word {
+        let word = biblio.get_tag("title").and_then(|title| {
+            title
+                .split(|c: char| c.is_whitespace() || c == '~' || c == '-')
+                .map(fold)
+                .filter(|word| !word.is_empty())
+                .find(|word| !STOP_WORDS.contains(&word.as_str()))
+        });
+        key.push_str(&word.unwrap_or_default());
+    }
+    if key.is_empty() {
+        key.push_str("item");
+    }
+    key
+}
+
+// Make a key unique among `used` keys with a suffix `a`, `b`, ... `z`,
+// `aa`, `ab`...
+pub(crate) fn unique_key(key: String, used: &HashSet<String>) -> String {
+    if !used.contains(&key) {
+        return key;
+    }
+    (1..)
+        .map(|i| format!("{}{}", key, suffix(i)))
+        .find(|key| !used.contains(key))
+        .unwrap_or(key)
+}
+
+// The suffix of the i-th duplicated key, from 1.
+fn suffix(mut i: usize) -> String {
+    let mut suffix = vec![];
+    while i > 0 {
+        i -= 1;
+        suffix.push(b'a' + (i % 26) as u8);
+        i /= 26;
+    }
+    suffix.iter().rev().map(|&b| b as char).collect()
+}
+
+// Lowercase a value folded to ASCII, keeping only the letters and digits.
+fn fold(value: &str) -> String {
+    let mut folded = String::new();
+    for c in latex::decode(value).chars().flat_map(char::to_lowercase) {
+        match c {
+            'a'..='z' | '0'..='9' => folded.push(c),
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ą' => folded.push('a'),
+            'ç' | 'ć' | 'č' => folded.push('c'),
+            'ď' | 'đ' => folded.push('d'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => folded.push('e'),
+            'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => folded.push('i'),
+            'ł' | 'ľ' => folded.push('l'),
+            'ñ' | 'ń' | 'ň' => folded.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => folded.push('o'),
+            'ř' => folded.push('r'),
+            'ś' | 'š' | 'ş' => folded.push('s'),
+            'ť' | 'ţ' => folded.push('t'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => folded.push('u'),
+            'ý' | 'ÿ' => folded.push('y'),
+            'ź' | 'ż' | 'ž' => folded.push('z'),
+            'ß' => folded.push_str("ss"),
+            'æ' => folded.push_str("ae"),
+            'œ' => folded.push_str("oe"),
+            'þ' => folded.push_str("th"),
+            _ => {}
+        }
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("Müller-Łukasiewicz"), "mullerlukasiewicz");
+        assert_eq!(fold("{\\O}rsted"), "orsted");
+        assert_eq!(fold("Straße"), "strasse");
+        assert_eq!(fold("{IEEE}"), "ieee");
+        assert_eq!(fold("李"), "");
+    }
+
+    #[test]
+    fn test_unique_key() {
+        let used = ["smith2020", "smith2020a"]
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        assert_eq!(unique_key("smith2020".into(), &used), "smith2020b");
+        assert_eq!(unique_key("jones".into(), &used), "jones");
+        assert_eq!(suffix(26), "z");
+        assert_eq!(suffix(27), "aa");
+    }
+}
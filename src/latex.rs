@@ -0,0 +1,508 @@
+//! Conversion of *LaTeX* escapes found in *BibTeX* values.
+//!
+//! Only the accent commands and the special letters commonly used in
+//! bibliographies are handled. Math mode, escaped characters such as `\&`
+//! and unknown commands are left untouched.
+//!
+//! `escape` does the reverse conversion, for the files read by *BibTeX*
+//! which does not support Unicode.
+
+// Accent command, matching combining character and precomposed letters.
+type Accent = (&'static str, char, &'static [(char, char)]);
+
+#[rustfmt::skip]
+const ACCENTS: [Accent; 15] = [
+    (
+        "'",
+        '\u{301}',
+        &[
+            ('a', 'á'), ('e', 'é'), ('i', 'í'), ('o', 'ó'), ('u', 'ú'), ('y', 'ý'),
+            ('A', 'Á'), ('E', 'É'), ('I', 'Í'), ('O', 'Ó'), ('U', 'Ú'), ('Y', 'Ý'),
+            ('c', 'ć'), ('C', 'Ć'), ('n', 'ń'), ('N', 'Ń'), ('s', 'ś'), ('S', 'Ś'),
+            ('z', 'ź'), ('Z', 'Ź'), ('l', 'ĺ'), ('L', 'Ĺ'), ('r', 'ŕ'), ('R', 'Ŕ'),
+        ],
+    ),
+    (
+        "`",
+        '\u{300}',
+        &[
+            ('a', 'à'), ('e', 'è'), ('i', 'ì'), ('o', 'ò'), ('u', 'ù'),
+            ('A', 'À'), ('E', 'È'), ('I', 'Ì'), ('O', 'Ò'), ('U', 'Ù'),
+        ],
+    ),
+    (
+        "^",
+        '\u{302}',
+        &[
+            ('a', 'â'), ('e', 'ê'), ('i', 'î'), ('o', 'ô'), ('u', 'û'),
+            ('A', 'Â'), ('E', 'Ê'), ('I', 'Î'), ('O', 'Ô'), ('U', 'Û'),
+            ('c', 'ĉ'), ('g', 'ĝ'), ('h', 'ĥ'), ('j', 'ĵ'), ('s', 'ŝ'), ('w', 'ŵ'),
+            ('y', 'ŷ'),
+            ('C', 'Ĉ'), ('G', 'Ĝ'), ('H', 'Ĥ'), ('J', 'Ĵ'), ('S', 'Ŝ'), ('W', 'Ŵ'),
+            ('Y', 'Ŷ'),
+        ],
+    ),
+    (
+        "\"",
+        '\u{308}',
+        &[
+            ('a', 'ä'), ('e', 'ë'), ('i', 'ï'), ('o', 'ö'), ('u', 'ü'), ('y', 'ÿ'),
+            ('A', 'Ä'), ('E', 'Ë'), ('I', 'Ï'), ('O', 'Ö'), ('U', 'Ü'), ('Y', 'Ÿ'),
+        ],
+    ),
+    (
+        "~",
+        '\u{303}',
+        &[
+            ('a', 'ã'), ('n', 'ñ'), ('o', 'õ'), ('i', 'ĩ'), ('u', 'ũ'),
+            ('A', 'Ã'), ('N', 'Ñ'), ('O', 'Õ'), ('I', 'Ĩ'), ('U', 'Ũ'),
+        ],
+    ),
+    (
+        "=",
+        '\u{304}',
+        &[
+            ('a', 'ā'), ('e', 'ē'), ('i', 'ī'), ('o', 'ō'), ('u', 'ū'),
+            ('A', 'Ā'), ('E', 'Ē'), ('I', 'Ī'), ('O', 'Ō'), ('U', 'Ū'),
+        ],
+    ),
+    (
+        ".",
+        '\u{307}',
+        &[
+            ('c', 'ċ'), ('e', 'ė'), ('g', 'ġ'), ('z', 'ż'),
+            ('C', 'Ċ'), ('E', 'Ė'), ('G', 'Ġ'), ('I', 'İ'), ('Z', 'Ż'),
+        ],
+    ),
+    (
+        "c",
+        '\u{327}',
+        &[
+            ('c', 'ç'), ('s', 'ş'), ('t', 'ţ'), ('g', 'ģ'), ('k', 'ķ'), ('l', 'ļ'),
+            ('n', 'ņ'), ('r', 'ŗ'),
+            ('C', 'Ç'), ('S', 'Ş'), ('T', 'Ţ'), ('G', 'Ģ'), ('K', 'Ķ'), ('L', 'Ļ'),
+            ('N', 'Ņ'), ('R', 'Ŗ'),
+        ],
+    ),
+    (
+        "v",
+        '\u{30C}',
+        &[
+            ('c', 'č'), ('d', 'ď'), ('e', 'ě'), ('n', 'ň'), ('r', 'ř'), ('s', 'š'),
+            ('t', 'ť'), ('z', 'ž'), ('l', 'ľ'),
+            ('C', 'Č'), ('D', 'Ď'), ('E', 'Ě'), ('N', 'Ň'), ('R', 'Ř'), ('S', 'Š'),
+            ('T', 'Ť'), ('Z', 'Ž'), ('L', 'Ľ'),
+        ],
+    ),
+    (
+        "u",
+        '\u{306}',
+        &[
+            ('a', 'ă'), ('e', 'ĕ'), ('g', 'ğ'), ('i', 'ĭ'), ('o', 'ŏ'), ('u', 'ŭ'),
+            ('A', 'Ă'), ('E', 'Ĕ'), ('G', 'Ğ'), ('I', 'Ĭ'), ('O', 'Ŏ'), ('U', 'Ŭ'),
+        ],
+    ),
+    ("H", '\u{30B}', &[('o', 'ő'), ('u', 'ű'), ('O', 'Ő'), ('U', 'Ű')]),
+    (
+        "k",
+        '\u{328}',
+        &[
+            ('a', 'ą'), ('e', 'ę'), ('i', 'į'), ('u', 'ų'),
+            ('A', 'Ą'), ('E', 'Ę'), ('I', 'Į'), ('U', 'Ų'),
+        ],
+    ),
+    ("r", '\u{30A}', &[('a', 'å'), ('u', 'ů'), ('A', 'Å'), ('U', 'Ů')]),
+    ("d", '\u{323}', &[]),
+    ("b", '\u{331}', &[]),
+];
+
+// Accents written without any letter, such as `\~{}`.
+const SPACING_ACCENTS: [(&str, char); 7] = [
+    ("'", '´'),
+    ("`", '`'),
+    ("^", '^'),
+    ("\"", '¨'),
+    ("~", '~'),
+    ("=", '¯'),
+    (".", '˙'),
+];
+
+const LETTERS: [(&str, char); 13] = [
+    ("ss", 'ß'),
+    ("o", 'ø'),
+    ("O", 'Ø'),
+    ("aa", 'å'),
+    ("AA", 'Å'),
+    ("ae", 'æ'),
+    ("AE", 'Æ'),
+    ("oe", 'œ'),
+    ("OE", 'Œ'),
+    ("l", 'ł'),
+    ("L", 'Ł'),
+    ("i", 'ı'),
+    ("j", 'ȷ'),
+];
+
+// Characters written with a command or a ligature, other than the letters.
+const SYMBOLS: [(char, &str); 16] = [
+    ('\u{a0}', "~"),
+    ('\u{2013}', "--"),
+    ('\u{2014}', "---"),
+    ('\u{2018}', "`"),
+    ('\u{2019}', "'"),
+    ('\u{201c}', "``"),
+    ('\u{201d}', "''"),
+    ('\u{2026}', "{\\ldots}"),
+    ('¡', "!`"),
+    ('¿', "?`"),
+    ('§', "{\\S}"),
+    ('¶', "{\\P}"),
+    ('©', "{\\copyright}"),
+    ('£', "{\\pounds}"),
+    ('†', "{\\dag}"),
+    ('‡', "{\\ddag}"),
+];
+
+/// Decode the *LaTeX* accents and special letters of a value to Unicode.
+///
+/// Brackets only protecting a special character, as in `{\"o}`, are removed
+/// while any other bracket is kept.
+pub fn decode(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    // Brackets following an unknown command are its argument.
+    let mut after_command = false;
+
+    while let Some(c) = rest.chars().next() {
+        let mut is_unknown_command = false;
+        match c {
+            // Math mode is kept verbatim.
+            '$' => {
+                let end = rest[1..].find('$').map_or(rest.len(), |i| i + 2);
+                result.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            '{' if !after_command && rest[1..].starts_with('\\') => match matching_bracket(rest) {
+                Some(end) => {
+                    let inner = decode(&rest[1..end]);
+                    if inner.contains('\\') {
+                        result.push('{');
+                        result.push_str(&inner);
+                        result.push('}');
+                    } else {
+                        result.push_str(&inner);
+                    }
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    result.push(c);
+                    rest = &rest[1..];
+                }
+            },
+            '\\' => match decode_command(rest) {
+                Some((decoded, len)) => {
+                    result.push_str(&decoded);
+                    rest = &rest[len..];
+                }
+                None => {
+                    let len = command_len(rest);
+                    result.push_str(&rest[..len]);
+                    rest = &rest[len..];
+                    is_unknown_command = true;
+                }
+            },
+            _ => {
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        after_command = is_unknown_command;
+    }
+    result
+}
+
+/// Escape the non-ASCII characters of a value with *LaTeX* commands, as
+/// `\"{o}` for `ö`, `{\ss}` for `ß` or `--` for an en dash.
+///
+/// The accented letters and the special letters of `decode` are escaped,
+/// as are the typographic quotes and dashes. ASCII text, including the
+/// commands already in the value, and math mode are kept as written. The
+/// other characters are kept too, see `unescapable_chars`.
+///
+/// ```
+/// use nom_bibtex::latex;
+///
+/// assert_eq!(
+///     latex::escape("Gödel, Straße \\& Français"),
+///     "G\\\"{o}del, Stra{\\ss}e \\& Fran\\c{c}ais"
+/// );
+/// assert_eq!(latex::escape("“Café” – 李"), "``Caf\\'{e}'' -- 李");
+/// assert_eq!(latex::unescapable_chars("“Café” – 李"), ['李']);
+/// ```
+pub fn escape(value: &str) -> String {
+    escape_reporting(value, &mut vec![])
+}
+
+/// Get the non-ASCII characters which `escape` keeps as they are, in the
+/// order of the value and without duplicates.
+pub fn unescapable_chars(value: &str) -> Vec<char> {
+    let mut unknown = vec![];
+    escape_reporting(value, &mut unknown);
+    unknown
+}
+
+// Escape a value, adding the characters which cannot be escaped to
+// `unknown`.
+pub(crate) fn escape_reporting(value: &str, unknown: &mut Vec<char>) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    let mut in_math = false;
+
+    while let Some(c) = chars.next() {
+        if in_math || c.is_ascii() {
+            result.push(c);
+            match c {
+                // An escaped dollar does not start math mode.
+                '\\' => result.extend(chars.next_if(char::is_ascii)),
+                '$' => in_math = !in_math,
+                // A combining accent, as decoded for an unknown letter.
+                _ if !in_math && c.is_ascii_alphabetic() => {
+                    let accent = chars.peek().and_then(|next| {
+                        ACCENTS.iter().find(|(_, combining, _)| combining == next)
+                    });
+                    if let Some((name, _, _)) = accent {
+                        chars.next();
+                        result.pop();
+                        result.push_str(&format!("\\{}{{{}}}", name, c));
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some((name, _)) = LETTERS.iter().find(|(_, letter)| *letter == c) {
+            result.push_str(&format!("{{\\{}}}", name));
+        } else if let Some((name, base)) = ACCENTS.iter().find_map(|(name, _, letters)| {
+            letters
+                .iter()
+                .find(|(_, letter)| *letter == c)
+                .map(|(base, _)| (name, base))
+        }) {
+            result.push_str(&format!("\\{}{{{}}}", name, base));
+        } else if let Some((_, escaped)) = SYMBOLS.iter().find(|(symbol, _)| *symbol == c) {
+            result.push_str(escaped);
+        } else {
+            result.push(c);
+            if !unknown.contains(&c) {
+                unknown.push(c);
+            }
+        }
+    }
+    result
+}
+
+// Length in bytes of the command starting `input`, including the backslash.
+fn command_len(input: &str) -> usize {
+    let after = &input[1..];
+    match after.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            1 + after
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(after.len())
+        }
+        Some(c) => 1 + c.len_utf8(),
+        None => 1,
+    }
+}
+
+// Decode the accents and remove the brackets and the new lines of a value,
+// for the export formats expecting plain text.
+pub(crate) fn plain_text(value: &str) -> String {
+    decode(value)
+        .replace(['{', '}'], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Decode the command at the start of `input`, returning the decoded text and
+// the number of bytes consumed. Unknown commands are not decoded.
+fn decode_command(input: &str) -> Option<(String, usize)> {
+    let mut consumed = command_len(input);
+    let name = &input[1..consumed];
+    let is_control_word = name.starts_with(|c: char| c.is_ascii_alphabetic());
+
+    if let Some(&(_, letter)) = LETTERS.iter().find(|(n, _)| *n == name) {
+        // Like TeX, eat the spaces or the empty group after a control word.
+        let rest = &input[consumed..];
+        let trimmed = rest.trim_start();
+        consumed += rest.len() - trimmed.len();
+        if trimmed.starts_with("{}") {
+            consumed += 2;
+        }
+        return Some((letter.to_string(), consumed));
+    }
+
+    if let Some(accent) = ACCENTS.iter().find(|(n, _, _)| *n == name) {
+        let mut rest = &input[consumed..];
+        if is_control_word {
+            let trimmed = rest.trim_start();
+            consumed += rest.len() - trimmed.len();
+            rest = trimmed;
+        }
+        let (base, len) = accent_argument(rest)?;
+        return Some((compose(accent, base), consumed + len));
+    }
+
+    None
+}
+
+// Parse the letter an accent applies to. `None` means there is no letter,
+// as in `\~{}`.
+fn accent_argument(input: &str) -> Option<(Option<char>, usize)> {
+    let dotless = |s: &str| match s {
+        "\\i" => Some('i'),
+        "\\j" => Some('j'),
+        _ => None,
+    };
+
+    if input.starts_with('{') {
+        let end = matching_bracket(input)?;
+        let inner = &input[1..end];
+        let mut chars = inner.chars();
+        let base = match (chars.next(), chars.next()) {
+            (None, _) => None,
+            (Some(c), None) if c != '\\' => Some(c),
+            _ => Some(dotless(inner)?),
+        };
+        return Some((base, end + 1));
+    }
+
+    if let Some(c) = input.get(..2).and_then(dotless) {
+        if !input[2..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Some((Some(c), 2));
+        }
+    }
+
+    match input.chars().next() {
+        Some(c) if !c.is_whitespace() && c != '}' && c != '\\' => Some((Some(c), c.len_utf8())),
+        _ => None,
+    }
+}
+
+fn compose(accent: &Accent, base: Option<char>) -> String {
+    let (name, combining, letters) = accent;
+    match base {
+        Some(base) => match letters.iter().find(|(b, _)| *b == base) {
+            Some(&(_, composed)) => composed.to_string(),
+            None => format!("{}{}", base, combining),
+        },
+        None => match SPACING_ACCENTS.iter().find(|(n, _)| n == name) {
+            Some(&(_, spacing)) => spacing.to_string(),
+            None => combining.to_string(),
+        },
+    }
+}
+
+// Find the index of the bracket closing the one starting `input`.
+fn matching_bracket(input: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_accents() {
+        assert_eq!(decode("M\\\"uller"), "Müller");
+        assert_eq!(decode("K{\\\"o}rper"), "Körper");
+        assert_eq!(decode("Caf\\'e"), "Café");
+        assert_eq!(decode("Caf\\'{e}"), "Café");
+        assert_eq!(decode("Espa\\~na"), "España");
+        assert_eq!(decode("\\`a la"), "à la");
+        assert_eq!(decode("h\\^otel"), "hôtel");
+        assert_eq!(decode("Fran\\c{c}ais"), "Français");
+        assert_eq!(decode("Fran\\c cais"), "Français");
+        assert_eq!(decode("Dvo\\v{r}\\'ak"), "Dvořák");
+        assert_eq!(decode("Erd\\H{o}s"), "Erdős");
+        assert_eq!(decode("na\\\"{\\i}ve"), "naïve");
+        assert_eq!(decode("\\'€"), "€\u{301}");
+    }
+
+    #[test]
+    fn test_decode_letters() {
+        assert_eq!(decode("Stra\\ss e"), "Straße");
+        assert_eq!(decode("Stra{\\ss}e"), "Straße");
+        assert_eq!(decode("{\\AA}ngstr{\\\"o}m"), "Ångström");
+        assert_eq!(decode("Bj\\o rn"), "Bjørn");
+        assert_eq!(decode("{\\ae}on"), "æon");
+        assert_eq!(decode("\\L{}\\'od\\'z"), "Łódź");
+    }
+
+    #[test]
+    fn test_decode_keeps_other_commands() {
+        assert_eq!(decode("Tom \\& Jerry"), "Tom \\& Jerry");
+        assert_eq!(decode("50\\% off"), "50\\% off");
+        assert_eq!(decode("$\\'e = mc^2$"), "$\\'e = mc^2$");
+        assert_eq!(decode("\\emph{\\'e}"), "\\emph{é}");
+        assert_eq!(decode("{\\em Title}"), "{\\em Title}");
+        assert_eq!(decode("The {GNU} Project"), "The {GNU} Project");
+        assert_eq!(decode("\\~{}uno"), "~uno");
+        assert_eq!(decode("trailing \\"), "trailing \\");
+    }
+
+    #[test]
+    fn test_decode_unknown_letter_uses_combining_character() {
+        assert_eq!(decode("\\'x"), "x\u{301}");
+        assert_eq!(decode("\\d{h}"), "h\u{323}");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("Müller"), "M\\\"{u}ller");
+        assert_eq!(escape("Dvořák, Erdős"), "Dvo\\v{r}\\'{a}k, Erd\\H{o}s");
+        assert_eq!(escape("Łódź"), "{\\L}\\'{o}d\\'{z}");
+        assert_eq!(escape("Ångström"), "{\\AA}ngstr\\\"{o}m");
+        assert_eq!(
+            escape("Œuvre — ‘quoted’…"),
+            "{\\OE}uvre --- `quoted'{\\ldots}"
+        );
+        assert_eq!(escape("Çeşme ğ ı"), "\\c{C}e\\c{s}me \\u{g} {\\i}");
+        assert_eq!(escape("x\u{301}"), "\\'{x}");
+        assert_eq!(unescapable_chars("Ħ þ Ħ"), ['Ħ', 'þ']);
+    }
+
+    #[test]
+    fn test_escape_keeps_latex() {
+        assert_eq!(escape("K{\\\"o}rper"), "K{\\\"o}rper");
+        assert_eq!(escape("Tom \\& Jerry 50\\%"), "Tom \\& Jerry 50\\%");
+        assert_eq!(escape("$é$ é"), "$é$ \\'{e}");
+        assert_eq!(escape("\\$ é \\$"), "\\$ \\'{e} \\$");
+        assert_eq!(escape("The {GNU} Project"), "The {GNU} Project");
+    }
+
+    #[test]
+    fn test_escape_decodes_back() {
+        let value = "Gödel Straße Français Dvořák Łódź Ångström ĵ ķ ľ ŏ ų";
+        assert_eq!(decode(&escape(value)), value);
+        assert!(unescapable_chars(value).is_empty());
+    }
+}
@@ -40,31 +40,52 @@
 //!     let preambles = bibtex.preambles();
 //!     assert_eq!(preambles[0], "A bibtex preamble another test");
 //!
-//!     let comments = bibtex.comments();
-//!     assert_eq!(comments[0], "Here is a comment.");
-//!     assert_eq!(comments[1], "Another comment!");
+//!     assert_eq!(bibtex.comment_blocks()[0], "Here is a comment.");
+//!     assert_eq!(bibtex.comments()[0], "Another comment!");
 //!
 //!     let variables = bibtex.variables();
-//!     assert_eq!(variables[0], ("name".into(), "Charles Vandevoorde".into()));
-//!     assert_eq!(variables[1], ("github".into(), "https://github.com/charlesvdv".into()));
+//!     assert_eq!(variables["name"], "Charles Vandevoorde");
+//!     assert_eq!(variables["github"], "https://github.com/charlesvdv");
 //!
 //!     let biblio = &bibtex.bibliographies()[0];
 //!     assert_eq!(biblio.entry_type(), "misc");
 //!     assert_eq!(biblio.citation_key(), "my_citation_key");
 //!
 //!     let bib_tags = biblio.tags();
-//!     assert_eq!(bib_tags[0], ("author".into(), "Charles Vandevoorde".into()));
-//!     assert_eq!(bib_tags[1], ("title".into(), "nom-bibtex".into()));
-//!     assert_eq!(bib_tags[2], ("note".into(), "Github: https://github.com/charlesvdv".into()));
+//!     assert_eq!(bib_tags["author"], "Charles Vandevoorde");
+//!     assert_eq!(bib_tags["title"], "nom-bibtex");
+//!     assert_eq!(bib_tags["note"], "Github: https://github.com/charlesvdv");
 //! }
 //! ```
 //!
+//! ## Features
+//!
+//! - `serde`: implements `Serialize` and `Deserialize` for the model types.
+//!   Tags are serialized as a map which keeps their order.
+//! - `csl`: adds the `csl` module exporting bibliographies to *CSL-JSON*.
+//!
 extern crate nom;
 extern crate quick_error;
 
+#[cfg(feature = "csl")]
+pub mod csl;
+pub mod encoding;
 pub mod error;
+pub mod key;
+pub mod latex;
 pub mod model;
+pub mod name;
+pub mod options;
 mod parser;
+pub mod ris;
+pub mod stream;
+pub mod validation;
+pub mod warning;
 
-pub use model::{Bibliography, Bibtex};
+pub use model::{
+    BibDate, Bibliography, Bibtex, BibtexBuilder, DatePart, DedupPolicy, EntryRef, EntryType,
+    KeyValue, MergePolicy, Month, Pages, SourceLocation, StringValueType, TitlePart,
+};
+pub use name::Name;
 pub use parser::Entry;
+pub use validation::ValidationIssue;
@@ -1,14 +1,33 @@
-use crate::error::BibtexError;
+use crate::encoding::{self, Encoding};
+use crate::error::{BibtexError, VariableUse};
+use crate::key::{self, KeyScheme};
+use crate::latex;
+use crate::name::{self, Name};
+use crate::options::{Delimiter, DuplicatePolicy, ParseOptions, WriteOptions};
 use crate::parser;
-use crate::parser::{mkspan, Entry, Span};
-use nom::error::VerboseError;
-use std::collections::HashMap;
+use crate::parser::Entry;
+use crate::ris;
+use crate::stream::{self, LocatedEntry};
+use crate::validation::{self, ValidationIssue};
+use crate::warning::Warning;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::Path;
 use std::result;
+use std::slice;
 use std::str;
+use std::sync::OnceLock;
 
 type Result<T> = result::Result<T, BibtexError>;
 
-const TABLE_MONTHS: [(&'static str, &'static str); 12] = [
+const TABLE_MONTHS: [(&str, &str); 12] = [
     ("jan", "January"),
     ("feb", "February"),
     ("mar", "March"),
@@ -24,192 +43,3678 @@ const TABLE_MONTHS: [(&'static str, &'static str); 12] = [
 ];
 
 /// A high-level definition of a bibtex file.
-#[derive(Debug, PartialEq, Eq, Default)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Bibtex {
     comments: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    comment_blocks: Vec<String>,
     preambles: Vec<String>,
-    const_map: HashMap<&'static str, &'static str>,
     variables: HashMap<String, String>,
+    // Original spelling of the variable keys which are not lowercase.
+    #[cfg_attr(feature = "serde", serde(default))]
+    variable_keys: HashMap<String, String>,
+    // Values of the variables before their expansion, by lowercase key.
+    #[cfg_attr(feature = "serde", serde(default))]
+    raw_variables: HashMap<String, Vec<StringValueType>>,
     bibliographies: Vec<Bibliography>,
+    // Kind of the entries in the order they were read.
+    #[cfg_attr(feature = "serde", serde(default))]
+    order: Vec<Slot>,
+    // Position of the bibliographies by citation key, built on first lookup.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    index: OnceLock<HashMap<String, usize>>,
 }
 
+// An entry in the document order, the n-th slot of a kind being the n-th
+// element of the matching collection. Variables are referenced by key since
+// they are stored in a map.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum Slot {
+    Comment,
+    CommentBlock,
+    Preamble,
+    Variable(String),
+    Bibliography,
+}
+
+/// What `Bibtex::merge_with` does with the entries defined by both
+/// `Bibtex`.
+///
+/// The policy applies to the bibliographies sharing a citation key and to
+/// the string variables defined with different values.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MergePolicy {
+    /// Keep the entries of the merged into `Bibtex`.
+    KeepSelf,
+    /// Keep the entries of the other `Bibtex`.
+    KeepOther,
+    /// Fail with `BibtexError::DuplicateCitationKey` or
+    /// `BibtexError::ConflictingStringVariable`.
+    Error,
+}
+
+/// Which bibliography of a group of duplicates `Bibtex::dedup` keeps.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DedupPolicy {
+    /// Keep the first bibliography of the group.
+    KeepFirst,
+    /// Keep the last bibliography of the group.
+    KeepLast,
+    /// Keep the bibliography with the most tags, the first one on ties.
+    KeepMostTags,
+}
+
+/// A borrowed entry of a `Bibtex`, as yielded by `Bibtex::entries_in_order`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EntryRef<'a> {
+    /// Text outside of the entries.
+    Comment(&'a str),
+    /// The content of a `@comment` entry.
+    CommentBlock(&'a str),
+    Preamble(&'a str),
+    /// A string variable with its key and expanded value.
+    Variable(&'a str, &'a str),
+    Bibliography(&'a Bibliography),
+}
+
+// The index is a cache, the order and the raw variables are layout details,
+// they are not part of the value.
+impl PartialEq for Bibtex {
+    fn eq(&self, other: &Self) -> bool {
+        self.comments == other.comments
+            && self.comment_blocks == other.comment_blocks
+            && self.preambles == other.preambles
+            && self.variables == other.variables
+            && self.variable_keys == other.variable_keys
+            && self.bibliographies == other.bibliographies
+    }
+}
+
+impl Eq for Bibtex {}
+
 impl Bibtex {
     /// Create a new Bibtex instance from a *BibTeX* file content.
+    ///
+    /// Fails with `BibtexError::EmptyInput` when the content has no entry
+    /// at all. Text outside of entries is a comment, so only empty and
+    /// whitespace-only contents are rejected.
+    ///
+    /// Entries of any other type than `@string`, `@preamble` and `@comment`
+    /// are bibliographies, including the *BibLaTeX* `@set` and `@xdata`
+    /// entries. They are kept as written, see `Bibliography::entry_set` and
+    /// `Bibtex::resolve_xdata`.
     pub fn parse(bibtex: &str) -> Result<Self> {
-        let entries = Self::raw_parse(bibtex)?;
+        Self::parse_with_variables(bibtex, HashMap::new())
+    }
+
+    /// Create a new Bibtex instance from a UTF-8 file.
+    ///
+    /// A byte order mark at the start of the file is ignored. Errors are
+    /// given with the path of the file in `BibtexError::File`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_file_with_variables(path.as_ref(), HashMap::new())
+    }
+
+    /// Create a new Bibtex instance from several files, as if they were a
+    /// single file.
+    ///
+    /// The string variables defined by a file can be used by the following
+    /// ones.
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        let mut bibtex = Bibtex::default();
+        for path in paths {
+            let file = Self::from_file_with_variables(path.as_ref(), bibtex.variables.clone())?;
+            bibtex.merge(file);
+        }
+        Ok(bibtex)
+    }
+
+    fn from_file_with_variables(path: &Path, vars: HashMap<String, String>) -> Result<Self> {
+        let parse = || {
+            let content = fs::read_to_string(path)?;
+            let content = content.strip_prefix('\u{feff}').unwrap_or(&content);
+            Self::parse_with_variables(content, vars)
+        };
+        parse().map_err(|err| BibtexError::File {
+            path: path.into(),
+            err: Box::new(err),
+        })
+    }
+
+    /// Create a new Bibtex instance from a file content which may not be
+    /// UTF-8, also returning the encoding it was decoded from.
+    ///
+    /// See `encoding::decode` for the supported encodings.
+    ///
+    /// ```
+    /// use nom_bibtex::encoding::Encoding;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let (bibtex, encoding) = Bibtex::parse_bytes(b"@misc{ key, author = {M\xfcller} }").unwrap();
+    ///
+    /// assert_eq!(encoding, Encoding::Windows1252);
+    /// assert_eq!(bibtex.get("key").unwrap().get_tag("author"), Some("M\u{fc}ller"));
+    /// ```
+    pub fn parse_bytes(bibtex: &[u8]) -> Result<(Self, Encoding)> {
+        let (content, encoding) = encoding::decode(bibtex)?;
+        Ok((Self::parse(&content)?, encoding))
+    }
+
+    /// Create a new Bibtex instance from a file content in a known
+    /// encoding, such as `Encoding::Latin1` which is never detected by
+    /// `Bibtex::parse_bytes`.
+    ///
+    /// Fails with `BibtexError::Encoding` when the content is not valid in
+    /// this encoding, see `encoding::decode_as`.
+    ///
+    /// ```
+    /// use nom_bibtex::encoding::Encoding;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse_bytes_as(b"@misc{ key, author = {G\xf6del} }", Encoding::Latin1)
+    ///     .unwrap();
+    /// assert_eq!(bibtex.get("key").unwrap().get_tag("author"), Some("G\u{f6}del"));
+    ///
+    /// assert!(Bibtex::parse_bytes_as(b"@misc{ key, author = {G\xf6del} }", Encoding::Utf8).is_err());
+    /// ```
+    pub fn parse_bytes_as(bibtex: &[u8], encoding: Encoding) -> Result<Self> {
+        Self::parse(&encoding::decode_as(bibtex, encoding)?)
+    }
+
+    /// Create a new Bibtex instance, failing on duplicated citation keys.
+    ///
+    /// As in *BibTeX*, citation keys are case-sensitive.
+    pub fn parse_strict(bibtex: &str) -> Result<Self> {
+        Self::parse_with(
+            bibtex,
+            &ParseOptions::new().duplicate_citation_keys(DuplicatePolicy::Error),
+        )
+    }
+
+    /// Create a new Bibtex instance with the given options.
+    pub fn parse_with(bibtex: &str, options: &ParseOptions) -> Result<Self> {
+        Ok(Self::parse_verbose(bibtex, options)?.0)
+    }
+
+    /// Create a new Bibtex instance with the given options, also returning
+    /// the warnings found while parsing in the order of the input.
+    ///
+    /// ```
+    /// use nom_bibtex::options::{DuplicatePolicy, ParseOptions};
+    /// use nom_bibtex::warning::Warning;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let options = ParseOptions::new().duplicate_citation_keys(DuplicatePolicy::Warn);
+    /// let (bibtex, warnings) = Bibtex::parse_verbose(
+    ///     "@misc{ key, title = {First} }
+    ///     @misc{ key, title = {Second} }",
+    ///     &options,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(bibtex.bibliographies().len(), 2);
+    /// assert!(matches!(
+    ///     &warnings[..],
+    ///     [Warning::DuplicateCitationKey { first, duplicate, .. }]
+    ///         if first.line == 1 && duplicate.line == 2
+    /// ));
+    /// ```
+    pub fn parse_verbose(bibtex: &str, options: &ParseOptions) -> Result<(Self, Vec<Warning>)> {
+        let mut warnings = vec![];
+        let bibtex = Self::parse_collecting(bibtex, options, &mut warnings)?;
+        Ok((bibtex, warnings))
+    }
+
+    // Parse as `Bibtex::parse_verbose`, adding the warnings to `warnings`
+    // even when parsing fails.
+    fn parse_collecting(
+        bibtex: &str,
+        options: &ParseOptions,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Self> {
+        let entries = Self::read_entries(bibtex, options, warnings)?;
+        let mut const_map = Self::constants();
+        const_map.extend(options.constants.clone());
+        let mut bibtex = Self::from_entries(entries, HashMap::new(), &const_map, None)?;
+        warnings.extend(bibtex.apply_duplicate_policy(options.duplicates)?);
+        warnings.extend(bibtex.tag_warnings());
+        warnings.sort_by_key(|warning| warning.location().offset);
+        if options.normalize_whitespace {
+            let verbatim_fields: Vec<&str> =
+                options.verbatim_fields.iter().map(String::as_str).collect();
+            bibtex.normalize_whitespace(&verbatim_fields);
+        }
+        if options.decode_latex {
+            bibtex.decode_latex();
+        }
+        Ok(bibtex)
+    }
+
+    /// Create a new Bibtex instance as `Bibtex::parse`, also returning the
+    /// warnings found while parsing, see `Bibtex::parse_verbose`.
+    ///
+    /// When parsing fails, the warnings found before the failure are
+    /// returned along with the error.
+    ///
+    /// ```
+    /// use nom_bibtex::warning::Warning;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let (bibtex, warnings) = Bibtex::parse_with_warnings(
+    ///     "@misc{ key, title = {}, month = {Smarch} }",
+    /// );
+    ///
+    /// assert!(bibtex.is_ok());
+    /// assert_eq!(
+    ///     warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(),
+    ///     [
+    ///         "Empty tag title in key at line 1",
+    ///         "Unknown month Smarch in key at line 1",
+    ///     ]
+    /// );
+    /// ```
+    pub fn parse_with_warnings(bibtex: &str) -> (Result<Self>, Vec<Warning>) {
+        let mut warnings = vec![];
+        let bibtex = Self::parse_collecting(bibtex, &ParseOptions::default(), &mut warnings);
+        warnings.sort_by_key(|warning| warning.location().offset);
+        (bibtex, warnings)
+    }
+
+    // Report the tags defined several times in a bibliography, once per
+    // repeated key.
+    fn duplicate_field_warnings(entries: &[LocatedEntry]) -> Vec<Warning> {
+        let mut warnings = vec![];
+        for (location, _, entry) in entries {
+            if let Entry::Bibliography(_, citation_key, tags) = entry {
+                let mut keys = HashSet::new();
+                let mut duplicates = HashSet::new();
+                for tag in tags {
+                    let key = tag.key.to_lowercase();
+                    if !keys.insert(key.clone()) && duplicates.insert(key.clone()) {
+                        warnings.push(Warning::DuplicateField {
+                            citation_key: citation_key.clone(),
+                            key,
+                            location: *location,
+                        });
+                    }
+                }
+            }
+        }
+        warnings
+    }
+
+    // Report the empty tags and the unknown months, in the order of the
+    // bibliographies.
+    fn tag_warnings(&self) -> Vec<Warning> {
+        let mut warnings = vec![];
+        for biblio in &self.bibliographies {
+            let location = match biblio.location {
+                Some(location) => location,
+                None => continue,
+            };
+            for (key, value) in biblio.iter_tags() {
+                if value.trim().is_empty() {
+                    warnings.push(Warning::EmptyTag {
+                        citation_key: biblio.citation_key.clone(),
+                        key: key.into(),
+                        location,
+                    });
+                } else if key == "month" && Month::parse(value).is_none() {
+                    warnings.push(Warning::UnknownMonth {
+                        citation_key: biblio.citation_key.clone(),
+                        value: value.into(),
+                        location,
+                    });
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Create a new Bibtex instance with some predefined string variables.
+    ///
+    /// Predefined variables are used to expand abbreviations as if they
+    /// were defined with `@string` but are overridden by the `@string`
+    /// entries of the file.
+    pub fn parse_with_variables(bibtex: &str, vars: HashMap<String, String>) -> Result<Self> {
+        Self::from_entries(
+            Self::read_entries(bibtex, &ParseOptions::default(), &mut vec![])?,
+            vars,
+            &Self::constants(),
+            None,
+        )
+    }
+
+    /// Create a new Bibtex instance with some constants in addition to the
+    /// months.
+    ///
+    /// Constants are used to expand the abbreviations which are not string
+    /// variables, and override the months with the same name. This is the
+    /// same as `Bibtex::parse_with` with a `ParseOptions::constant` for each
+    /// constant.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    /// use std::collections::HashMap;
+    ///
+    /// let constants = HashMap::from([("prl", "Physical Review Letters")]);
+    /// let bibtex = Bibtex::parse_with_constants(
+    ///     "@article{ key, journal = prl, month = jan }",
+    ///     &constants,
+    /// )
+    /// .unwrap();
+    ///
+    /// let biblio = bibtex.get("key").unwrap();
+    /// assert_eq!(biblio.get_tag("journal"), Some("Physical Review Letters"));
+    /// assert_eq!(biblio.get_tag("month"), Some("January"));
+    /// ```
+    pub fn parse_with_constants(bibtex: &str, constants: &HashMap<&str, &str>) -> Result<Self> {
+        let options = constants
+            .iter()
+            .fold(ParseOptions::new(), |options, (name, value)| {
+                options.constant(name, value)
+            });
+        Self::parse_with(bibtex, &options)
+    }
+
+    // Parse all the entries, failing on the first invalid one. The warnings
+    // about the entries read before it are added to `warnings`.
+    fn read_entries(
+        bibtex: &str,
+        options: &ParseOptions,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Vec<LocatedEntry>> {
+        let mut entries = stream::Entries::new(bibtex)
+            .lenient_comments(options.lenient_comments)
+            .lenient_commas(options.lenient_commas)
+            .max_nesting_depth(options.max_nesting_depth);
+        let mut located = vec![];
+        let mut failure = None;
+        while let Some(entry) = entries.next_located() {
+            match entry {
+                Ok(entry) => located.push(entry),
+                Err(err) => failure = Some(err),
+            }
+        }
+        warnings.extend(entries.take_warnings());
+        warnings.extend(Self::duplicate_field_warnings(&located));
+        match failure {
+            Some(err) => Err(err),
+            None if located.is_empty() => Err(BibtexError::EmptyInput),
+            None => Ok(located),
+        }
+    }
+
+    /// Create a new Bibtex instance, skipping the entries which cannot be
+    /// parsed instead of failing.
+    ///
+    /// After an invalid entry, parsing resumes at the next line starting
+    /// with a `@`. Unknown string variables are kept as written. The parsing
+    /// errors are returned first, followed by the unknown variables. An
+    /// empty content gives `BibtexError::EmptyInput` as with `Bibtex::parse`.
+    ///
+    /// Unclosed brackets, unbalanced braces, invalid field names and empty
+    /// citation keys keep their own error, the other invalid entries giving
+    /// `BibtexError::InvalidEntry`.
+    ///
+    /// ```
+    /// use nom_bibtex::error::{BibtexError, VariableUse};
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let (bibtex, errors) = Bibtex::parse_lossy(
+    ///     "@misc{ first, title = oops }
+    ///     @misc{ second, title = = }
+    ///     @misc{ third, title = {Title} }",
+    /// );
+    ///
+    /// assert_eq!(bibtex.bibliographies().len(), 2);
+    /// assert_eq!(bibtex.get("first").unwrap().get_tag("title"), Some("oops"));
+    /// assert_eq!(
+    ///     errors,
+    ///     vec![
+    ///         BibtexError::InvalidEntry {
+    ///             entry: "second".into(),
+    ///             line: 2,
+    ///             column: 5
+    ///         },
+    ///         BibtexError::StringVariableNotFound {
+    ///             var: "oops".into(),
+    ///             used_in: VariableUse::Tag {
+    ///                 citation_key: "first".into(),
+    ///                 field: "title".into()
+    ///             }
+    ///         },
+    ///     ]
+    /// );
+    /// ```
+    pub fn parse_lossy(bibtex: &str) -> (Self, Vec<BibtexError>) {
+        Self::parse_lossy_with(bibtex, &ParseOptions::default())
+    }
+
+    /// Create a new Bibtex instance with the given options, skipping the
+    /// entries which cannot be parsed as `Bibtex::parse_lossy`.
+    ///
+    /// Unknown string variables are kept as written, unless they are
+    /// defined with `ParseOptions::constant`. With
+    /// `DuplicatePolicy::Error`, the first duplicated citation key is
+    /// reported after the other errors and all the bibliographies are kept.
+    ///
+    /// ```
+    /// use nom_bibtex::error::{BibtexError, VariableUse};
+    /// use nom_bibtex::options::ParseOptions;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let options = ParseOptions::new().constant("jacm", "Journal of the ACM");
+    /// let (bibtex, errors) = Bibtex::parse_lossy_with(
+    ///     "@article{ first, journal = jacm }
+    ///     @article{ second, journal = tocs }",
+    ///     &options,
+    /// );
+    ///
+    /// let journals = bibtex
+    ///     .bibliographies()
+    ///     .iter()
+    ///     .map(|biblio| biblio.get_tag("journal").unwrap())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(journals, ["Journal of the ACM", "tocs"]);
+    /// assert_eq!(
+    ///     errors,
+    ///     vec![BibtexError::StringVariableNotFound {
+    ///         var: "tocs".into(),
+    ///         used_in: VariableUse::Tag {
+    ///             citation_key: "second".into(),
+    ///             field: "journal".into()
+    ///         }
+    ///     }]
+    /// );
+    /// ```
+    pub fn parse_lossy_with(bibtex: &str, options: &ParseOptions) -> (Self, Vec<BibtexError>) {
+        let mut entries = stream::Entries::new(bibtex)
+            .lenient_comments(options.lenient_comments)
+            .lenient_commas(options.lenient_commas)
+            .max_nesting_depth(options.max_nesting_depth);
+        let mut raw_entries = vec![];
+        let mut errors = vec![];
+        loop {
+            match entries.next_located() {
+                Some(Ok(entry)) => raw_entries.push(entry),
+                Some(Err(err)) => {
+                    let entry = entries.skip_failed_entry();
+                    errors.push(match err {
+                        BibtexError::UnclosedBracket { .. }
+                        | BibtexError::InvalidFieldName { .. }
+                        | BibtexError::UnbalancedBraces { .. }
+                        | BibtexError::EmptyCitationKey { .. }
+                        | BibtexError::NestingTooDeep { .. } => err,
+                        _ => BibtexError::InvalidEntry {
+                            entry: parser::entry_name(entry.fragment()),
+                            line: entry.location_line(),
+                            column: entry.get_utf8_column(),
+                        },
+                    });
+                }
+                None => break,
+            }
+        }
+        if raw_entries.is_empty() && errors.is_empty() {
+            errors.push(BibtexError::EmptyInput);
+        }
+
+        let mut const_map = Self::constants();
+        const_map.extend(options.constants.clone());
+        let mut bibtex =
+            match Self::from_entries(raw_entries, HashMap::new(), &const_map, Some(&mut errors)) {
+                Ok(bibtex) => bibtex,
+                Err(err) => {
+                    errors.push(err);
+                    Bibtex::default()
+                }
+            };
+        if let Err(err) = bibtex.apply_duplicate_policy(options.duplicates) {
+            errors.push(err);
+        }
+        if options.normalize_whitespace {
+            let verbatim_fields: Vec<&str> =
+                options.verbatim_fields.iter().map(String::as_str).collect();
+            bibtex.normalize_whitespace(&verbatim_fields);
+        }
+        if options.decode_latex {
+            bibtex.decode_latex();
+        }
+        (bibtex, errors)
+    }
+
+    // Drop or report the bibliographies whose citation key is already used.
+    fn apply_duplicate_policy(&mut self, policy: DuplicatePolicy) -> Result<Vec<Warning>> {
+        if policy == DuplicatePolicy::Allow {
+            return Ok(vec![]);
+        }
+
+        let mut warnings = vec![];
+        let mut kept = vec![true; self.bibliographies.len()];
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for (i, biblio) in self.bibliographies.iter().enumerate() {
+            let first = match seen.get(biblio.citation_key.as_str()) {
+                Some(&first) => first,
+                None => {
+                    seen.insert(&biblio.citation_key, i);
+                    continue;
+                }
+            };
+            match policy {
+                DuplicatePolicy::Allow => {}
+                DuplicatePolicy::Error => {
+                    return Err(BibtexError::DuplicateCitationKey {
+                        key: biblio.citation_key.clone(),
+                        first: self.bibliographies[first].location,
+                        duplicate: biblio.location,
+                    })
+                }
+                DuplicatePolicy::KeepFirst => kept[i] = false,
+                DuplicatePolicy::KeepLast => {
+                    kept[first] = false;
+                    seen.insert(&biblio.citation_key, i);
+                }
+                DuplicatePolicy::Warn => {
+                    if let (Some(first), Some(duplicate)) =
+                        (self.bibliographies[first].location, biblio.location)
+                    {
+                        warnings.push(Warning::DuplicateCitationKey {
+                            citation_key: biblio.citation_key.clone(),
+                            first,
+                            duplicate,
+                        });
+                    }
+                }
+            }
+        }
+
+        if kept.contains(&false) {
+            let mut bibliographies = kept.iter();
+            self.order.retain(|slot| match slot {
+                Slot::Bibliography => *bibliographies.next().unwrap_or(&true),
+                _ => true,
+            });
+            let mut kept = kept.into_iter();
+            self.bibliographies.retain(|_| kept.next().unwrap_or(true));
+            self.index = OnceLock::new();
+        }
+        Ok(warnings)
+    }
+
+    // Build a Bibtex from the raw entries. In lossy mode, unknown variables
+    // are added to the errors instead of failing.
+    fn from_entries(
+        entries: Vec<LocatedEntry>,
+        vars: HashMap<String, String>,
+        const_map: &HashMap<String, String>,
+        mut errors: Option<&mut Vec<BibtexError>>,
+    ) -> Result<Self> {
+        let mut bibtex = Bibtex::default();
+        for (key, value) in vars {
+            let key = bibtex.variable_key(key);
+            bibtex.variables.insert(key, value);
+        }
+
+        Self::fill_variables(&mut bibtex, &entries, const_map, errors.as_deref_mut())?;
+
+        let mut seen_variables = HashSet::new();
+        for (location, end, entry) in entries {
+            match entry {
+                Entry::Variable(v) => {
+                    // Already expanded, only the first definition is kept in
+                    // the order.
+                    let key = v.key.to_lowercase();
+                    bibtex.raw_variables.insert(key.clone(), v.value);
+                    if seen_variables.insert(key.clone()) {
+                        bibtex.variable_keys.remove(&key);
+                        bibtex.variable_key(v.key);
+                        bibtex.order.push(Slot::Variable(key));
+                    }
+                }
+                Entry::Comment(v) => {
+                    bibtex.comments.push(v);
+                    bibtex.order.push(Slot::Comment);
+                }
+                Entry::CommentBlock(v) => {
+                    bibtex.comment_blocks.push(v);
+                    bibtex.order.push(Slot::CommentBlock);
+                }
+                Entry::Preamble(v) => {
+                    let number = bibtex.preambles.len() + 1;
+                    let new_val = Self::expand_str_abbreviations(
+                        v,
+                        &bibtex.variables,
+                        const_map,
+                        || VariableUse::Preamble(number),
+                        errors.as_deref_mut(),
+                    )?;
+                    bibtex.preambles.push(new_val);
+                    bibtex.order.push(Slot::Preamble);
+                }
+                Entry::Bibliography(entry_t, citation_key, tags) => {
+                    let raw_tags = tags
+                        .iter()
+                        .map(|tag| (tag.key.to_lowercase(), tag.value.clone()))
+                        .collect();
+                    let mut new_tags = Vec::with_capacity(tags.len());
+                    for tag in tags {
+                        let value = Self::expand_str_abbreviations(
+                            tag.value,
+                            &bibtex.variables,
+                            const_map,
+                            || VariableUse::Tag {
+                                citation_key: citation_key.clone(),
+                                field: tag.key.to_lowercase(),
+                            },
+                            errors.as_deref_mut(),
+                        )?;
+                        new_tags.push((tag.key, value));
+                    }
+                    let mut biblio = Bibliography::new(entry_t, citation_key, new_tags);
+                    biblio.location = Some(location);
+                    biblio.end = Some(end);
+                    biblio.raw_tags = raw_tags;
+                    bibtex.bibliographies.push(biblio);
+                    bibtex.order.push(Slot::Bibliography);
+                }
+            }
+        }
+        Ok(bibtex)
+    }
+
+    /// Iterate over the raw entries of a file, parsing them one at a time.
+    ///
+    /// The iteration stops after the first error. See `stream::ExpandingIter`
+    /// to expand the string variables of the entries.
+    pub fn iter_entries(bibtex: &str) -> stream::Entries<'_> {
+        stream::Entries::new(bibtex)
+    }
+
+    /// Iterate over the raw entries of a file as `Bibtex::iter_entries`,
+    /// with the lenient comments and the nesting depth of the options.
+    ///
+    /// ```
+    /// use nom_bibtex::error::BibtexError;
+    /// use nom_bibtex::options::ParseOptions;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let options = ParseOptions::new().max_nesting_depth(1);
+    /// let mut entries = Bibtex::iter_entries_with("@misc{ key, title = {{{T}}} }", &options);
+    /// assert!(matches!(
+    ///     entries.next(),
+    ///     Some(Err(BibtexError::NestingTooDeep { max_depth: 1, .. }))
+    /// ));
+    /// ```
+    pub fn iter_entries_with<'a>(bibtex: &'a str, options: &ParseOptions) -> stream::Entries<'a> {
+        stream::Entries::new(bibtex)
+            .lenient_comments(options.lenient_comments)
+            .max_nesting_depth(options.max_nesting_depth)
+    }
+
+    /// Iterate over the raw entries read from a reader, parsing them one at
+    /// a time without reading the whole input first.
+    ///
+    /// The iteration stops after the first error, reading errors and input
+    /// which is not UTF-8 being reported as `BibtexError::Io`. See
+    /// `stream::ExpandingIter` to expand the string variables of the entries.
+    ///
+    /// ```
+    /// use nom_bibtex::stream::{ExpandedEntry, ExpandingIter};
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let file: &[u8] = b"@string{ name = {Name} }
+    ///     @misc{ key, author = name }";
+    ///
+    /// let entries = ExpandingIter::new(Bibtex::parse_reader(file))
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert!(matches!(
+    ///     &entries[1],
+    ///     ExpandedEntry::Bibliography(b) if b.get_tag("author") == Some("Name")
+    /// ));
+    /// ```
+    pub fn parse_reader<R: Read>(reader: R) -> stream::ReaderEntries<R> {
+        stream::ReaderEntries::new(reader)
+    }
+
+    /// Iterate over the raw entries read from a reader as
+    /// `Bibtex::parse_reader`, with the lenient comments and the nesting
+    /// depth of the options.
+    pub fn parse_reader_with<R: Read>(
+        reader: R,
+        options: &ParseOptions,
+    ) -> stream::ReaderEntries<R> {
+        stream::ReaderEntries::new(reader)
+            .lenient_comments(options.lenient_comments)
+            .max_nesting_depth(options.max_nesting_depth)
+    }
+
+    /// Get a raw vector of entries in order from the files.
+    ///
+    /// See `Entry` for the values before the expansion of the variables.
+    /// As when parsing, the brackets cannot be nested deeper than
+    /// `options::DEFAULT_MAX_NESTING_DEPTH`.
+    pub fn raw_parse(bibtex: &str) -> Result<Vec<Entry>> {
+        Self::iter_entries(bibtex).collect()
+    }
+
+    /// Get preambles with expanded variables.
+    pub fn preambles(&self) -> &[String] {
+        &self.preambles
+    }
+
+    /// Get the comments written as text outside of the entries.
+    ///
+    /// Each line starting with a `%` is a comment of its own, kept with its
+    /// `%`, even when it contains a `@`.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Get the contents of the `@comment` entries.
+    pub fn comment_blocks(&self) -> &[String] {
+        &self.comment_blocks
+    }
+
+    /// Get string variables with a tuple of key and expanded value.
+    ///
+    /// Keys are spelled as in their first definition, though variables are
+    /// case-insensitive. See `Bibtex::iter_variables` to avoid copying them.
+    pub fn variables(&self) -> HashMap<String, String> {
+        self.iter_variables()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect()
+    }
+
+    /// Iterate over the string variables with their expanded value, in no
+    /// particular order.
+    ///
+    /// As with `Bibtex::variables`, keys are spelled as in their first
+    /// definition.
+    pub fn iter_variables(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables
+            .iter()
+            .map(|(k, v)| (self.original_variable_key(k), v.as_str()))
+    }
+
+    /// Get the expanded value of a string variable, without copying the
+    /// variables as `Bibtex::variables` does.
+    ///
+    /// As abbreviations, the key is case-insensitive.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse("@string{ JACM = {Journal of the ACM} }").unwrap();
+    ///
+    /// assert_eq!(bibtex.variable("jacm"), Some("Journal of the ACM"));
+    /// assert_eq!(bibtex.variable("acm"), None);
+    /// ```
+    pub fn variable(&self, key: &str) -> Option<&str> {
+        self.variables.get(&key.to_lowercase()).map(String::as_str)
+    }
+
+    // Store the spelling of a variable key which is not lowercase, unless
+    // one is already known, and return the lowercase key.
+    fn variable_key(&mut self, key: String) -> String {
+        let normalized = key.to_lowercase();
+        if normalized != key {
+            self.variable_keys.entry(normalized.clone()).or_insert(key);
+        }
+        normalized
+    }
+
+    fn original_variable_key<'a>(&'a self, key: &'a str) -> &'a str {
+        self.variable_keys.get(key).map_or(key, String::as_str)
+    }
+
+    /// Get bibliographies entry with variables expanded.
+    pub fn bibliographies(&self) -> &Vec<Bibliography> {
+        &self.bibliographies
+    }
+
+    /// Iterate over the bibliographies, as `&bibtex` does in a `for` loop.
+    pub fn iter(&self) -> slice::Iter<'_, Bibliography> {
+        self.bibliographies.iter()
+    }
+
+    /// Whether there is no entry at all, neither bibliography, string
+    /// variable, preamble nor comment.
+    pub fn is_empty(&self) -> bool {
+        self.bibliographies.is_empty()
+            && self.variables.is_empty()
+            && self.preambles.is_empty()
+            && self.comments.is_empty()
+            && self.comment_blocks.is_empty()
+    }
+
+    /// Iterate over the entries in the order they were read.
+    ///
+    /// A string variable defined several times is only yielded at its first
+    /// definition, with its last value. Predefined variables are not yielded.
+    ///
+    /// ```
+    /// use nom_bibtex::{Bibtex, EntryRef};
+    ///
+    /// let bibtex = Bibtex::parse(
+    ///     "@comment{Generated}
+    ///     @misc{ key, title = {Title} }
+    ///     @string{ name = {Value} }",
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut entries = bibtex.entries_in_order();
+    /// assert_eq!(entries.next(), Some(EntryRef::CommentBlock("Generated")));
+    /// assert!(matches!(entries.next(), Some(EntryRef::Bibliography(_))));
+    /// assert_eq!(entries.next(), Some(EntryRef::Variable("name", "Value")));
+    /// assert_eq!(entries.next(), None);
+    /// ```
+    pub fn entries_in_order(&self) -> impl Iterator<Item = EntryRef<'_>> {
+        let mut comments = self.comments.iter();
+        let mut comment_blocks = self.comment_blocks.iter();
+        let mut preambles = self.preambles.iter();
+        let mut bibliographies = self.bibliographies.iter();
+        self.order.iter().filter_map(move |slot| match slot {
+            Slot::Comment => comments.next().map(|v| EntryRef::Comment(v)),
+            Slot::CommentBlock => comment_blocks.next().map(|v| EntryRef::CommentBlock(v)),
+            Slot::Preamble => preambles.next().map(|v| EntryRef::Preamble(v)),
+            Slot::Variable(key) => self
+                .variables
+                .get(key)
+                .map(|v| EntryRef::Variable(self.original_variable_key(key), v)),
+            Slot::Bibliography => bibliographies.next().map(EntryRef::Bibliography),
+        })
+    }
+
+    /// Get the number of bibliographies.
+    pub fn entry_count(&self) -> usize {
+        self.bibliographies.len()
+    }
+
+    /// Get the number of bibliographies of each entry type, by lowercase
+    /// entry type.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse(
+    ///     "@article{ a, year = 2021 }
+    ///     @Article{ b, year = 2020 }
+    ///     @book{ c, year = 2021 }",
+    /// )
+    /// .unwrap();
+    ///
+    /// let counts = bibtex.entry_type_counts();
+    /// assert_eq!(bibtex.entry_count(), 3);
+    /// assert_eq!(counts["article"], 2);
+    /// assert_eq!(counts["book"], 1);
+    /// ```
+    pub fn entry_type_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for biblio in &self.bibliographies {
+            *counts.entry(biblio.entry_type.to_lowercase()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Get the number of string variables.
+    pub fn variable_count(&self) -> usize {
+        self.variables.len()
+    }
+
+    /// Iterate over the bibliographies having a keyword, ignoring case.
+    ///
+    /// See `Bibliography::keywords`.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse(
+    ///     "@misc{ a, keywords = {Parsing; BibTeX} }
+    ///     @misc{ b, keywords = {bibtex, {machine learning, deep}} }
+    ///     @misc{ c, title = {No keywords} }",
+    /// )
+    /// .unwrap();
+    ///
+    /// let keys = bibtex
+    ///     .entries_with_keyword("bibtex")
+    ///     .map(|b| b.citation_key())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(keys, ["a", "b"]);
+    /// assert_eq!(bibtex.entries_with_keyword("machine learning, deep").count(), 1);
+    /// ```
+    pub fn entries_with_keyword<'a>(
+        &'a self,
+        keyword: &'a str,
+    ) -> impl Iterator<Item = &'a Bibliography> {
+        let keyword = keyword.trim().to_lowercase();
+        self.bibliographies
+            .iter()
+            .filter(move |b| b.keywords().iter().any(|k| k.to_lowercase() == keyword))
+    }
+
+    /// Iterate over the bibliographies of an entry type, ignoring case.
+    pub fn by_entry_type<'a>(
+        &'a self,
+        entry_type: &'a str,
+    ) -> impl Iterator<Item = &'a Bibliography> {
+        self.bibliographies
+            .iter()
+            .filter(move |b| b.entry_type.eq_ignore_ascii_case(entry_type))
+    }
+
+    /// Iterate over the bibliographies matching a predicate.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse(
+    ///     "@article{ a, year = 2021 }
+    ///     @Article{ b, year = 2020 }
+    ///     @book{ c, year = 2021 }",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(bibtex.by_entry_type("ARTICLE").count(), 2);
+    /// let keys = bibtex
+    ///     .filter(|b| b.year() == Some(2021))
+    ///     .map(|b| b.citation_key())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(keys, ["a", "c"]);
+    /// ```
+    pub fn filter<F>(&self, predicate: F) -> impl Iterator<Item = &Bibliography>
+    where
+        F: Fn(&Bibliography) -> bool,
+    {
+        self.bibliographies.iter().filter(move |b| predicate(b))
+    }
+
+    /// Only keep the bibliographies matching a predicate.
+    ///
+    /// The other entries are kept, including the string variables which are
+    /// no longer used.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let mut bibtex = Bibtex::parse(
+    ///     "@string{ name = {Name} }
+    ///     @misc{ cited, author = name }
+    ///     @misc{ uncited, author = name }",
+    /// )
+    /// .unwrap();
+    ///
+    /// bibtex.retain(|b| b.citation_key() == "cited");
+    /// assert_eq!(bibtex.bibliographies().len(), 1);
+    /// assert!(bibtex.get("uncited").is_none());
+    /// assert_eq!(bibtex.variables()["name"], "Name");
+    /// ```
+    pub fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&Bibliography) -> bool,
+    {
+        let removed = self
+            .bibliographies
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| !predicate(b))
+            .map(|(i, _)| i)
+            .collect();
+        self.remove_indices(&removed);
+    }
+
+    /// Copy the bibliographies matching a predicate, as `Bibtex::retain`,
+    /// with all the other entries.
+    pub fn filtered<F>(&self, predicate: F) -> Bibtex
+    where
+        F: FnMut(&Bibliography) -> bool,
+    {
+        let mut bibtex = Bibtex {
+            comments: self.comments.clone(),
+            comment_blocks: self.comment_blocks.clone(),
+            preambles: self.preambles.clone(),
+            variables: self.variables.clone(),
+            variable_keys: self.variable_keys.clone(),
+            raw_variables: self.raw_variables.clone(),
+            bibliographies: self.bibliographies.clone(),
+            order: self.order.clone(),
+            index: OnceLock::new(),
+        };
+        bibtex.retain(predicate);
+        bibtex
+    }
+
+    /// Get a bibliography by its citation key.
+    ///
+    /// Lookups use an index built on the first call. When several
+    /// bibliographies share the same citation key, the first one is returned.
+    pub fn get(&self, citation_key: &str) -> Option<&Bibliography> {
+        let i = *self.index().get(citation_key)?;
+        Some(&self.bibliographies[i])
+    }
+
+    /// Get a mutable bibliography by its citation key.
+    ///
+    /// As with `Bibtex::get`, the first bibliography with this citation key
+    /// is returned.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let mut bibtex = Bibtex::parse(
+    ///     "@misc{ first, title = {Teh title} }
+    ///     @misc{ second, title = {Another title} }",
+    /// )
+    /// .unwrap();
+    ///
+    /// bibtex
+    ///     .get_mut("first")
+    ///     .unwrap()
+    ///     .insert_tag("title", "The title".into())
+    ///     .unwrap();
+    /// bibtex.remove_bibliography("second");
+    ///
+    /// assert_eq!(bibtex.bibliographies().len(), 1);
+    /// assert_eq!(bibtex.get("first").unwrap().get_tag("title"), Some("The title"));
+    /// ```
+    pub fn get_mut(&mut self, citation_key: &str) -> Option<&mut Bibliography> {
+        let i = *self.index().get(citation_key)?;
+        // The citation key can be changed through the returned reference.
+        self.index = OnceLock::new();
+        Some(&mut self.bibliographies[i])
+    }
+
+    /// Add a bibliography after the existing ones.
+    pub fn push_bibliography(&mut self, bibliography: Bibliography) {
+        self.bibliographies.push(bibliography);
+        self.order.push(Slot::Bibliography);
+        self.index = OnceLock::new();
+    }
+
+    /// Remove the first bibliography with the given citation key.
+    pub fn remove_bibliography(&mut self, citation_key: &str) -> Option<Bibliography> {
+        let i = self
+            .bibliographies
+            .iter()
+            .position(|b| b.citation_key == citation_key)?;
+        if let Some(slot) = self
+            .order
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| **slot == Slot::Bibliography)
+            .nth(i)
+            .map(|(slot, _)| slot)
+        {
+            self.order.remove(slot);
+        }
+        self.index = OnceLock::new();
+        Some(self.bibliographies.remove(i))
+    }
+
+    /// Group the bibliographies which are likely the same work under
+    /// several citation keys.
+    ///
+    /// Bibliographies are the same when they have the same DOI, as given by
+    /// `Bibliography::doi` and ignoring case. A bibliography without DOI is
+    /// the same as the first one with an identical title, compared
+    /// lowercase without brackets and with the runs of whitespaces collapsed.
+    /// Bibliographies with different DOIs are never grouped.
+    ///
+    /// Only the groups of several bibliographies are returned, in the order
+    /// of their first bibliography.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse(
+    ///     "@article{ smith2020, title = {A {Study}}, doi = {10.1000/XYZ} }
+    ///     @article{ Smith20, title = {A study}, doi = {https://doi.org/10.1000/xyz} }
+    ///     @misc{ copy, title = {a   study} }
+    ///     @misc{ other, title = {Another study} }",
+    /// )
+    /// .unwrap();
+    ///
+    /// let groups = bibtex.find_duplicates();
+    /// assert_eq!(groups.len(), 1);
+    /// let keys = groups[0].iter().map(|b| b.citation_key()).collect::<Vec<_>>();
+    /// assert_eq!(keys, ["smith2020", "Smith20", "copy"]);
+    /// ```
+    pub fn find_duplicates(&self) -> Vec<Vec<&Bibliography>> {
+        self.duplicate_groups()
+            .into_iter()
+            .map(|group| group.into_iter().map(|i| &self.bibliographies[i]).collect())
+            .collect()
+    }
+
+    /// Remove all the bibliographies but one of each group found by
+    /// `Bibtex::find_duplicates`, returning the removed ones.
+    pub fn dedup(&mut self, policy: DedupPolicy) -> Vec<Bibliography> {
+        let mut removed = HashSet::new();
+        for group in self.duplicate_groups() {
+            let kept = match policy {
+                DedupPolicy::KeepFirst => group[0],
+                DedupPolicy::KeepLast => group[group.len() - 1],
+                DedupPolicy::KeepMostTags => *group
+                    .iter()
+                    .rev()
+                    .max_by_key(|i| self.bibliographies[**i].tags.len())
+                    .unwrap(),
+            };
+            removed.extend(group.into_iter().filter(|i| *i != kept));
+        }
+        self.remove_indices(&removed)
+    }
+
+    // The indices of the groups of duplicated bibliographies.
+    fn duplicate_groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = vec![];
+        let mut by_doi = HashMap::new();
+        let mut by_title = HashMap::new();
+
+        let mut without_doi = vec![];
+        for (i, biblio) in self.bibliographies.iter().enumerate() {
+            let doi = match biblio.doi() {
+                Some(doi) => doi.as_str().to_lowercase(),
+                None => {
+                    without_doi.push(i);
+                    continue;
+                }
+            };
+            let group = *by_doi.entry(doi).or_insert_with(|| {
+                groups.push(vec![]);
+                groups.len() - 1
+            });
+            groups[group].push(i);
+            if let Some(title) = biblio.get_tag("title").map(normalize_title) {
+                by_title.entry(title).or_insert(group);
+            }
+        }
+        for i in without_doi {
+            let title = match self.bibliographies[i].get_tag("title").map(normalize_title) {
+                Some(title) if !title.is_empty() => title,
+                _ => continue,
+            };
+            let group = *by_title.entry(title).or_insert_with(|| {
+                groups.push(vec![]);
+                groups.len() - 1
+            });
+            groups[group].push(i);
+        }
+
+        let mut groups: Vec<_> = groups
+            .into_iter()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort_unstable();
+                group
+            })
+            .collect();
+        groups.sort_unstable_by_key(|group| group[0]);
+        groups
+    }
+
+    // Remove the bibliographies at the given indices, keeping the order of
+    // the others.
+    fn remove_indices(&mut self, indices: &HashSet<usize>) -> Vec<Bibliography> {
+        let mut i = 0;
+        self.order.retain(|slot| {
+            if *slot != Slot::Bibliography {
+                return true;
+            }
+            i += 1;
+            !indices.contains(&(i - 1))
+        });
+        let (removed, kept) = std::mem::take(&mut self.bibliographies)
+            .into_iter()
+            .enumerate()
+            .partition::<Vec<_>, _>(|(i, _)| indices.contains(i));
+        self.bibliographies = kept.into_iter().map(|(_, b)| b).collect();
+        self.index = OnceLock::new();
+        removed.into_iter().map(|(_, b)| b).collect()
+    }
+
+    /// Replace the citation keys of the bibliographies by generated ones,
+    /// see `Bibliography::generate_key`.
+    ///
+    /// Keys generated for several bibliographies get a suffix `a`, `b`, ...
+    /// after the first one, such as `smith2020a`. The `crossref` tags are
+    /// updated to the new keys. Returns the old and new citation keys of
+    /// each bibliography.
+    ///
+    /// ```
+    /// use nom_bibtex::key::KeyScheme;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let mut bibtex = Bibtex::parse(
+    ///     "@misc{ a, author = {Jane Smith}, year = 2020 }
+    ///     @misc{ b, author = {Smith, John}, year = 2020 }",
+    /// )
+    /// .unwrap();
+    ///
+    /// bibtex.regenerate_keys(&KeyScheme::new());
+    /// assert_eq!(bibtex.get("smith2020a").unwrap().get_tag("author"), Some("Smith, John"));
+    /// ```
+    pub fn regenerate_keys(&mut self, scheme: &KeyScheme) -> Vec<(String, String)> {
+        let mut used = HashSet::new();
+        let mut renamed = vec![];
+        for biblio in &mut self.bibliographies {
+            let citation_key = key::unique_key(biblio.generate_key(scheme), &used);
+            used.insert(citation_key.clone());
+            let old = std::mem::replace(&mut biblio.citation_key, citation_key.clone());
+            renamed.push((old, citation_key));
+        }
+
+        let mut new_keys = HashMap::new();
+        for (old, new) in &renamed {
+            new_keys.entry(old.as_str()).or_insert(new.as_str());
+        }
+        for biblio in &mut self.bibliographies {
+            let crossref = biblio.get_tag("crossref").and_then(|c| new_keys.get(c));
+            if let Some(&crossref) = crossref {
+                biblio.set_tag("crossref", crossref.into());
+            }
+        }
+        self.index = OnceLock::new();
+        renamed
+    }
+
+    /// Sort the bibliographies with a comparison function.
+    ///
+    /// The sort is stable, so equal bibliographies keep their order. The
+    /// other entries keep their position in `Bibtex::entries_in_order`, the
+    /// bibliographies being reordered between them.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let mut bibtex = Bibtex::parse(
+    ///     "@misc{ b, title = {Second} }
+    ///     @misc{ a, title = {First} }",
+    /// )
+    /// .unwrap();
+    ///
+    /// bibtex.sort_by(|a, b| a.get_tag("title").cmp(&b.get_tag("title")));
+    /// assert_eq!(bibtex.bibliographies()[0].citation_key(), "a");
+    /// assert_eq!(bibtex.get("b").unwrap().get_tag("title"), Some("Second"));
+    /// ```
+    pub fn sort_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&Bibliography, &Bibliography) -> Ordering,
+    {
+        self.bibliographies.sort_by(compare);
+        self.index = OnceLock::new();
+    }
+
+    /// Sort the bibliographies by citation key, see `Bibtex::sort_by`.
+    pub fn sort_by_key(&mut self) {
+        self.sort_by(|a, b| a.citation_key.cmp(&b.citation_key));
+    }
+
+    /// Sort the bibliographies by year and then by month, see
+    /// `Bibtex::sort_by`.
+    ///
+    /// The years are read by `Bibliography::year` and the months by
+    /// `Bibliography::month`, or from the `date` tag without a `month` tag.
+    /// The bibliographies without a year, or whose year is not a number, are
+    /// sorted last, as are the ones without a month in a year.
+    pub fn sort_by_year(&mut self) {
+        let date = |b: &Bibliography| {
+            let year = b.year();
+            let month = b
+                .month_number()
+                .or_else(|| b.date().and_then(|date| date.month()));
+            (year.is_none(), year, month.is_none(), month)
+        };
+        self.sort_by(|a, b| date(a).cmp(&date(b)));
+    }
+
+    /// Sort the bibliographies by the value of a tag, see `Bibtex::sort_by`.
+    ///
+    /// Values are compared as integers when they all are, and as written
+    /// otherwise. The bibliographies without the tag are sorted last. As the
+    /// sort is stable, sorting by `author` and then by `year` sorts by year
+    /// and then by author.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let mut bibtex = Bibtex::parse(
+    ///     "@misc{ a, year = 2000 }
+    ///     @misc{ b }
+    ///     @misc{ c, year = 999 }",
+    /// )
+    /// .unwrap();
+    ///
+    /// bibtex.sort_by_field("year");
+    /// let keys = bibtex
+    ///     .bibliographies()
+    ///     .iter()
+    ///     .map(|b| b.citation_key())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(keys, ["c", "a", "b"]);
+    /// ```
+    pub fn sort_by_field(&mut self, field: &str) {
+        let numeric = self
+            .bibliographies
+            .iter()
+            .filter_map(|b| b.get_tag(field))
+            .all(|value| value.trim().parse::<i64>().is_ok());
+        if numeric {
+            let number = |b: &Bibliography| {
+                let number = b.get_tag(field).and_then(|v| v.trim().parse::<i64>().ok());
+                (number.is_none(), number)
+            };
+            self.sort_by(|a, b| number(a).cmp(&number(b)));
+        } else {
+            self.sort_by(|a, b| {
+                let (a, b) = (a.get_tag(field), b.get_tag(field));
+                (a.is_none(), a).cmp(&(b.is_none(), b))
+            });
+        }
+    }
+
+    /// Copy the tags of the entries referenced by a `crossref` tag into the
+    /// entries referencing them.
+    ///
+    /// Tags already defined by an entry are kept. Crossrefs are resolved
+    /// whatever the order of the entries, and parents referencing another
+    /// entry are resolved first. The crossref is case-insensitive when no
+    /// citation key matches it exactly. Fails when a crossref is not found or is
+    /// cyclic, in which case some entries might already have been resolved.
+    pub fn resolve_crossrefs(&mut self) -> Result<()> {
+        let mut resolved = vec![false; self.bibliographies.len()];
+
+        for start in 0..self.bibliographies.len() {
+            // Follow the crossrefs until an entry without one or already
+            // resolved.
+            let mut chain = vec![];
+            let mut i = start;
+            while !resolved[i] {
+                if chain.contains(&i) {
+                    let key = self.bibliographies[start].citation_key.clone();
+                    return Err(BibtexError::CyclicCrossref(key));
+                }
+                chain.push(i);
+                match self.crossref_parent(i)? {
+                    Some(parent) => i = parent,
+                    None => break,
+                }
+            }
+
+            for &child in chain.iter().rev() {
+                if let Some(parent) = self.crossref_parent(child)? {
+                    self.inherit_tags(child, parent);
+                }
+                resolved[child] = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the tags of the `@xdata` entries referenced by a `xdata` tag
+    /// into the entries referencing them, as *BibLaTeX* does.
+    ///
+    /// The `xdata` tag is a comma-separated list of citation keys of
+    /// `@xdata` entries, whose tags are copied in the order of the list.
+    /// Tags already defined by an entry are kept, and `@xdata` entries can
+    /// themselves reference other ones. The `@xdata` entries and the
+    /// `xdata` tags are kept, see `Bibtex::retain` to remove them. Fails
+    /// when an `@xdata` entry is not found or is cyclic, in which case some
+    /// entries might already have been resolved.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let mut bibtex = Bibtex::parse(
+    ///     "@xdata{ pub, publisher = {Publisher}, location = {City} }
+    ///     @book{ key, xdata = {pub}, location = {Other city} }",
+    /// )
+    /// .unwrap();
+    ///
+    /// bibtex.resolve_xdata().unwrap();
+    /// let biblio = bibtex.get("key").unwrap();
+    /// assert_eq!(biblio.get_tag("publisher"), Some("Publisher"));
+    /// assert_eq!(biblio.get_tag("location"), Some("Other city"));
+    /// ```
+    pub fn resolve_xdata(&mut self) -> Result<()> {
+        let mut resolved = vec![false; self.bibliographies.len()];
+        for i in 0..self.bibliographies.len() {
+            self.resolve_xdata_of(i, &mut resolved, &mut vec![])?;
+        }
+        Ok(())
+    }
+
+    // Resolve the `@xdata` entries referenced by a bibliography and then the
+    // bibliography. The stack holds the entries being resolved.
+    fn resolve_xdata_of(
+        &mut self,
+        i: usize,
+        resolved: &mut [bool],
+        stack: &mut Vec<usize>,
+    ) -> Result<()> {
+        if resolved[i] {
+            return Ok(());
+        }
+        if stack.contains(&i) {
+            let key = self.bibliographies[stack[0]].citation_key.clone();
+            return Err(BibtexError::CyclicXdata(key));
+        }
+
+        let parents = match self.bibliographies[i].get_tag("xdata") {
+            Some(xdata) => xdata
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(|key| {
+                    self.index()
+                        .get(key)
+                        .copied()
+                        .filter(|&parent| self.bibliographies[parent].is_xdata())
+                        .ok_or_else(|| BibtexError::XdataNotFound {
+                            citation_key: self.bibliographies[i].citation_key.clone(),
+                            xdata: key.into(),
+                        })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![],
+        };
+        stack.push(i);
+        for &parent in &parents {
+            self.resolve_xdata_of(parent, resolved, stack)?;
+        }
+        stack.pop();
+
+        for parent in parents {
+            self.inherit_tags(i, parent);
+        }
+        resolved[i] = true;
+        Ok(())
+    }
+
+    // Copy the tags of a parent which are not defined by a child.
+    fn inherit_tags(&mut self, child: usize, parent: usize) {
+        let parent = &self.bibliographies[parent];
+        let inherited = parent
+            .original_keys()
+            .zip(&parent.tags)
+            .map(|(key, (_, value))| (key.to_string(), value.clone()))
+            .collect::<Vec<_>>();
+
+        let child = &mut self.bibliographies[child];
+        for (key, value) in inherited {
+            if !child.has_tag(&key) {
+                child.set_tag(&key, value);
+            }
+        }
+    }
+
+    /// Decode the *LaTeX* accents of all the tags to Unicode.
+    ///
+    /// Preambles, comments and string variables are left untouched. See
+    /// `latex::decode` for the supported escapes.
+    pub fn decode_latex(&mut self) {
+        for biblio in &mut self.bibliographies {
+            for (_, value) in &mut biblio.tags {
+                *value = latex::decode(value);
+            }
+        }
+    }
+
+    /// Escape the non-ASCII characters of all the tags with *LaTeX*
+    /// commands, for the *BibTeX* versions which do not support Unicode.
+    ///
+    /// This is the reverse of `Bibtex::decode_latex`, see `latex::escape`.
+    /// Returns the characters which cannot be escaped and are kept as they
+    /// are, in the order of the bibliographies and without duplicates.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let mut bibtex = Bibtex::parse("@misc{ key, author = {Gödel}, title = {李} }").unwrap();
+    ///
+    /// assert_eq!(bibtex.encode_latex(), ['李']);
+    /// let biblio = bibtex.get("key").unwrap();
+    /// assert_eq!(biblio.get_tag("author"), Some("G\\\"{o}del"));
+    /// ```
+    pub fn encode_latex(&mut self) -> Vec<char> {
+        let mut unknown = vec![];
+        for biblio in &mut self.bibliographies {
+            for (_, value) in &mut biblio.tags {
+                *value = latex::escape_reporting(value, &mut unknown);
+            }
+        }
+        unknown
+    }
+
+    /// Collapse the runs of whitespaces and newlines of the tags into a
+    /// single space, as `Bibliography::tag_normalized` does.
+    ///
+    /// The tags named in `verbatim_fields`, whose whitespaces are
+    /// significant, are left untouched. Their names are case-insensitive.
+    pub fn normalize_whitespace(&mut self, verbatim_fields: &[&str]) {
+        for biblio in &mut self.bibliographies {
+            for (key, value) in &mut biblio.tags {
+                if !verbatim_fields
+                    .iter()
+                    .any(|field| field.eq_ignore_ascii_case(key))
+                {
+                    *value = normalize_whitespace(value);
+                }
+            }
+        }
+    }
+
+    /// Get the missing fields of all the bibliographies.
+    ///
+    /// See `Bibliography::validate`.
+    pub fn validate_all(&self) -> Vec<ValidationIssue> {
+        self.bibliographies
+            .iter()
+            .flat_map(Bibliography::validate)
+            .collect()
+    }
+
+    /// Get the required fields missing from each bibliography, see
+    /// `Bibliography::missing_required_fields`.
+    ///
+    /// Only the bibliographies with missing fields are listed, by citation
+    /// key.
+    pub fn validate(&self) -> Vec<(String, Vec<&'static str>)> {
+        self.bibliographies
+            .iter()
+            .map(|biblio| {
+                (
+                    biblio.citation_key.clone(),
+                    biblio.missing_required_fields(),
+                )
+            })
+            .filter(|(_, fields)| !fields.is_empty())
+            .collect()
+    }
+
+    /// Append the entries of another `Bibtex`.
+    ///
+    /// String variables defined by both take the value of `other`, the keys
+    /// of those whose value changed are returned sorted. Bibliographies are
+    /// appended even when their citation key is already used, see
+    /// `Bibtex::try_merge` to reject them or `Bibtex::merge_with` to choose
+    /// the entries kept.
+    pub fn merge(&mut self, mut other: Bibtex) -> Vec<String> {
+        let mut overwritten = vec![];
+        // The variables which `other` does not define itself, such as the
+        // ones seeded by `Bibtex::from_files`, keep their unexpanded value.
+        let defined = other
+            .order
+            .iter()
+            .filter_map(|slot| match slot {
+                Slot::Variable(key) => Some(key.clone()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        for slot in other.order {
+            match slot {
+                Slot::Variable(ref key) if self.variables.contains_key(key) => continue,
+                slot => self.order.push(slot),
+            }
+        }
+        for (key, original) in other.variable_keys {
+            if !self.variables.contains_key(&key) {
+                self.variable_keys.insert(key, original);
+            }
+        }
+        for (key, value) in other.variables {
+            if defined.contains(&key) {
+                match other.raw_variables.remove(&key) {
+                    Some(raw_value) => self.raw_variables.insert(key.clone(), raw_value),
+                    None => self.raw_variables.remove(&key),
+                };
+            }
+            if let Some(old) = self.variables.insert(key.clone(), value) {
+                if old != self.variables[&key] {
+                    overwritten.push(key);
+                }
+            }
+        }
+        overwritten.sort();
+
+        self.comments.extend(other.comments);
+        self.comment_blocks.extend(other.comment_blocks);
+        self.preambles.extend(other.preambles);
+        self.bibliographies.extend(other.bibliographies);
+        self.index = OnceLock::new();
+        overwritten
+    }
+
+    /// Append the entries of another `Bibtex` as `Bibtex::merge`, failing
+    /// without change when a citation key is used by both.
+    pub fn try_merge(&mut self, other: Bibtex) -> Result<Vec<String>> {
+        if let Some(biblio) = other
+            .bibliographies
+            .iter()
+            .find(|b| self.get(&b.citation_key).is_some())
+        {
+            return Err(BibtexError::DuplicateCitationKey {
+                key: biblio.citation_key.clone(),
+                first: self.get(&biblio.citation_key).and_then(|b| b.location),
+                duplicate: biblio.location,
+            });
+        }
+        Ok(self.merge(other))
+    }
+
+    /// Append the entries of another `Bibtex`, resolving the entries defined
+    /// by both with a policy.
+    ///
+    /// Comments, preambles and the other entries are appended in order. With
+    /// `MergePolicy::KeepOther`, the bibliographies of `self` sharing a
+    /// citation key with `other` are removed before the ones of `other` are
+    /// appended. On failure, `self` is left unchanged.
+    ///
+    /// ```
+    /// use nom_bibtex::{Bibtex, MergePolicy};
+    ///
+    /// let mut bibtex = Bibtex::parse("@misc{ key, pages = {1--2} }").unwrap();
+    /// let other = Bibtex::parse("@misc{ key, pages = {3--4} }").unwrap();
+    ///
+    /// bibtex.merge_with(other, MergePolicy::KeepSelf).unwrap();
+    /// assert_eq!(bibtex.bibliographies().len(), 1);
+    /// assert_eq!(bibtex.get("key").unwrap().get_tag("pages"), Some("1--2"));
+    /// ```
+    pub fn merge_with(&mut self, mut other: Bibtex, policy: MergePolicy) -> Result<()> {
+        let conflicts: Vec<String> = other
+            .variables
+            .iter()
+            .filter(|(key, value)| self.variables.get(*key).is_some_and(|v| v != *value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        let duplicates: Vec<&Bibliography> = other
+            .bibliographies
+            .iter()
+            .filter(|b| self.get(&b.citation_key).is_some())
+            .collect();
+
+        match policy {
+            MergePolicy::KeepSelf => {
+                let duplicates: Vec<String> =
+                    duplicates.iter().map(|b| b.citation_key.clone()).collect();
+                for key in duplicates {
+                    other.remove_bibliography(&key);
+                }
+                for key in conflicts {
+                    other.variables.remove(&key);
+                }
+            }
+            MergePolicy::KeepOther => {
+                let duplicates: Vec<String> =
+                    duplicates.iter().map(|b| b.citation_key.clone()).collect();
+                for key in duplicates {
+                    while self.remove_bibliography(&key).is_some() {}
+                }
+            }
+            MergePolicy::Error => {
+                if let Some(biblio) = duplicates.first() {
+                    return Err(BibtexError::DuplicateCitationKey {
+                        key: biblio.citation_key.clone(),
+                        first: self.get(&biblio.citation_key).and_then(|b| b.location),
+                        duplicate: biblio.location,
+                    });
+                }
+                if let Some(key) = conflicts.into_iter().min() {
+                    return Err(BibtexError::ConflictingStringVariable(
+                        self.original_variable_key(&key).into(),
+                    ));
+                }
+            }
+        }
+        self.merge(other);
+        Ok(())
+    }
+
+    /// Convert the bibliographies to an array of *CSL-JSON* items, see the
+    /// `csl` module.
+    #[cfg(feature = "csl")]
+    pub fn to_csl_json(&self) -> serde_json::Value {
+        crate::csl::to_csl_json(self)
+    }
+
+    /// Create a new Bibtex instance from an array of *CSL-JSON* items.
+    ///
+    /// The `id` of the items is used as citation key. When missing, already
+    /// used or not a valid citation key, such as one with whitespaces or
+    /// commas, a key is generated from the family name of the first author
+    /// and the year, such as `einstein1905`. Fields without *BibTeX*
+    /// equivalent are dropped, and a value with unbalanced braces fails with
+    /// `BibtexError::UnbalancedTagValue`.
+    #[cfg(feature = "csl")]
+    pub fn from_csl_json(json: &str) -> Result<Self> {
+        crate::csl::from_csl_json(json)
+    }
+
+    /// Serialize back to a *BibTeX* document.
+    ///
+    /// Entries are written in the order of the input, the ones added later
+    /// being written after them. Values are delimited so that the output
+    /// can be parsed again with `Bibtex::parse` into an equal `Bibtex`,
+    /// except for consecutive comments outside of the entries which are read
+    /// back as a single comment.
+    ///
+    /// String variables are written in the order of their definition. The
+    /// parsed values of the variables and of the tags are written as in the
+    /// source, with their abbreviations and `#` concatenations, as long as
+    /// they still expand to the current value.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse(
+    ///     r#"@string{ acm = {ACM} }
+    ///     @string{ jacm = "Journal of the " # acm }
+    ///     @article{ key, journal = jacm, month = jan }"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(
+    ///     bibtex.to_bibtex_string(),
+    ///     r#"@string{acm = {ACM}}
+    /// @string{jacm = "Journal of the " # acm}
+    ///
+    /// @article{key,
+    ///     journal = jacm,
+    ///     month = jan
+    /// }
+    /// "#
+    /// );
+    /// ```
+    pub fn to_bibtex_string(&self) -> String {
+        self.to_bibtex_string_pretty(&WriteOptions::default())
+    }
+
+    /// Serialize back to a *BibTeX* document as `Bibtex::to_bibtex_string`,
+    /// with the given layout.
+    ///
+    /// ```
+    /// use nom_bibtex::options::{Delimiter, WriteOptions};
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse(
+    ///     "@article{ key,
+    ///         author = {Einstein, Albert},
+    ///         title = {Zur Elektrodynamik bewegter K{\\\"o}rper},
+    ///         year = 1905
+    ///     }",
+    /// )
+    /// .unwrap();
+    ///
+    /// let options = WriteOptions::new()
+    ///     .indent(2)
+    ///     .max_line_length(30)
+    ///     .align_equals(true)
+    ///     .delimiter(Delimiter::Quotes);
+    /// assert_eq!(
+    ///     bibtex.to_bibtex_string_pretty(&options),
+    ///     r#"@article{key,
+    ///   author = "Einstein, Albert",
+    ///   title  = "Zur Elektrodynamik
+    ///             bewegter
+    ///             K{\"o}rper",
+    ///   year   = "1905"
+    /// }
+    /// "#
+    /// );
+    /// ```
+    pub fn to_bibtex_string_pretty(&self, options: &WriteOptions) -> String {
+        let delimiter = options.delimiter;
+        let mut out = String::new();
+
+        // The variables without slot are written first, as they may be used
+        // anywhere. The other entries are written in the order of their
+        // slots, followed by the ones without slot.
+        let mut keys = self
+            .variables
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !self.order.contains(&Slot::Variable(key.to_string())))
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|key| self.original_variable_key(key));
+        let mut slots = keys
+            .into_iter()
+            .map(|key| Slot::Variable(key.into()))
+            .collect::<Vec<_>>();
+        slots.extend(self.order.iter().cloned());
+        slots.extend(
+            [
+                (Slot::Comment, self.comments.len()),
+                (Slot::CommentBlock, self.comment_blocks.len()),
+                (Slot::Preamble, self.preambles.len()),
+                (Slot::Bibliography, self.bibliographies.len()),
+            ]
+            .into_iter()
+            .flat_map(|(slot, len)| {
+                let written = self.order.iter().filter(|s| **s == slot).count();
+                std::iter::repeat_n(slot, len.saturating_sub(written))
+            }),
+        );
+
+        let mut comments = self.comments.iter();
+        let mut comment_blocks = self.comment_blocks.iter();
+        let mut preambles = self.preambles.iter();
+        let mut bibliographies = self.bibliographies.iter();
+        // Variables are only used after their definition, as *BibTeX* does.
+        let mut defined = HashMap::new();
+        let mut after_variable = false;
+        for slot in &slots {
+            let entry = match slot {
+                Slot::Variable(key) => {
+                    let value = match self.variables.get(key) {
+                        Some(value) if !defined.contains_key(key) => value,
+                        _ => continue,
+                    };
+                    let raw_value = self.raw_variables.get(key);
+                    let written = written_value(raw_value, value, &defined, delimiter);
+                    let original = self.original_variable_key(key);
+                    out.push_str(&format!("@string{{{} = {}}}\n", original, written));
+                    defined.insert(key.clone(), value.clone());
+                    after_variable = true;
+                    continue;
+                }
+                Slot::Comment => comments.next().map(|comment| comment.to_string()),
+                Slot::CommentBlock => comment_blocks
+                    .next()
+                    .map(|comment| format!("@comment{{{}}}", comment)),
+                Slot::Preamble => preambles
+                    .next()
+                    .map(|preamble| format!("@preamble{{{}}}", delimit_value(preamble, delimiter))),
+                Slot::Bibliography => bibliographies.next().map(|biblio| {
+                    let mut entry = String::new();
+                    let _ = biblio.write_tags(&mut entry, options, |key, value| {
+                        written_value(biblio.raw_tags.get(key), value, &defined, delimiter)
+                    });
+                    entry
+                }),
+            };
+            if let Some(entry) = entry {
+                if after_variable {
+                    out.push('\n');
+                    after_variable = false;
+                }
+                out.push_str(&entry);
+                out.push_str("\n\n");
+            }
+        }
+
+        // Only keep a single trailing new line.
+        let len = out.trim_end().len();
+        out.truncate(len);
+        out.push('\n');
+        out
+    }
+
+    // Position of the entry referenced by the crossref of a bibliography.
+    //
+    // As in *BibTeX*, the crossref can differ in case from the citation key.
+    fn crossref_parent(&self, i: usize) -> Result<Option<usize>> {
+        let biblio = &self.bibliographies[i];
+        match biblio.get_tag("crossref") {
+            Some(crossref) => match self.index().get(crossref).copied().or_else(|| {
+                self.bibliographies
+                    .iter()
+                    .position(|b| b.citation_key.eq_ignore_ascii_case(crossref))
+            }) {
+                Some(parent) => Ok(Some(parent)),
+                None => Err(BibtexError::CrossrefNotFound {
+                    citation_key: biblio.citation_key.clone(),
+                    crossref: crossref.into(),
+                }),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn index(&self) -> &HashMap<String, usize> {
+        self.index.get_or_init(|| {
+            let mut index = HashMap::with_capacity(self.bibliographies.len());
+            for (i, biblio) in self.bibliographies.iter().enumerate() {
+                index.entry(biblio.citation_key.clone()).or_insert(i);
+            }
+            index
+        })
+    }
+
+    // Constants are only used to expand abbreviations while parsing.
+    pub(crate) fn constants() -> HashMap<String, String> {
+        TABLE_MONTHS
+            .iter()
+            .map(|&(k, v)| (k.into(), v.into()))
+            .collect()
+    }
+
+    // Expand the `@string` entries, which can use the variables already
+    // defined and the constants, as the tags do.
+    fn fill_variables(
+        bibtex: &mut Bibtex,
+        entries: &[LocatedEntry],
+        const_map: &HashMap<String, String>,
+        mut errors: Option<&mut Vec<BibtexError>>,
+    ) -> Result<()> {
+        let variables = entries
+            .iter()
+            .filter_map(|(_, _, v)| match v {
+                Entry::Variable(v) => Some(KeyValue::new(v.key.to_lowercase(), v.value.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let variables = variables.iter().collect::<Vec<_>>();
+        let mut predefined = const_map.clone();
+        predefined.extend(bibtex.variables.clone());
+
+        for (i, var) in variables.iter().enumerate() {
+            let value = Self::expand_variables_value(
+                &var.value,
+                &variables,
+                &predefined,
+                &mut vec![i],
+                errors.as_deref_mut(),
+            )?;
+            bibtex.variables.insert(var.key.clone(), value);
+        }
+
+        Ok(())
+    }
+
+    // Expand a variable value, the variables being looked up among all the
+    // `@string` entries so that they can be used before their definition.
+    // The stack holds the position of the variables being expanded.
+    fn expand_variables_value(
+        var_values: &[StringValueType],
+        variables: &[&KeyValue],
+        predefined: &HashMap<String, String>,
+        stack: &mut Vec<usize>,
+        mut errors: Option<&mut Vec<BibtexError>>,
+    ) -> Result<String> {
+        let mut result_value = String::new();
+
+        for chunck in var_values {
+            match chunck.clone() {
+                StringValueType::Str(v) => result_value.push_str(&v),
+                StringValueType::Abbreviation(v) => {
+                    let key = v.to_lowercase();
+                    if let Some(i) = variables.iter().position(|&x| key == x.key) {
+                        if stack.contains(&i) {
+                            cyclic_variable(v, &mut result_value, errors.as_deref_mut())?;
+                            continue;
+                        }
+                        stack.push(i);
+                        result_value.push_str(&Self::expand_variables_value(
+                            &variables[i].value,
+                            variables,
+                            predefined,
+                            stack,
+                            errors.as_deref_mut(),
+                        )?);
+                        stack.pop();
+                    } else {
+                        match predefined.get(&key) {
+                            Some(value) => result_value.push_str(value),
+                            None => {
+                                // The stack always holds the variable being
+                                // expanded.
+                                let name = &variables[stack[stack.len() - 1]].key;
+                                missing_variable(
+                                    v,
+                                    VariableUse::Variable(name.clone()),
+                                    &mut result_value,
+                                    errors.as_deref_mut(),
+                                )?
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(result_value)
+    }
+
+    // Expand a value, `used_in` giving the context of the unknown variables.
+    pub(crate) fn expand_str_abbreviations(
+        value: Vec<StringValueType>,
+        variables: &HashMap<String, String>,
+        const_map: &HashMap<String, String>,
+        used_in: impl Fn() -> VariableUse,
+        mut errors: Option<&mut Vec<BibtexError>>,
+    ) -> Result<String> {
+        let mut result = String::new();
+
+        for chunck in value {
+            match chunck {
+                StringValueType::Str(v) => result.push_str(&v),
+                StringValueType::Abbreviation(v) => {
+                    let key = v.to_lowercase();
+                    if let Some(res) = variables.get(&key) {
+                        result.push_str(res)
+                    } else {
+                        match const_map.get(&key) {
+                            Some(res) => result.push_str(res),
+                            None => {
+                                missing_variable(v, used_in(), &mut result, errors.as_deref_mut())?
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// A builder to create a `Bibtex` without parsing.
+///
+/// Entries keep the order in which they are added.
+///
+/// ```
+/// use nom_bibtex::BibtexBuilder;
+///
+/// let bibtex = BibtexBuilder::new()
+///     .add_comment("Generated".into())
+///     .add_string_variable("pub".into(), "Publisher".into())
+///     .add_bibliography(
+///         "book".into(),
+///         "key".into(),
+///         vec![("title".into(), "Title".into())],
+///     )
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(bibtex.variables()["pub"], "Publisher");
+/// assert_eq!(bibtex.get("key").unwrap().get_tag("title"), Some("Title"));
+/// ```
+#[derive(Debug, Default)]
+pub struct BibtexBuilder {
+    bibtex: Bibtex,
+}
+
+impl BibtexBuilder {
+    pub fn new() -> BibtexBuilder {
+        Self::default()
+    }
+
+    /// Add a bibliography, see `Bibliography::new` for how tags are stored.
+    pub fn add_bibliography<T>(mut self, entry_type: String, citation_key: String, tags: T) -> Self
+    where
+        T: IntoIterator<Item = (String, String)>,
+    {
+        self.bibtex
+            .push_bibliography(Bibliography::new(entry_type, citation_key, tags));
+        self
+    }
+
+    /// Add a string variable, which is case-insensitive as when parsing.
+    ///
+    /// A variable added again keeps its first spelling and position, and its
+    /// last value.
+    pub fn add_string_variable(mut self, key: String, value: String) -> Self {
+        let key = self.bibtex.variable_key(key);
+        self.bibtex.raw_variables.remove(&key);
+        if self.bibtex.variables.insert(key.clone(), value).is_none() {
+            self.bibtex.order.push(Slot::Variable(key));
+        }
+        self
+    }
+
+    /// Add a comment written as text outside of the entries.
+    pub fn add_comment(mut self, comment: String) -> Self {
+        self.bibtex.comments.push(comment);
+        self.bibtex.order.push(Slot::Comment);
+        self
+    }
+
+    /// Add a `@comment` entry.
+    pub fn add_comment_block(mut self, comment: String) -> Self {
+        self.bibtex.comment_blocks.push(comment);
+        self.bibtex.order.push(Slot::CommentBlock);
+        self
+    }
+
+    pub fn add_preamble(mut self, preamble: String) -> Self {
+        self.bibtex.preambles.push(preamble);
+        self.bibtex.order.push(Slot::Preamble);
+        self
+    }
+
+    /// Build the `Bibtex`, failing if a citation key is empty or if a tag or
+    /// a string variable could not be parsed back once written.
+    pub fn build(self) -> Result<Bibtex> {
+        if let Some(biblio) = self
+            .bibtex
+            .bibliographies
+            .iter()
+            .find(|b| b.citation_key.trim().is_empty())
+        {
+            return Err(BibtexError::EmptyCitationKey {
+                entry_type: biblio.entry_type.clone(),
+                line: None,
+            });
+        }
+        for slot in &self.bibtex.order {
+            if let Slot::Variable(key) = slot {
+                let name = self.bibtex.original_variable_key(key);
+                check_tag("@string", name, &self.bibtex.variables[key])?;
+            }
+        }
+        for biblio in &self.bibtex.bibliographies {
+            for key in biblio.original_keys() {
+                let value = biblio.get_tag(key).unwrap_or_default();
+                check_tag(&biblio.citation_key, key, value)?;
+            }
+        }
+        Ok(self.bibtex)
+    }
+}
+
+impl<'a> IntoIterator for &'a Bibtex {
+    type Item = &'a Bibliography;
+    type IntoIter = slice::Iter<'a, Bibliography>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Display for Bibtex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_bibtex_string())
+    }
+}
+
+// Normalize the keys of tags and merge the duplicated ones.
+//
+// Also returns the original spelling of the keys that were not lowercase.
+fn merge_tags<T>(tags: T) -> (Vec<(String, String)>, HashMap<String, String>)
+where
+    T: IntoIterator<Item = (String, String)>,
+{
+    let mut merged: Vec<(String, String)> = Vec::new();
+    let mut original_keys = HashMap::new();
+    for (key, value) in tags {
+        let normalized = key.to_lowercase();
+        match merged.iter_mut().find(|(k, _)| *k == normalized) {
+            Some(tag) => tag.1 = value,
+            None => {
+                if normalized != key {
+                    original_keys.insert(normalized.clone(), key);
+                }
+                merged.push((normalized, value));
+            }
+        }
+    }
+    (merged, original_keys)
+}
+
+// Fail on an unknown variable or, in lossy mode, record the error and keep
+// the variable name.
+fn missing_variable(
+    name: String,
+    used_in: VariableUse,
+    result: &mut String,
+    errors: Option<&mut Vec<BibtexError>>,
+) -> Result<()> {
+    let err = BibtexError::StringVariableNotFound {
+        var: name.clone(),
+        used_in,
+    };
+    match errors {
+        Some(errors) => {
+            result.push_str(&name);
+            errors.push(err);
+            Ok(())
+        }
+        None => Err(err),
+    }
+}
+
+// Same as `missing_variable` for a variable defined from itself.
+fn cyclic_variable(
+    name: String,
+    result: &mut String,
+    errors: Option<&mut Vec<BibtexError>>,
+) -> Result<()> {
+    match errors {
+        Some(errors) => {
+            result.push_str(&name);
+            errors.push(BibtexError::CyclicStringVariable(name));
+            Ok(())
+        }
+        None => Err(BibtexError::CyclicStringVariable(name)),
+    }
+}
+
+// Whether the brackets of a value are balanced, closing brackets never
+// preceding their opening one.
+fn is_balanced(value: &str) -> bool {
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return false,
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+// Compare titles lowercase, without brackets and with runs of whitespaces
+// collapsed.
+fn normalize_title(title: &str) -> String {
+    normalize_whitespace(&title.replace(['{', '}'], "").to_lowercase())
+}
+
+// Replace the runs of whitespaces of a value by a space and trim its ends.
+fn normalize_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Check that a tag or a string variable of `entry` can be parsed back once
+// written.
+pub(crate) fn check_tag(entry: &str, key: &str, value: &str) -> Result<()> {
+    if !parser::is_valid_name(key) {
+        return Err(BibtexError::InvalidTagName {
+            entry: entry.into(),
+            name: key.into(),
+        });
+    }
+    if !parser::has_balanced_braces(value) {
+        return Err(BibtexError::UnbalancedTagValue {
+            entry: entry.into(),
+            key: key.to_lowercase(),
+        });
+    }
+    Ok(())
+}
+
+// Trim the whitespaces, quotes and brackets around a value.
+fn trim_delimiters(value: &str) -> &str {
+    value.trim_matches(|c: char| c.is_whitespace() || "\"{}".contains(c))
+}
+
+// Write a value as parsed, with its abbreviations and concatenations, when
+// it still expands to `value` with `variables` and the months. It is
+// delimited otherwise.
+fn written_value(
+    raw_value: Option<&Vec<StringValueType>>,
+    value: &str,
+    variables: &HashMap<String, String>,
+    delimiter: Delimiter,
+) -> String {
+    match raw_value {
+        Some(raw_value)
+            if (raw_value.len() > 1
+                || matches!(raw_value.as_slice(), [StringValueType::Abbreviation(_)]))
+                && expand_raw_value(raw_value, variables).as_deref() == Some(value) =>
+        {
+            raw_value
+                .iter()
+                .map(|part| match part {
+                    StringValueType::Str(v) => delimit_value(v, delimiter),
+                    StringValueType::Abbreviation(v) => v.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(" # ")
+        }
+        _ => delimit_value(value, delimiter),
+    }
+}
+
+fn expand_raw_value(
+    raw_value: &[StringValueType],
+    variables: &HashMap<String, String>,
+) -> Option<String> {
+    let mut result = String::new();
+    for part in raw_value {
+        match part {
+            StringValueType::Str(v) => result.push_str(v),
+            StringValueType::Abbreviation(v) => {
+                let key = v.to_lowercase();
+                let value = variables.get(&key).map(String::as_str).or_else(|| {
+                    TABLE_MONTHS
+                        .iter()
+                        .find(|(month, _)| *month == key)
+                        .map(|(_, value)| *value)
+                })?;
+                result.push_str(value);
+            }
+        }
+    }
+    Some(result)
+}
+
+// Delimit a value so it is parsed back verbatim.
+//
+// Bracketed values are trimmed by the parser, so values with surrounding
+// whitespace are quoted instead when no quote would end them early.
+fn delimit_value(value: &str, delimiter: Delimiter) -> String {
+    let mut depth = 0;
+    let mut has_toplevel_quote = false;
+    for c in value.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' if depth == 0 => has_toplevel_quote = true,
+            _ => continue,
+        }
+    }
+
+    if !has_toplevel_quote && (delimiter == Delimiter::Quotes || value.trim() != value) {
+        format!("\"{}\"", value)
+    } else {
+        format!("{{{}}}", value)
+    }
+}
+
+// Wrap a written value starting at the column `start` so that its lines fit
+// in `max` columns, the next lines being aligned after its delimiter.
+fn wrap_value(value: &str, start: usize, max: usize) -> String {
+    if start + value.chars().count() <= max {
+        return value.into();
+    }
+
+    let mut out = String::with_capacity(value.len());
+    let continuation = start + 1;
+    let mut column = start;
+    let mut word_start = 0;
+    let mut breaks = break_positions(value);
+    breaks.push(value.len());
+    for end in breaks {
+        let word = &value[word_start..end];
+        let len = word.chars().count();
+        if word_start == 0 {
+            column += len;
+        } else if column + 1 + len > max {
+            out.push('\n');
+            out.push_str(&" ".repeat(continuation));
+            column = continuation + len;
+        } else {
+            out.push(' ');
+            column += 1 + len;
+        }
+        out.push_str(word);
+        word_start = end + 1;
+    }
+    out
+}
+
+// The byte positions of the single spaces of a written value where a line
+// can be broken: outside of nested brackets, and neither escaped nor ending
+// a *LaTeX* control word as in `\ss e`.
+fn break_positions(value: &str) -> Vec<usize> {
+    let mut positions = vec![];
+    let mut depth = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut in_control_word = false;
+    let mut prev = None;
+    let mut chars = value.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        if escaped {
+            escaped = false;
+            in_control_word = c.is_ascii_alphabetic();
+            prev = Some(c);
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' if depth == 0 => in_quotes = !in_quotes,
+            ' ' if !in_control_word
+                && prev.is_some_and(|prev| prev != ' ')
+                && next.is_some_and(|next| next != ' ')
+                && depth <= if in_quotes { 0 } else { 1 } =>
+            {
+                positions.push(i)
+            }
+            _ => {}
+        }
+        if !c.is_ascii_alphabetic() {
+            in_control_word = false;
+        }
+        prev = Some(c);
+    }
+    positions
+}
+
+/// The position of an entry in the parsed input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SourceLocation {
+    /// The line, starting at 1.
+    pub line: u32,
+    /// The column in characters, starting at 1.
+    pub column: usize,
+    /// The offset in bytes from the start of the input.
+    pub offset: usize,
+}
+
+/// This is the main representation of a bibliography.
+///
+/// Bibliographies are equal when they have the same entry type, citation
+/// key and tags, the location where they were read being ignored. They are
+/// ordered by citation key and then by entry type.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(from = "serde_impl::BibliographyFields")
+)]
+pub struct Bibliography {
+    entry_type: String,
+    citation_key: String,
+    tags: Vec<(String, String)>,
+    // Original spelling of the tag keys which are not lowercase.
+    original_keys: HashMap<String, String>,
+    // Values of the tags before the expansion of the string variables.
+    raw_tags: HashMap<String, Vec<StringValueType>>,
+    location: Option<SourceLocation>,
+    // Offset just after the end of the entry.
+    end: Option<usize>,
+}
+
+// The raw tags and the location are where the bibliography was read from,
+// they are not part of the value.
+impl PartialEq for Bibliography {
+    fn eq(&self, other: &Self) -> bool {
+        self.entry_type == other.entry_type
+            && self.citation_key == other.citation_key
+            && self.tags == other.tags
+            && self.original_keys == other.original_keys
+    }
+}
+
+impl Eq for Bibliography {}
+
+// Consistent with `PartialEq`, the maps being hashed in key order.
+impl Hash for Bibliography {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.entry_type.hash(state);
+        self.citation_key.hash(state);
+        let mut tags = self.tags.iter().collect::<Vec<_>>();
+        tags.sort();
+        tags.hash(state);
+        self.sorted_original_keys().hash(state);
+    }
+}
+
+// The tags only order the bibliographies sharing a citation key and an
+// entry type, so that the order is consistent with `PartialEq`.
+impl Ord for Bibliography {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.citation_key
+            .cmp(&other.citation_key)
+            .then_with(|| self.entry_type.cmp(&other.entry_type))
+            .then_with(|| self.tags.cmp(&other.tags))
+            .then_with(|| {
+                self.sorted_original_keys()
+                    .cmp(&other.sorted_original_keys())
+            })
+    }
+}
+
+impl PartialOrd for Bibliography {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Bibliography {
+    /// Create a new bibliography.
+    ///
+    /// Tags keep the order in which they are given and their keys are
+    /// stored lowercase, the original spelling being available with
+    /// `Bibliography::original_key`. When a key is given more than once, the
+    /// last value wins but keeps the first position and spelling.
+    pub fn new<T>(entry_type: String, citation_key: String, tags: T) -> Bibliography
+    where
+        T: IntoIterator<Item = (String, String)>,
+    {
+        let (tags, original_keys) = merge_tags(tags);
+        let raw_tags = tags
+            .iter()
+            .map(|(k, v)| (k.clone(), vec![StringValueType::Str(v.clone())]))
+            .collect();
+        Bibliography {
+            entry_type,
+            citation_key,
+            tags,
+            original_keys,
+            raw_tags,
+            location: None,
+            end: None,
+        }
+    }
+
+    /// Get the entry type.
+    ///
+    /// It represents the type of the publications such as article, book, ...
+    /// The entry type is kept as written, see `Bibliography::entry_type_enum`
+    /// to compare it regardless of case.
+    pub fn entry_type(&self) -> &str {
+        &self.entry_type
+    }
+
+    /// Get the entry type as an `EntryType`.
+    pub fn entry_type_enum(&self) -> EntryType {
+        EntryType::from(self.entry_type.as_str())
+    }
+
+    /// Get the citation key.
+    ///
+    /// The citation key is the the keyword used to reference the bibliography
+    /// in a LaTeX file for example.
+    pub fn citation_key(&self) -> &str {
+        &self.citation_key
+    }
+
+    /// Get the location of the entry in the parsed input.
+    ///
+    /// Returns `None` for a bibliography which was not parsed.
+    pub fn location(&self) -> Option<SourceLocation> {
+        self.location
+    }
+
+    /// Get the byte offsets of the entry in the parsed input, from its `@`
+    /// to just after its closing bracket.
+    ///
+    /// Returns `None` for a bibliography which was not parsed.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let input = "% Notes\n@misc{ key, title = {Title} }\n";
+    /// let bibtex = Bibtex::parse(input).unwrap();
+    ///
+    /// let (start, end) = bibtex.get("key").unwrap().source_span().unwrap();
+    /// assert_eq!(&input[start..end], "@misc{ key, title = {Title} }");
+    /// ```
+    pub fn source_span(&self) -> Option<(usize, usize)> {
+        Some((self.location?.offset, self.end?))
+    }
+
+    /// Set the citation key.
+    pub fn set_citation_key(&mut self, citation_key: String) {
+        self.citation_key = citation_key;
+    }
+
+    /// Get the tags.
+    ///
+    /// Tags are the specifics information about a bibliography
+    /// such as author, date, title, ...
+    ///
+    /// The tags are copied, see `Bibliography::iter_tags` or
+    /// `Bibliography::tags_ordered` to borrow them instead.
+    pub fn tags(&self) -> HashMap<String, String> {
+        self.tags.iter().cloned().collect()
+    }
+
+    /// Get the values of the tags before the expansion of the string
+    /// variables, by lowercase key.
+    ///
+    /// Values which were not parsed, such as the ones given to
+    /// `Bibliography::new` or `Bibliography::insert_tag`, are a single
+    /// `StringValueType::Str`.
+    pub fn raw_tags(&self) -> &HashMap<String, Vec<StringValueType>> {
+        &self.raw_tags
+    }
+
+    /// Get the value of a tag before the expansion of the string variables.
+    ///
+    /// As with `Bibliography::get_tag`, the key is case-insensitive.
+    pub fn raw_tag(&self, key: &str) -> Option<&[StringValueType]> {
+        self.raw_tags
+            .get(&key.to_lowercase())
+            .map(|value| value.as_slice())
+    }
+
+    /// Get the tags in the order they appeared in the file.
+    pub fn tags_ordered(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Iterate over the tags in the order they appeared in the file.
+    pub fn iter_tags(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.tags.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Get the key of a tag as it was written, such as `DOI` for `doi`.
+    ///
+    /// The key is case-insensitive. Returns `None` if the tag is not defined.
+    pub fn original_key(&self, key: &str) -> Option<&str> {
+        let (key, _) = self
+            .tags
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))?;
+        Some(self.original_keys.get(key).unwrap_or(key))
+    }
+
+    /// Get the original spelling of a tag from its lowercase key, as used by
+    /// `Bibliography::tags`.
+    ///
+    /// Unlike `Bibliography::original_key`, the key must be lowercase.
+    pub fn original_field_name(&self, normalized_key: &str) -> Option<&str> {
+        let (key, _) = self.tags.iter().find(|(k, _)| k == normalized_key)?;
+        Some(self.original_keys.get(key).unwrap_or(key))
+    }
+
+    /// Iterate over the keys of the tags as they were written, in order.
+    pub fn original_keys(&self) -> impl Iterator<Item = &str> {
+        self.tags
+            .iter()
+            .map(|(k, _)| self.original_keys.get(k).unwrap_or(k).as_str())
+    }
+
+    // The original spelling of the keys, in key order.
+    fn sorted_original_keys(&self) -> Vec<(&String, &String)> {
+        let mut keys = self.original_keys.iter().collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    /// Get the value of a tag.
+    ///
+    /// As *BibTeX* field names, the key is case-insensitive.
+    pub fn get_tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Check if a tag is defined, the key is case-insensitive.
+    pub fn has_tag(&self, key: &str) -> bool {
+        self.get_tag(key).is_some()
+    }
+
+    /// Set the value of a tag, returning the previous value if any.
+    ///
+    /// Keys are stored lowercase, as when parsing. A new tag is added after
+    /// the existing ones while an existing tag keeps its position and
+    /// original spelling.
+    ///
+    /// Fails with `BibtexError::InvalidTagName` or
+    /// `BibtexError::UnbalancedTagValue` when the tag could not be parsed
+    /// back once written.
+    pub fn insert_tag(&mut self, key: &str, value: String) -> Result<Option<String>> {
+        check_tag(&self.citation_key, key, &value)?;
+        Ok(self.set_tag(key, value))
+    }
+
+    // Set the value of a tag as `Bibliography::insert_tag`, without checking
+    // it.
+    fn set_tag(&mut self, key: &str, value: String) -> Option<String> {
+        let raw_value = vec![StringValueType::Str(value.clone())];
+        match self
+            .tags
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        {
+            Some(tag) => {
+                self.raw_tags.insert(tag.0.clone(), raw_value);
+                Some(std::mem::replace(&mut tag.1, value))
+            }
+            None => {
+                let normalized = key.to_lowercase();
+                if normalized != key {
+                    self.original_keys.insert(normalized.clone(), key.into());
+                }
+                self.raw_tags.insert(normalized.clone(), raw_value);
+                self.tags.push((normalized, value));
+                None
+            }
+        }
+    }
+
+    /// Remove a tag, returning its value if it was defined.
+    pub fn remove_tag(&mut self, key: &str) -> Option<String> {
+        let i = self
+            .tags
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(key))?;
+        let (key, value) = self.tags.remove(i);
+        self.original_keys.remove(&key);
+        self.raw_tags.remove(&key);
+        Some(value)
+    }
+
+    /// Rename a tag, returning whether it was defined.
+    ///
+    /// The tag keeps its position and value. A tag already named `to` is
+    /// replaced. As in `Bibliography::insert_tag`, the new key is stored
+    /// lowercase with its original spelling, and fails with
+    /// `BibtexError::InvalidTagName` when it is not a valid field name.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let mut bibtex = Bibtex::parse(
+    ///     "@article{ key, journaltitle = {Journal}, journal = {Old}, year = 2000 }",
+    /// )
+    /// .unwrap();
+    /// let biblio = bibtex.get_mut("key").unwrap();
+    ///
+    /// assert_eq!(biblio.rename_tag("JournalTitle", "journal"), Ok(true));
+    /// assert_eq!(biblio.rename_tag("journaltitle", "journal"), Ok(false));
+    /// assert!(biblio.rename_tag("journal", "journal title").is_err());
+    /// assert_eq!(
+    ///     biblio.iter_tags().collect::<Vec<_>>(),
+    ///     [("journal", "Journal"), ("year", "2000")]
+    /// );
+    /// ```
+    pub fn rename_tag(&mut self, from: &str, to: &str) -> Result<bool> {
+        if !parser::is_valid_name(to) {
+            return Err(BibtexError::InvalidTagName {
+                entry: self.citation_key.clone(),
+                name: to.into(),
+            });
+        }
+        let i = match self
+            .tags
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(from))
+        {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+        let normalized = to.to_lowercase();
+        let old_key = std::mem::replace(&mut self.tags[i].0, normalized.clone());
+        self.original_keys.remove(&old_key);
+        let raw_value = self.raw_tags.remove(&old_key);
+
+        if let Some(j) = self
+            .tags
+            .iter()
+            .enumerate()
+            .position(|(j, (k, _))| j != i && *k == normalized)
+        {
+            self.tags.remove(j);
+        }
+        if normalized != to {
+            self.original_keys.insert(normalized.clone(), to.into());
+        } else {
+            self.original_keys.remove(&normalized);
+        }
+        match raw_value {
+            Some(raw_value) => self.raw_tags.insert(normalized, raw_value),
+            None => self.raw_tags.remove(&normalized),
+        };
+        Ok(true)
+    }
+
+    /// Get the `title` tag, a shortcut for `get_tag("title")`.
+    pub fn title(&self) -> Option<&str> {
+        self.get_tag("title")
+    }
+
+    /// Get the `author` tag as written, see `Bibliography::authors` for the
+    /// parsed names.
+    pub fn author(&self) -> Option<&str> {
+        self.get_tag("author")
+    }
+
+    /// Get the `journal` tag, or the *BibLaTeX* `journaltitle` tag.
+    pub fn journal(&self) -> Option<&str> {
+        self.get_tag("journal")
+            .or_else(|| self.get_tag("journaltitle"))
+    }
+
+    /// Get the month.
+    ///
+    /// Returns `None` when the `month` tag is missing or is not understood by
+    /// `Month::parse`.
+    pub fn month(&self) -> Option<Month> {
+        Month::parse(self.get_tag("month")?)
+    }
+
+    /// Get the month as a number from 1 to 12.
+    ///
+    /// The `month` tag can either be a number, the three letters abbreviation
+    /// or the full english name of the month.
+    pub fn month_number(&self) -> Option<u8> {
+        self.month().map(Month::number)
+    }
+
+    /// Get the year.
+    ///
+    /// When there is no `year` tag, the year is taken from a *BibLaTeX*
+    /// `date` tag such as `2021-06-01` or `2020/2021`. Returns `None` when
+    /// the value is not a number.
+    pub fn year(&self) -> Option<i32> {
+        let year = match self.get_tag("year") {
+            Some(year) => trim_delimiters(year),
+            None => trim_delimiters(self.get_tag("date")?)
+                .split(['-', '/'])
+                .next()?,
+        };
+        year.parse().ok()
+    }
+
+    /// Get the date from the *BibLaTeX* `date` tag.
+    ///
+    /// Without a `date` tag, the date is built from the `year` and `month`
+    /// tags. A value which is not a valid date is returned as
+    /// `BibDate::Raw`.
+    pub fn date(&self) -> Option<BibDate> {
+        if let Some(date) = self.get_tag("date").and_then(BibDate::parse) {
+            return Some(date);
+        }
+
+        let year = trim_delimiters(self.get_tag("year")?).trim();
+        if year.is_empty() {
+            return None;
+        }
+        let date = match year.parse() {
+            Ok(year) => BibDate::Single(DatePart {
+                year,
+                month: self.month_number(),
+                day: None,
+            }),
+            Err(_) => BibDate::Raw(year.into()),
+        };
+        Some(date)
+    }
+
+    /// Get the DOI of the bibliography.
+    ///
+    /// The DOI is looked for in the `doi` tag, then in the `url` and `note`
+    /// tags, see `Doi::parse`.
+    pub fn doi(&self) -> Option<Doi> {
+        ["doi", "url", "note"]
+            .iter()
+            .find_map(|key| Doi::parse(self.get_tag(key)?))
+    }
+
+    /// Generate a citation key from the first author, the year and
+    /// optionally the title, see the `key` module.
+    ///
+    /// The last name of the author is read by `Bibliography::authors`, so
+    /// that `de la Vallée Poussin, Charles` gives `valleepoussin`.
+    pub fn generate_key(&self, scheme: &KeyScheme) -> String {
+        key::generate_key(self, scheme)
+    }
+
+    /// Get the `title` tag split into plain text and case-protected parts,
+    /// see `TitlePart::split`.
+    ///
+    /// The brackets of the values are kept when parsing, only the ones
+    /// delimiting the whole value are removed, so `{{GNU} Tools}` gives the
+    /// title `{GNU} Tools`. Returns an empty list without title.
+    pub fn title_parts(&self) -> Vec<TitlePart> {
+        self.get_tag("title")
+            .map(TitlePart::split)
+            .unwrap_or_default()
+    }
+
+    /// Whether this is a *BibLaTeX* `@xdata` entry, holding tags shared by
+    /// other entries, see `Bibtex::resolve_xdata`.
+    pub fn is_xdata(&self) -> bool {
+        self.entry_type.eq_ignore_ascii_case("xdata")
+    }
 
-        let mut bibtex = Bibtex::default();
+    /// Get the citation keys of the members of a *BibLaTeX* `@set` entry,
+    /// as given by its comma-separated `entryset` tag.
+    ///
+    /// Returns an empty list without `entryset` tag.
+    pub fn entry_set(&self) -> Vec<&str> {
+        self.get_tag("entryset")
+            .map(|keys| {
+                keys.split(',')
+                    .map(str::trim)
+                    .filter(|key| !key.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 
-        Self::fill_constants(&mut bibtex)?;
-        Self::fill_variables(&mut bibtex, &entries)?;
+    /// Get the pages, `None` when the `pages` tag is missing or empty.
+    pub fn pages(&self) -> Option<Pages> {
+        Pages::parse(self.get_tag("pages")?)
+    }
 
-        for entry in entries {
-            match entry {
-                Entry::Variable(_) => continue, // Already handled.
-                Entry::Comment(v) => bibtex.comments.push(v),
-                Entry::Preamble(v) => {
-                    let new_val = Self::expand_str_abbreviations(v, &bibtex)?;
-                    bibtex.preambles.push(new_val);
-                }
-                Entry::Bibliography(entry_t, citation_key, tags) => {
-                    let mut new_tags = HashMap::new();
-                    for tag in tags {
-                        new_tags.insert(
-                            tag.key,
-                            Self::expand_str_abbreviations(tag.value, &bibtex)?,
-                        );
-                    }
-                    bibtex
-                        .bibliographies
-                        .push(Bibliography::new(entry_t, citation_key, new_tags));
+    /// Get the keywords of the `keywords` tag, separated by commas or
+    /// semicolons.
+    ///
+    /// Keywords are trimmed and the empty ones are dropped. The separators
+    /// inside brackets are kept, the brackets around a whole keyword being
+    /// removed: `{machine learning, deep}` is a single keyword.
+    pub fn keywords(&self) -> Vec<String> {
+        let value = match self.get_tag("keywords") {
+            Some(value) => value,
+            None => return vec![],
+        };
+
+        let mut keywords = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in value.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ',' | ';' if depth == 0 => {
+                    keywords.push(&value[start..i]);
+                    start = i + 1;
                 }
+                _ => {}
             }
         }
-        Ok(bibtex)
+        keywords.push(&value[start..]);
+
+        keywords
+            .into_iter()
+            .map(|keyword| {
+                let keyword = keyword.trim();
+                match keyword.strip_prefix('{').and_then(|k| k.strip_suffix('}')) {
+                    Some(inner) if is_balanced(inner) => inner.trim(),
+                    _ => keyword,
+                }
+            })
+            .filter(|keyword| !keyword.is_empty())
+            .map(String::from)
+            .collect()
     }
 
-    /// Get a raw vector of entries in order from the files.
-    pub fn raw_parse(bibtex: &str) -> Result<Vec<Entry>> {
-        let span = mkspan(bibtex);
-        match parser::entries::<VerboseError<Span>>(span) {
-            Ok((_, v)) => Ok(v),
-            Err(e) => Err(BibtexError::with_context(bibtex, e)),
+    /// Get the value of a tag with its *LaTeX* accents decoded to Unicode.
+    ///
+    /// See `latex::decode` for the supported escapes.
+    pub fn tag_decoded(&self, key: &str) -> Option<String> {
+        self.get_tag(key).map(latex::decode)
+    }
+
+    /// Get the value of a tag on a single line, the runs of whitespaces and
+    /// newlines being replaced by a space and the ends trimmed.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse(
+    ///     "@misc{ key,
+    ///         abstract = {A long
+    ///                     abstract.   With spaces.}
+    ///     }",
+    /// )
+    /// .unwrap();
+    ///
+    /// let biblio = bibtex.get("key").unwrap();
+    /// assert_eq!(
+    ///     biblio.tag_normalized("abstract").as_deref(),
+    ///     Some("A long abstract. With spaces.")
+    /// );
+    /// ```
+    pub fn tag_normalized(&self, key: &str) -> Option<String> {
+        self.get_tag(key).map(normalize_whitespace)
+    }
+
+    /// Get the authors parsed from the `author` tag.
+    ///
+    /// Returns an empty list when the bibliography has no author.
+    pub fn authors(&self) -> Result<Vec<Name>> {
+        self.names("author")
+    }
+
+    /// Get the editors parsed from the `editor` tag.
+    ///
+    /// Returns an empty list when the bibliography has no editor.
+    pub fn editors(&self) -> Result<Vec<Name>> {
+        self.names("editor")
+    }
+
+    fn names(&self, key: &str) -> Result<Vec<Name>> {
+        match self.get_tag(key) {
+            Some(names) => name::parse_names(names),
+            None => Ok(vec![]),
         }
     }
 
-    /// Get preambles with expanded variables.
-    pub fn preambles(&self) -> &[String] {
-        &self.preambles
+    /// Convert to a *RIS* record, see the `ris` module.
+    pub fn to_ris(&self) -> String {
+        ris::to_ris_record(self)
     }
 
-    /// Get comments.
-    pub fn comments(&self) -> &[String] {
-        &self.comments
+    /// Convert to a *CSL-JSON* item, see the `csl` module.
+    #[cfg(feature = "csl")]
+    pub fn to_csl_json(&self) -> serde_json::Value {
+        crate::csl::to_csl_item(self)
     }
 
-    /// Get string variables with a tuple of key and expanded value.
-    pub fn variables(&self) -> HashMap<String, String> {
-        self.variables.clone()
+    /// Get the required and recommended fields missing for the entry type.
+    ///
+    /// Only standard entry types are checked, see the `validation` module.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        validation::validate(self)
     }
 
-    /// Get bibliographies entry with variables expanded.
-    pub fn bibliographies(&self) -> &Vec<Bibliography> {
-        &self.bibliographies
+    /// Get the required fields missing for the entry type.
+    ///
+    /// Returns an empty list for the entry types which are not standard.
+    pub fn missing_required_fields(&self) -> Vec<&'static str> {
+        validation::missing_required_fields(self)
+    }
+
+    /// Serialize the bibliography as a *BibTeX* entry.
+    ///
+    /// Tag keys are written with their original spelling. This is the
+    /// `Display` output followed by a new line. The alternate `Display`
+    /// form, `{:#}`, sorts the fields as `WriteOptions::sort_fields`.
+    ///
+    /// ```
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bibtex = Bibtex::parse("@book{ key, year = 1984, Title = {The {TeX}book} }").unwrap();
+    /// let biblio = bibtex.get("key").unwrap();
+    ///
+    /// assert_eq!(
+    ///     biblio.to_bibtex_string(),
+    ///     "@book{key,\n    year = {1984},\n    Title = {The {TeX}book}\n}\n"
+    /// );
+    /// assert_eq!(
+    ///     format!("{:#}", biblio),
+    ///     "@book{key,\n    Title = {The {TeX}book},\n    year = {1984}\n}"
+    /// );
+    /// ```
+    pub fn to_bibtex_string(&self) -> String {
+        format!("{}\n", self)
     }
 
-    fn fill_constants(bibtex: &mut Bibtex) -> Result<()> {
-        for m in &TABLE_MONTHS {
-            bibtex.const_map.insert(m.0, m.1);
+    /// Serialize the bibliography as a *BibTeX* entry with the given
+    /// layout, see `Bibtex::to_bibtex_string_pretty`.
+    pub fn to_bibtex_string_pretty(&self, options: &WriteOptions) -> String {
+        let mut out = String::new();
+        let _ = self.write_tags(&mut out, options, |_, value| {
+            delimit_value(value, options.delimiter)
+        });
+        out.push('\n');
+        out
+    }
+
+    // Write the entry, `value` giving the written value of a tag from its
+    // lowercase key and value.
+    fn write_tags<W, F>(&self, f: &mut W, options: &WriteOptions, value: F) -> fmt::Result
+    where
+        W: fmt::Write,
+        F: Fn(&str, &str) -> String,
+    {
+        writeln!(f, "@{}{{{},", self.entry_type, self.citation_key)?;
+        let mut tags = self
+            .original_keys()
+            .zip(&self.tags)
+            .map(|(key, (normalized, value))| (key, normalized.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        if options.sort_fields {
+            tags.sort_by_key(|&(_, normalized, _)| {
+                let rank = FIRST_FIELDS.iter().position(|&key| key == normalized);
+                (rank.unwrap_or(FIRST_FIELDS.len()), normalized)
+            });
         }
-        Ok(())
+        let width = match options.align_equals {
+            true => tags.iter().map(|(key, _, _)| key.chars().count()).max(),
+            false => None,
+        };
+        for (i, (key, normalized, v)) in tags.iter().enumerate() {
+            let separator = if i + 1 < tags.len() { "," } else { "" };
+            let prefix = format!(
+                "{}{:width$} = ",
+                " ".repeat(options.indent),
+                key,
+                width = width.unwrap_or(0)
+            );
+            let mut value = value(normalized, v);
+            value.push_str(separator);
+            if let Some(max) = options.max_line_length {
+                value = wrap_value(&value, prefix.chars().count(), max);
+            }
+            writeln!(f, "{}{}", prefix, value)?;
+        }
+        f.write_str("}")
     }
+}
 
-    fn fill_variables(bibtex: &mut Bibtex, entries: &[Entry]) -> Result<()> {
-        let variables = entries
+// Fields written first by `WriteOptions::sort_fields`.
+const FIRST_FIELDS: [&str; 3] = ["author", "title", "year"];
+
+// The alternate form sorts the fields.
+impl fmt::Display for Bibliography {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let options = WriteOptions::default().sort_fields(f.alternate());
+        self.write_tags(f, &options, |_, value| {
+            delimit_value(value, options.delimiter)
+        })
+    }
+}
+
+/// The standard *BibTeX* entry types.
+///
+/// Entry types are case-insensitive, other types are kept as written in
+/// `EntryType::Other`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum EntryType {
+    Article,
+    Book,
+    Booklet,
+    Conference,
+    InBook,
+    InCollection,
+    InProceedings,
+    Manual,
+    MastersThesis,
+    Misc,
+    PhdThesis,
+    Proceedings,
+    TechReport,
+    Unpublished,
+    Other(String),
+}
+
+const TABLE_ENTRY_TYPES: [(&str, EntryType); 14] = [
+    ("article", EntryType::Article),
+    ("book", EntryType::Book),
+    ("booklet", EntryType::Booklet),
+    ("conference", EntryType::Conference),
+    ("inbook", EntryType::InBook),
+    ("incollection", EntryType::InCollection),
+    ("inproceedings", EntryType::InProceedings),
+    ("manual", EntryType::Manual),
+    ("mastersthesis", EntryType::MastersThesis),
+    ("misc", EntryType::Misc),
+    ("phdthesis", EntryType::PhdThesis),
+    ("proceedings", EntryType::Proceedings),
+    ("techreport", EntryType::TechReport),
+    ("unpublished", EntryType::Unpublished),
+];
+
+impl From<&str> for EntryType {
+    fn from(entry_type: &str) -> EntryType {
+        TABLE_ENTRY_TYPES
             .iter()
-            .filter_map(|v| match v {
-                &Entry::Variable(ref v) => Some(v),
-                _ => None,
+            .find(|(name, _)| entry_type.eq_ignore_ascii_case(name))
+            .map_or_else(|| EntryType::Other(entry_type.into()), |(_, t)| t.clone())
+    }
+}
+
+impl str::FromStr for EntryType {
+    type Err = Infallible;
+
+    fn from_str(entry_type: &str) -> result::Result<Self, Self::Err> {
+        Ok(EntryType::from(entry_type))
+    }
+}
+
+impl fmt::Display for EntryType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EntryType::Other(entry_type) => f.write_str(entry_type),
+            _ => {
+                let (name, _) = TABLE_ENTRY_TYPES
+                    .iter()
+                    .find(|(_, t)| t == self)
+                    .expect("standard entry types are in the table");
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+/// A month of the year.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum Month {
+    January = 1,
+    February,
+    March,
+    April,
+    May,
+    June,
+    July,
+    August,
+    September,
+    October,
+    November,
+    December,
+}
+
+const MONTHS: [Month; 12] = [
+    Month::January,
+    Month::February,
+    Month::March,
+    Month::April,
+    Month::May,
+    Month::June,
+    Month::July,
+    Month::August,
+    Month::September,
+    Month::October,
+    Month::November,
+    Month::December,
+];
+
+impl Month {
+    /// Get the month from its number, from 1 to 12.
+    pub fn from_number(number: u8) -> Option<Month> {
+        MONTHS.get(usize::from(number).checked_sub(1)?).copied()
+    }
+
+    /// Parse a month from a number, the three letters abbreviation or the
+    /// full english name, ignoring case and surrounding quotes or brackets.
+    pub fn parse(value: &str) -> Option<Month> {
+        let value = trim_delimiters(value);
+        if let Ok(number) = value.parse::<u8>() {
+            return Month::from_number(number);
+        }
+        TABLE_MONTHS
+            .iter()
+            .position(|(abbrev, name)| {
+                value.eq_ignore_ascii_case(abbrev) || value.eq_ignore_ascii_case(name)
             })
-            .collect::<Vec<_>>();
+            .map(|i| MONTHS[i])
+    }
 
-        for var in &variables {
-            bibtex.variables.insert(
-                var.key.clone(),
-                Self::expand_variables_value(&var.value, &variables)?,
-            );
+    /// Get the number of the month, from 1 to 12.
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    /// Get the three letters abbreviation, as the *BibTeX* constants.
+    pub fn abbrev(self) -> &'static str {
+        TABLE_MONTHS[self as usize - 1].0
+    }
+
+    /// Get the full english name.
+    pub fn full_name(self) -> &'static str {
+        TABLE_MONTHS[self as usize - 1].1
+    }
+}
+
+impl fmt::Display for Month {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.full_name())
+    }
+}
+
+/// A DOI in its bare form, such as `10.1145/3290605.3300233`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Doi(String);
+
+impl Doi {
+    /// Find the first DOI in a value.
+    ///
+    /// The DOI can be bare or in a URL, such as
+    /// `https://dx.doi.org/10.1145/3290605`, or after a `doi:` prefix. It
+    /// must start with `10.` followed by a registrant code of at least four
+    /// digits, and the punctuation ending a sentence is removed from its end.
+    ///
+    /// ```
+    /// use nom_bibtex::model::Doi;
+    ///
+    /// let doi = Doi::parse("See doi:10.1145/3290605.3300233.").unwrap();
+    /// assert_eq!(doi.as_str(), "10.1145/3290605.3300233");
+    /// assert_eq!(doi.as_url(), "https://doi.org/10.1145/3290605.3300233");
+    /// assert_eq!(Doi::parse("10.12/short"), None);
+    /// ```
+    pub fn parse(value: &str) -> Option<Doi> {
+        value
+            .match_indices("10.")
+            .filter(|(i, _)| {
+                !value[..*i]
+                    .chars()
+                    .next_back()
+                    .is_some_and(|c| c.is_alphanumeric() || c == '.')
+            })
+            .find_map(|(i, _)| Self::parse_at(&value[i..]))
+    }
+
+    // Parse a DOI at the start of a value.
+    fn parse_at(value: &str) -> Option<Doi> {
+        let (prefix, rest) = value.split_once('/')?;
+        let registrant = &prefix[3..];
+        if registrant.chars().filter(char::is_ascii_digit).count() < 4
+            || !registrant.chars().all(|c| c.is_ascii_digit() || c == '.')
+            || registrant.ends_with('.')
+        {
+            return None;
         }
 
-        Ok(())
+        let end = rest
+            .find(|c: char| c.is_whitespace() || "\"<>{}".contains(c))
+            .unwrap_or(rest.len());
+        let mut suffix = &rest[..end];
+        loop {
+            let trimmed = suffix.trim_end_matches(['.', ',', ';', ':']);
+            let unbalanced = |open, close| {
+                trimmed.ends_with(close)
+                    && trimmed.matches(open).count() < trimmed.matches(close).count()
+            };
+            suffix = if unbalanced('(', ')') || unbalanced('[', ']') {
+                &trimmed[..trimmed.len() - 1]
+            } else {
+                trimmed
+            };
+            if suffix.len() == trimmed.len() {
+                break;
+            }
+        }
+        if suffix.is_empty() {
+            return None;
+        }
+        Some(Doi(format!("{}/{}", prefix, suffix)))
     }
 
-    fn expand_variables_value(
-        var_values: &Vec<StringValueType>,
-        variables: &Vec<&KeyValue>,
-    ) -> Result<String> {
-        let mut result_value = String::new();
+    /// Get the bare DOI.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 
-        for chunck in var_values {
-            match chunck.clone() {
-                StringValueType::Str(v) => result_value.push_str(&v),
-                StringValueType::Abbreviation(v) => {
-                    let var = variables
-                        .iter()
-                        .find(|&x| *v == x.key)
-                        .ok_or_else(|| BibtexError::StringVariableNotFound(v.into()))?;
-                    result_value.push_str(&Self::expand_variables_value(&var.value, &variables)?);
+    /// Get the URL resolving the DOI.
+    pub fn as_url(&self) -> String {
+        format!("https://doi.org/{}", self.0)
+    }
+}
+
+impl fmt::Display for Doi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The pages of a bibliography, as given by the `pages` tag.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Pages {
+    /// A range such as `101--110` or `101-110`.
+    Range { start: String, end: String },
+    /// A single page such as `101` or `e1234`.
+    Single(String),
+    /// Any other value, such as several ranges, kept as written.
+    Other(String),
+}
+
+impl Pages {
+    /// Parse the value of a `pages` tag, `None` when it is empty.
+    pub fn parse(value: &str) -> Option<Pages> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        if value.contains(',') {
+            return Some(Pages::Other(value.into()));
+        }
+
+        let separator = ["--", "-", "\u{2013}"]
+            .iter()
+            .find_map(|sep| value.find(sep).map(|i| (i, sep.len())));
+        let pages = match separator {
+            None if !value.contains(char::is_whitespace) => Pages::Single(value.into()),
+            Some((i, len)) => {
+                let (start, end) = (value[..i].trim(), value[i + len..].trim());
+                let is_page = |page: &str| {
+                    !page.is_empty() && !page.contains(|c: char| c == '-' || c.is_whitespace())
+                };
+                if is_page(start) && is_page(end) {
+                    Pages::Range {
+                        start: start.into(),
+                        end: end.into(),
+                    }
+                } else {
+                    Pages::Other(value.into())
                 }
             }
+            None => Pages::Other(value.into()),
+        };
+        Some(pages)
+    }
+
+    /// Format the pages as in a *BibTeX* file, ranges being separated by
+    /// `--`.
+    pub fn display_bibtex(&self) -> String {
+        match self {
+            Pages::Range { start, end } => format!("{}--{}", start, end),
+            Pages::Single(page) | Pages::Other(page) => page.clone(),
         }
-        Ok(result_value)
     }
+}
 
-    fn expand_str_abbreviations(value: Vec<StringValueType>, bibtex: &Bibtex) -> Result<String> {
-        let mut result = String::new();
+/// A part of a title, see `Bibliography::title_parts`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TitlePart {
+    /// Text whose case can be changed by the bibliography style.
+    Plain(String),
+    /// The content of a top-level bracket group, such as `GNU` in
+    /// `The {GNU} Project`, whose case is kept as written. Nested brackets
+    /// are kept in the content.
+    Protected(String),
+}
 
-        for chunck in value {
-            match chunck {
-                StringValueType::Str(v) => result.push_str(&v),
-                StringValueType::Abbreviation(v) => {
-                    let var = bibtex.variables.iter().find(|&x| &v == x.0);
-                    if let Some(res) = var {
-                        result.push_str(res.1)
-                    } else {
-                        match bibtex.const_map.get(v.as_str()) {
-                            Some(res) => result.push_str(res),
-                            None => return Err(BibtexError::StringVariableNotFound(v.into())),
-                        }
+impl TitlePart {
+    /// Split a value into its plain text and its top-level bracket groups.
+    ///
+    /// The parts are displayed with their brackets, so that joining them
+    /// gives back the value.
+    ///
+    /// ```
+    /// use nom_bibtex::model::TitlePart;
+    ///
+    /// let parts = TitlePart::split("The {GNU} Project");
+    /// assert_eq!(
+    ///     parts,
+    ///     [
+    ///         TitlePart::Plain("The ".into()),
+    ///         TitlePart::Protected("GNU".into()),
+    ///         TitlePart::Plain(" Project".into()),
+    ///     ]
+    /// );
+    /// assert_eq!(
+    ///     parts.iter().map(|part| part.to_string()).collect::<String>(),
+    ///     "The {GNU} Project"
+    /// );
+    /// ```
+    pub fn split(value: &str) -> Vec<TitlePart> {
+        let mut parts = vec![];
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in value.char_indices() {
+            match c {
+                '{' if depth == 0 => {
+                    if i > start {
+                        parts.push(TitlePart::Plain(value[start..i].into()));
                     }
+                    depth = 1;
+                    start = i + 1;
+                }
+                '{' => depth += 1,
+                '}' if depth == 1 => {
+                    parts.push(TitlePart::Protected(value[start..i].into()));
+                    depth = 0;
+                    start = i + 1;
                 }
+                '}' if depth > 1 => depth -= 1,
+                _ => {}
             }
         }
-        Ok(result)
+        // An unclosed group protects the rest of the value.
+        match depth {
+            0 if start < value.len() => parts.push(TitlePart::Plain(value[start..].into())),
+            0 => {}
+            _ => parts.push(TitlePart::Protected(value[start..].into())),
+        }
+        parts
     }
 }
 
-/// This is the main representation of a bibliography.
-#[derive(Debug, PartialEq, Eq)]
-pub struct Bibliography {
-    entry_type: String,
-    citation_key: String,
-    tags: HashMap<String, String>,
+impl fmt::Display for TitlePart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TitlePart::Plain(text) => f.write_str(text),
+            TitlePart::Protected(text) => write!(f, "{{{}}}", text),
+        }
+    }
 }
 
-impl Bibliography {
-    /// Create a new bibliography.
-    pub fn new(
-        entry_type: String,
-        citation_key: String,
-        tags: HashMap<String, String>,
-    ) -> Bibliography {
-        Bibliography {
-            entry_type,
-            citation_key,
-            tags,
+/// A calendar date with an optional month and day, as used by `BibDate`.
+///
+/// Dates are ordered chronologically, a date without day coming before
+/// the days of its month.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct DatePart {
+    pub year: i32,
+    /// The month, from 1 to 12.
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+}
+
+impl DatePart {
+    // Parse `YYYY`, `YYYY-MM` or `YYYY-MM-DD`.
+    fn parse(value: &str) -> Option<DatePart> {
+        let mut parts = value.split('-');
+        let number = |part: &str, len| {
+            if part.len() == len && part.bytes().all(|b| b.is_ascii_digit()) {
+                part.parse().ok()
+            } else {
+                None
+            }
+        };
+
+        let year = number(parts.next()?, 4)?;
+        let month = match parts.next() {
+            Some(month) => Some(number(month, 2).filter(|m| (1..=12).contains(m))? as u8),
+            None => None,
+        };
+        let day = match parts.next() {
+            Some(day) => Some(number(day, 2).filter(|d| (1..=31).contains(d))? as u8),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return None;
         }
+        Some(DatePart { year, month, day })
     }
+}
 
-    /// Get the entry type.
-    ///
-    /// It represents the type of the publications such as article, book, ...
-    pub fn entry_type(&self) -> &str {
-        &self.entry_type
+impl fmt::Display for DatePart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "-{:02}", month)?;
+            if let Some(day) = self.day {
+                write!(f, "-{:02}", day)?;
+            }
+        }
+        Ok(())
     }
+}
 
-    /// Get the citation key.
+/// The date of a bibliography, see `Bibliography::date`.
+///
+/// ```
+/// use nom_bibtex::{BibDate, DatePart};
+///
+/// let date = BibDate::parse("2019-03/2019-06").unwrap();
+/// assert_eq!(date.year(), Some(2019));
+/// assert_eq!(date.month(), Some(3));
+/// assert_eq!(date.end().and_then(|end| end.month), Some(6));
+/// assert_eq!(date.to_string(), "2019-03/2019-06");
+///
+/// assert_eq!(BibDate::parse("2019/").unwrap().end(), None);
+/// assert_eq!(
+///     BibDate::parse("Spring 2019"),
+///     Some(BibDate::Raw("Spring 2019".into()))
+/// );
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BibDate {
+    /// A single date such as `2019`, `2019-03` or `2019-03-05`.
+    Single(DatePart),
+    /// A range such as `2019-03/2019-06`, an end being `None` when the range
+    /// is open such as `2019/`.
+    Range {
+        start: Option<DatePart>,
+        end: Option<DatePart>,
+    },
+    /// Any other value, kept as written.
+    Raw(String),
+}
+
+impl BibDate {
+    /// Parse a *BibLaTeX* date, `None` when it is empty.
     ///
-    /// The citation key is the the keyword used to reference the bibliography
-    /// in a LaTeX file for example.
-    pub fn citation_key(&self) -> &str {
-        &self.citation_key
+    /// Open ends of ranges can be empty or written `..`.
+    pub fn parse(value: &str) -> Option<BibDate> {
+        let value = trim_delimiters(value).trim();
+        if value.is_empty() {
+            return None;
+        }
+
+        let date = match value.split_once('/') {
+            None => DatePart::parse(value).map(BibDate::Single),
+            Some((start, end)) => {
+                let endpoint = |part: &str| match part.trim() {
+                    "" | ".." => Some(None),
+                    part => DatePart::parse(part).map(Some),
+                };
+                match (endpoint(start), endpoint(end)) {
+                    (Some(None), Some(None)) => None,
+                    (Some(start), Some(end)) => Some(BibDate::Range { start, end }),
+                    _ => None,
+                }
+            }
+        };
+        Some(date.unwrap_or_else(|| BibDate::Raw(value.into())))
     }
 
-    /// Get the tags.
-    ///
-    /// Tags are the specifics information about a bibliography
-    /// such as author, date, title, ...
-    pub fn tags(&self) -> HashMap<String, String> {
-        self.tags.clone()
+    /// The single date or the start of the range.
+    pub fn start(&self) -> Option<DatePart> {
+        match self {
+            BibDate::Single(date) => Some(*date),
+            BibDate::Range { start, .. } => *start,
+            BibDate::Raw(_) => None,
+        }
+    }
+
+    /// The end of the range, the single date being its own end.
+    pub fn end(&self) -> Option<DatePart> {
+        match self {
+            BibDate::Single(date) => Some(*date),
+            BibDate::Range { end, .. } => *end,
+            BibDate::Raw(_) => None,
+        }
+    }
+
+    /// The year of the start, or of the end for ranges with an open start.
+    pub fn year(&self) -> Option<i32> {
+        self.start().or_else(|| self.end()).map(|date| date.year)
+    }
+
+    /// The month of the start, or of the end for ranges with an open start.
+    pub fn month(&self) -> Option<u8> {
+        self.start().or_else(|| self.end())?.month
+    }
+
+    /// The day of the start, or of the end for ranges with an open start.
+    pub fn day(&self) -> Option<u8> {
+        self.start().or_else(|| self.end())?.day
+    }
+}
+
+impl fmt::Display for BibDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BibDate::Single(date) => date.fmt(f),
+            BibDate::Range { start, end } => {
+                if let Some(start) = start {
+                    start.fmt(f)?;
+                }
+                f.write_str("/")?;
+                if let Some(end) = end {
+                    end.fmt(f)?;
+                }
+                Ok(())
+            }
+            BibDate::Raw(value) => f.write_str(value),
+        }
     }
 }
 
@@ -218,6 +3723,7 @@ impl Bibliography {
 /// - strings value
 /// - string variable/abbreviation which will be expanded after parsing.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum StringValueType {
     /// Just a basic string.
     Str(String),
@@ -225,10 +3731,24 @@ pub enum StringValueType {
     Abbreviation(String),
 }
 
+// The string as written between its delimiters, or the name of the
+// abbreviation.
+impl fmt::Display for StringValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StringValueType::Str(value) | StringValueType::Abbreviation(value) => {
+                f.write_str(value)
+            }
+        }
+    }
+}
+
 /// Representation of a key-value.
 ///
-/// Only used by parsing.
+/// Only used by parsing, in `Entry`, the key is kept as written in the file.
+/// The value is the list of the parts concatenated with `#`.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct KeyValue {
     pub key: String,
     pub value: Vec<StringValueType>,
@@ -236,6 +3756,90 @@ pub struct KeyValue {
 
 impl KeyValue {
     pub fn new(key: String, value: Vec<StringValueType>) -> KeyValue {
-        Self { key: key.to_lowercase(), value }
+        Self { key, value }
+    }
+}
+
+// Bibliographies are serialized with their tags as a map keeping their order
+// and original spelling.
+//
+// Tags are deserialized in the order of the map, through `Bibliography::new`
+// to uphold the same invariants.
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::Bibliography;
+    use serde::de::{MapAccess, Visitor};
+    use serde::ser::{SerializeMap, SerializeStruct};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::fmt;
+
+    impl Serialize for Bibliography {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut state = serializer.serialize_struct("Bibliography", 3)?;
+            state.serialize_field("entry_type", &self.entry_type)?;
+            state.serialize_field("citation_key", &self.citation_key)?;
+            state.serialize_field("tags", &OrderedTags(self))?;
+            state.end()
+        }
+    }
+
+    struct OrderedTags<'a>(&'a Bibliography);
+
+    impl Serialize for OrderedTags<'_> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut map = serializer.serialize_map(Some(self.0.tags.len()))?;
+            for (key, (_, value)) in self.0.original_keys().zip(&self.0.tags) {
+                map.serialize_entry(key, value)?;
+            }
+            map.end()
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct BibliographyFields {
+        entry_type: String,
+        citation_key: String,
+        #[serde(deserialize_with = "deserialize_tags")]
+        tags: Vec<(String, String)>,
+    }
+
+    impl From<BibliographyFields> for Bibliography {
+        fn from(fields: BibliographyFields) -> Bibliography {
+            Bibliography::new(fields.entry_type, fields.citation_key, fields.tags)
+        }
+    }
+
+    fn deserialize_tags<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct OrderedMapVisitor;
+
+        impl<'de> Visitor<'de> for OrderedMapVisitor {
+            type Value = Vec<(String, String)>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of tags")
+            }
+
+            fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                let mut tags = Vec::with_capacity(access.size_hint().unwrap_or(0));
+                while let Some((key, value)) = access.next_entry()? {
+                    tags.push((key, value));
+                }
+                Ok(tags)
+            }
+        }
+
+        deserializer.deserialize_map(OrderedMapVisitor)
     }
 }
@@ -0,0 +1,289 @@
+//! Parsing of *BibTeX* names such as the `author` and `editor` tags.
+//!
+//! Names are separated by `and` and each name can be written in one of
+//! the three forms understood by *BibTeX*:
+//!
+//! - `First von Last`
+//! - `von Last, First`
+//! - `von Last, Jr, First`
+//!
+//! Words enclosed in brackets are never split and the `von` part is made of
+//! the words starting with a lowercase letter.
+
+use crate::error::BibtexError;
+use std::result;
+
+type Result<T> = result::Result<T, BibtexError>;
+
+/// A person name split in its four *BibTeX* parts.
+///
+/// Missing parts are empty strings.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Name {
+    pub first: String,
+    pub von: String,
+    pub last: String,
+    pub jr: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Comma,
+}
+
+/// Parse a list of names separated by `and`.
+pub fn parse_names(value: &str) -> Result<Vec<Name>> {
+    let tokens = tokenize(value)?;
+    if tokens.is_empty() {
+        return Ok(vec![]);
+    }
+
+    tokens
+        .split(|t| matches!(t, Token::Word(w) if w.eq_ignore_ascii_case("and")))
+        .map(|tokens| parse_name(tokens, value))
+        .collect()
+}
+
+// Split a value in words and commas outside of brackets.
+fn tokenize(value: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut depth = 0;
+    let mut start = None;
+
+    for (i, c) in value.char_indices() {
+        let is_separator = depth == 0 && (c.is_whitespace() || c == '~' || c == ',');
+        if is_separator {
+            if let Some(s) = start.take() {
+                tokens.push(Token::Word(&value[s..i]));
+            }
+            if c == ',' {
+                tokens.push(Token::Comma);
+            }
+            continue;
+        }
+
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => {
+                return Err(BibtexError::NameParsing(format!(
+                    "unbalanced brackets in `{}`",
+                    value
+                )))
+            }
+            '}' => depth -= 1,
+            _ => {}
+        }
+        if start.is_none() {
+            start = Some(i);
+        }
+    }
+
+    if depth != 0 {
+        return Err(BibtexError::NameParsing(format!(
+            "unbalanced brackets in `{}`",
+            value
+        )));
+    }
+    if let Some(s) = start {
+        tokens.push(Token::Word(&value[s..]));
+    }
+    Ok(tokens)
+}
+
+fn parse_name(tokens: &[Token], value: &str) -> Result<Name> {
+    let parts = tokens
+        .split(|t| *t == Token::Comma)
+        .map(|part| {
+            part.iter()
+                .filter_map(|t| match t {
+                    Token::Word(w) => Some(*w),
+                    Token::Comma => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    if parts[0].is_empty() {
+        return Err(BibtexError::NameParsing(format!(
+            "empty name in `{}`",
+            value
+        )));
+    }
+
+    let mut name = Name::default();
+    match parts.len() {
+        1 => {
+            let words = &parts[0];
+            let last_word = words.len() - 1;
+            match words[..last_word].iter().position(|w| is_von(w)) {
+                Some(von_start) => {
+                    let von_end = words[..last_word]
+                        .iter()
+                        .rposition(|w| is_von(w))
+                        .map_or(0, |i| i + 1);
+                    name.first = words[..von_start].join(" ");
+                    name.von = words[von_start..von_end].join(" ");
+                    name.last = words[von_end..].join(" ");
+                }
+                None => {
+                    name.first = words[..last_word].join(" ");
+                    name.last = words[last_word].into();
+                }
+            }
+        }
+        2 | 3 => {
+            let words = &parts[0];
+            let last_word = words.len() - 1;
+            let von_end = words[..last_word]
+                .iter()
+                .rposition(|w| is_von(w))
+                .map_or(0, |i| i + 1);
+            name.von = words[..von_end].join(" ");
+            name.last = words[von_end..].join(" ");
+            if parts.len() == 2 {
+                name.first = parts[1].join(" ");
+            } else {
+                name.jr = parts[1].join(" ");
+                name.first = parts[2].join(" ");
+            }
+        }
+        _ => {
+            return Err(BibtexError::NameParsing(format!(
+                "too many commas in `{}`",
+                value
+            )))
+        }
+    }
+    Ok(name)
+}
+
+// A word belongs to the von part when its first letter outside brackets is
+// lowercase. Special characters such as `{\'e}` count as letters.
+fn is_von(word: &str) -> bool {
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '{' {
+            if chars.peek() == Some(&'\\') {
+                // Special character, the case is the one of its first letter.
+                return chars
+                    .take_while(|c| *c != '}')
+                    .find(|c| c.is_alphabetic())
+                    .is_some_and(|c| c.is_lowercase());
+            }
+            // Skip the whole bracketed group, it has no case.
+            let mut depth = 1;
+            for c in chars.by_ref() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+        } else if c.is_alphabetic() {
+            return c.is_lowercase();
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(first: &str, von: &str, last: &str, jr: &str) -> Name {
+        Name {
+            first: first.into(),
+            von: von.into(),
+            last: last.into(),
+            jr: jr.into(),
+        }
+    }
+
+    #[test]
+    fn test_first_von_last() {
+        assert_eq!(
+            parse_names("Donald E. Knuth"),
+            Ok(vec![name("Donald E.", "", "Knuth", "")])
+        );
+        assert_eq!(
+            parse_names("Charles Louis Xavier Joseph de la Vallée Poussin"),
+            Ok(vec![name(
+                "Charles Louis Xavier Joseph",
+                "de la",
+                "Vallée Poussin",
+                ""
+            )])
+        );
+        assert_eq!(parse_names("Knuth"), Ok(vec![name("", "", "Knuth", "")]));
+    }
+
+    #[test]
+    fn test_von_last_first() {
+        assert_eq!(
+            parse_names("de la Vallée Poussin, Charles Louis Xavier Joseph"),
+            Ok(vec![name(
+                "Charles Louis Xavier Joseph",
+                "de la",
+                "Vallée Poussin",
+                ""
+            )])
+        );
+        assert_eq!(
+            parse_names("{van der Berg}, Jan"),
+            Ok(vec![name("Jan", "", "{van der Berg}", "")])
+        );
+        assert_eq!(
+            parse_names("von Neumann, John"),
+            Ok(vec![name("John", "von", "Neumann", "")])
+        );
+    }
+
+    #[test]
+    fn test_von_last_jr_first() {
+        assert_eq!(
+            parse_names("Ford, Jr., Henry"),
+            Ok(vec![name("Henry", "", "Ford", "Jr.")])
+        );
+        assert_eq!(
+            parse_names("Doe, Jr., John"),
+            Ok(vec![name("John", "", "Doe", "Jr.")])
+        );
+    }
+
+    #[test]
+    fn test_multiple_names() {
+        assert_eq!(
+            parse_names("Oren Patashnik and Knuth, Donald and {Barnes and Noble}"),
+            Ok(vec![
+                name("Oren", "", "Patashnik", ""),
+                name("Donald", "", "Knuth", ""),
+                name("", "", "{Barnes and Noble}", ""),
+            ])
+        );
+        assert_eq!(parse_names("  "), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_special_characters_case() {
+        assert_eq!(
+            parse_names("Jean {\\'e}mile Zola"),
+            Ok(vec![name("Jean", "{\\'e}mile", "Zola", "")])
+        );
+        assert_eq!(
+            parse_names("Kurt {\\\"O}del"),
+            Ok(vec![name("Kurt", "", "{\\\"O}del", "")])
+        );
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        assert!(parse_names("A and and B").is_err());
+        assert!(parse_names("a, b, c, d").is_err());
+        assert!(parse_names("{Unclosed Name").is_err());
+        assert!(parse_names("Closed} Name").is_err());
+    }
+}
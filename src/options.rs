@@ -0,0 +1,309 @@
+//! Options changing how a *BibTeX* file is parsed and written.
+//!
+//! ```
+//! use nom_bibtex::options::{DuplicatePolicy, ParseOptions};
+//! use nom_bibtex::Bibtex;
+//!
+//! let options = ParseOptions::new().duplicate_citation_keys(DuplicatePolicy::KeepLast);
+//! let bibtex = Bibtex::parse_with(
+//!     "@misc{ key, title = {First} }
+//!     @misc{ key, title = {Second} }",
+//!     &options,
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(bibtex.bibliographies().len(), 1);
+//! assert_eq!(bibtex.get("key").unwrap().get_tag("title"), Some("Second"));
+//! ```
+
+use std::collections::HashMap;
+
+/// The default maximum depth of the brackets nested in an entry, see
+/// `ParseOptions::max_nesting_depth`.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 100;
+
+/// What to do with bibliographies sharing the same citation key.
+///
+/// As in *BibTeX*, citation keys are case-sensitive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DuplicatePolicy {
+    /// Keep all the bibliographies.
+    #[default]
+    Allow,
+    /// Fail with `BibtexError::DuplicateCitationKey`.
+    Error,
+    /// Only keep the first bibliography with a citation key.
+    KeepFirst,
+    /// Only keep the last bibliography with a citation key.
+    KeepLast,
+    /// Keep all the bibliographies and report the duplicates with
+    /// `Warning::DuplicateCitationKey`.
+    Warn,
+}
+
+/// The options of `Bibtex::parse_with`.
+///
+/// The default options are the ones used by `Bibtex::parse`. New options can
+/// be added at any time, so options are only set with the builder methods.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub(crate) duplicates: DuplicatePolicy,
+    pub(crate) lenient_comments: bool,
+    pub(crate) lenient_commas: bool,
+    pub(crate) constants: HashMap<String, String>,
+    pub(crate) decode_latex: bool,
+    pub(crate) normalize_whitespace: bool,
+    pub(crate) verbatim_fields: Vec<String>,
+    pub(crate) max_nesting_depth: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            duplicates: DuplicatePolicy::default(),
+            lenient_comments: false,
+            lenient_commas: false,
+            constants: HashMap::new(),
+            decode_latex: false,
+            normalize_whitespace: false,
+            verbatim_fields: vec!["url".into(), "file".into()],
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> ParseOptions {
+        Self::default()
+    }
+
+    /// Set the policy for duplicated citation keys, `DuplicatePolicy::Allow`
+    /// by default.
+    pub fn duplicate_citation_keys(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicates = policy;
+        self
+    }
+
+    /// Keep the `@` which do not start an entry, as in `user@example.com`,
+    /// in the free text comments instead of failing, false by default.
+    ///
+    /// As with the other comments, only the whitespaces around the text are
+    /// removed.
+    pub fn lenient_comments(mut self, lenient: bool) -> Self {
+        self.lenient_comments = lenient;
+        self
+    }
+
+    /// Tolerate a comma missing between two tags when the second one starts
+    /// on another line, false by default.
+    ///
+    /// Each missing comma is reported with `Warning::MissingComma` by
+    /// `Bibtex::parse_verbose`.
+    ///
+    /// ```
+    /// use nom_bibtex::options::ParseOptions;
+    /// use nom_bibtex::warning::Warning;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let bib_str = "@misc{ key,
+    ///     title = {Title}
+    ///     author = {Author}
+    /// }";
+    /// assert!(Bibtex::parse(bib_str).is_err());
+    ///
+    /// let options = ParseOptions::new().lenient_commas(true);
+    /// let (bibtex, warnings) = Bibtex::parse_verbose(bib_str, &options).unwrap();
+    ///
+    /// assert_eq!(bibtex.get("key").unwrap().get_tag("author"), Some("Author"));
+    /// assert!(matches!(
+    ///     &warnings[..],
+    ///     [Warning::MissingComma { citation_key, location }]
+    ///         if citation_key == "key" && (location.line, location.column) == (2, 20)
+    /// ));
+    /// ```
+    pub fn lenient_commas(mut self, lenient: bool) -> Self {
+        self.lenient_commas = lenient;
+        self
+    }
+
+    /// Add a constant expanding the abbreviation `name`, as the predefined
+    /// months do.
+    ///
+    /// As abbreviations, names are case-insensitive. The `@string` variables
+    /// of the input take precedence over the constants, and the constants
+    /// over the months.
+    ///
+    /// ```
+    /// use nom_bibtex::options::ParseOptions;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let options = ParseOptions::new().constant("jacm", "Journal of the ACM");
+    /// let bibtex = Bibtex::parse_with("@article{ key, journal = jacm }", &options).unwrap();
+    ///
+    /// let biblio = bibtex.get("key").unwrap();
+    /// assert_eq!(biblio.get_tag("journal"), Some("Journal of the ACM"));
+    /// ```
+    pub fn constant(mut self, name: &str, value: &str) -> Self {
+        self.constants.insert(name.to_lowercase(), value.into());
+        self
+    }
+
+    /// Decode the *LaTeX* accents of the tags to Unicode once parsed, as
+    /// `Bibtex::decode_latex` does, false by default.
+    pub fn decode_latex(mut self, decode: bool) -> Self {
+        self.decode_latex = decode;
+        self
+    }
+
+    /// Collapse the runs of whitespaces and newlines inside the tags into a
+    /// single space once parsed, as *BibTeX* does, false by default.
+    ///
+    /// The verbatim fields are left untouched, see
+    /// `ParseOptions::verbatim_fields`.
+    ///
+    /// ```
+    /// use nom_bibtex::options::ParseOptions;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let options = ParseOptions::new().normalize_whitespace(true);
+    /// let bibtex = Bibtex::parse_with(
+    ///     "@misc{ key,
+    ///         title = {A long
+    ///                  title},
+    ///         url = {http://example.com/a  b}
+    ///     }",
+    ///     &options,
+    /// )
+    /// .unwrap();
+    ///
+    /// let biblio = bibtex.get("key").unwrap();
+    /// assert_eq!(biblio.get_tag("title"), Some("A long title"));
+    /// assert_eq!(biblio.get_tag("url"), Some("http://example.com/a  b"));
+    /// ```
+    pub fn normalize_whitespace(mut self, normalize: bool) -> Self {
+        self.normalize_whitespace = normalize;
+        self
+    }
+
+    /// Set the maximum depth of the brackets nested in an entry,
+    /// `DEFAULT_MAX_NESTING_DEPTH` by default.
+    ///
+    /// The brackets delimiting the entry are not counted, those delimiting a
+    /// value are. Deeper entries fail with `BibtexError::NestingTooDeep`, so
+    /// that untrusted inputs cannot exhaust the consumers of the values.
+    ///
+    /// ```
+    /// use nom_bibtex::error::BibtexError;
+    /// use nom_bibtex::options::ParseOptions;
+    /// use nom_bibtex::Bibtex;
+    ///
+    /// let options = ParseOptions::new().max_nesting_depth(2);
+    /// let bib_str = "@misc{ key, title = {A {{deep}} title} }";
+    ///
+    /// assert!(Bibtex::parse(bib_str).is_ok());
+    /// assert_eq!(
+    ///     Bibtex::parse_with(bib_str, &options),
+    ///     Err(BibtexError::NestingTooDeep {
+    ///         entry: "key".into(),
+    ///         line: 1,
+    ///         column: 25,
+    ///         max_depth: 2
+    ///     })
+    /// );
+    /// ```
+    pub fn max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = depth;
+        self
+    }
+
+    /// Set the fields whose whitespaces are kept by
+    /// `ParseOptions::normalize_whitespace`, `url` and `file` by default.
+    ///
+    /// Field names are case-insensitive.
+    pub fn verbatim_fields(mut self, fields: &[&str]) -> Self {
+        self.verbatim_fields = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+}
+
+/// How the values are delimited by `Bibtex::to_bibtex_string_pretty`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Delimiter {
+    /// Brackets, as in `{value}`, or quotes for the values with whitespaces
+    /// around them, which brackets would not keep.
+    #[default]
+    Braces,
+    /// Quotes, as in `"value"`, or brackets for the values with a quote
+    /// outside of nested brackets.
+    Quotes,
+}
+
+/// The options of `Bibtex::to_bibtex_string_pretty`.
+///
+/// The default options are the ones used by `Bibtex::to_bibtex_string`. As
+/// with `ParseOptions`, options are only set with the builder methods.
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    pub(crate) indent: usize,
+    pub(crate) max_line_length: Option<usize>,
+    pub(crate) align_equals: bool,
+    pub(crate) delimiter: Delimiter,
+    pub(crate) sort_fields: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            indent: 4,
+            max_line_length: None,
+            align_equals: false,
+            delimiter: Delimiter::default(),
+            sort_fields: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    pub fn new() -> WriteOptions {
+        Self::default()
+    }
+
+    /// Set the number of spaces before the tags, 4 by default.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Wrap the values of the tags so that their lines fit in `length`
+    /// characters, values are not wrapped by default.
+    ///
+    /// Lines are only broken at the spaces which are neither in nested
+    /// brackets nor ending a *LaTeX* command, and the next lines are aligned
+    /// after the opening delimiter of the value. A word longer than the line
+    /// is kept whole. *BibTeX* reads the line breaks as spaces, see
+    /// `ParseOptions::normalize_whitespace` to read them back as such.
+    pub fn max_line_length(mut self, length: usize) -> Self {
+        self.max_line_length = Some(length);
+        self
+    }
+
+    /// Align the `=` of the tags of an entry, false by default.
+    pub fn align_equals(mut self, align: bool) -> Self {
+        self.align_equals = align;
+        self
+    }
+
+    /// Set the delimiter of the values, `Delimiter::Braces` by default.
+    pub fn delimiter(mut self, delimiter: Delimiter) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Write the `author`, `title` and `year` tags first and the other ones
+    /// in alphabetical order, instead of the order of the entry, false by
+    /// default.
+    pub fn sort_fields(mut self, sort: bool) -> Self {
+        self.sort_fields = sort;
+        self
+    }
+}
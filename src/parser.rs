@@ -5,41 +5,124 @@
 // // Required because the compiler don't seems do recognize
 // // that macros are use inside of each others..
 //
-use crate::model::{KeyValue, StringValueType};
+use crate::model::{KeyValue, SourceLocation, StringValueType};
 use nom::character::complete::char as _char;
-use nom::error::ParseError;
+use nom::error::{ContextError, ErrorKind, ParseError};
 use nom::IResult;
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, take_until, take_while1},
-    character::complete::{digit1, multispace0},
-    combinator::{map, opt, peek},
+    bytes::complete::{is_not, tag_no_case, take_until, take_while1},
+    character::complete::{digit1, multispace0, multispace1, not_line_ending},
+    combinator::{map, not, opt, peek},
     multi::{separated_list0, separated_list1},
-    sequence::{delimited, preceded, separated_pair, tuple},
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
     AsChar, Slice,
 };
 use nom_locate::LocatedSpan;
 #[cfg(feature = "trace")]
 use nom_tracable::tracable_parser;
 use nom_tracable::TracableInfo;
+use std::cell::RefCell;
 use std::num::NonZeroUsize;
 use std::str;
 
-const NEEDED_ONE: nom::Needed = nom::Needed::Size(unsafe { NonZeroUsize::new_unchecked(1) });
+const NEEDED_ONE: nom::Needed = nom::Needed::Size(NonZeroUsize::new(1).unwrap());
+
+// Contexts of the failures on unclosed brackets, at the bracket and at the
+// start of the entry.
+pub const UNCLOSED_BRACKET: &str = "unclosed bracket";
+pub const ENTRY: &str = "entry";
+// Contexts of the failures in a tag, at its start, and of the other failures
+// with their own error, at the invalid input.
+pub const FIELD: &str = "field";
+pub const UNBALANCED_BRACES: &str = "unbalanced braces";
+pub const INVALID_FIELD_NAME: &str = "invalid field name";
+pub const EMPTY_CITATION_KEY: &str = "empty citation key";
 
 pub type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
 pub fn mkspan<'a>(s: &'a str) -> Span<'a> {
     Span::new_extra(s, TracableInfo::new())
 }
 
+/// A raw entry of a *BibTeX* file, as returned by `Bibtex::raw_parse` and
+/// `Bibtex::iter_entries`.
+///
+/// Values are kept as parsed, before the expansion of the string variables.
+/// This type is part of the stable API: its variants and their fields only
+/// change with a new major version.
+///
+/// ```
+/// use nom_bibtex::{Bibtex, Entry};
+///
+/// let entries = Bibtex::raw_parse(
+///     "@string{ name = {Name} }
+///     @misc{ key, author = name }",
+/// )
+/// .unwrap();
+///
+/// assert!(matches!(&entries[0], Entry::Variable(v) if v.key == "name"));
+/// assert_eq!(entries[1].citation_key(), Some("key"));
+/// assert_eq!(entries[1].tags()[0].key, "author");
+/// ```
 #[derive(Debug, PartialEq, Eq)]
 pub enum Entry {
+    /// The value of a `@preamble` entry.
     Preamble(Vec<StringValueType>),
+    /// Text outside of the entries, or a line starting with a `%` kept with
+    /// its `%`.
     Comment(String),
+    /// The content of a `@comment` entry.
+    CommentBlock(String),
+    /// A `@string` entry with its key as written.
     Variable(KeyValue),
+    /// A bibliography with its entry type, citation key and tags, the types
+    /// and keys being as written.
     Bibliography(String, String, Vec<KeyValue>),
 }
 
+impl Entry {
+    /// Get the entry type of a bibliography, such as `article`, or the type
+    /// of the other entries: `preamble`, `comment` or `string`.
+    ///
+    /// The type of a bibliography is as written, the other ones are
+    /// lowercase. Comments outside of the entries have no type.
+    pub fn entry_type(&self) -> Option<&str> {
+        match self {
+            Entry::Preamble(_) => Some("preamble"),
+            Entry::Comment(_) => None,
+            Entry::CommentBlock(_) => Some("comment"),
+            Entry::Variable(_) => Some("string"),
+            Entry::Bibliography(entry_type, _, _) => Some(entry_type),
+        }
+    }
+
+    /// Get the citation key of a bibliography.
+    pub fn citation_key(&self) -> Option<&str> {
+        match self {
+            Entry::Bibliography(_, citation_key, _) => Some(citation_key),
+            _ => None,
+        }
+    }
+
+    /// Get the tags of a bibliography, empty for the other entries.
+    pub fn tags(&self) -> &[KeyValue] {
+        match self {
+            Entry::Bibliography(_, _, tags) => tags,
+            _ => &[],
+        }
+    }
+}
+
+// The errors which are tolerated by the lenient parsers.
+#[derive(Debug, Default)]
+pub struct Lenience {
+    // Whether a @ which does not start an entry is part of a comment.
+    pub comments: bool,
+    // When set, a comma missing between two tags is tolerated and the
+    // location where it is missing is recorded.
+    pub missing_commas: Option<RefCell<Vec<SourceLocation>>>,
+}
+
 // Defines a parser with a common type signature
 macro_rules! def_parser {
     ($vis:vis $name:ident(
@@ -49,9 +132,9 @@ macro_rules! def_parser {
         // with this directive included
         #[cfg_attr(feature = "trace", tracable_parser)]
         $vis fn $name<'a, E> (
-            $input_name: Span<'a>, $($arg: $ty),*
+            $input_name: Span<'a>, $($arg: $type),*
         ) -> IResult<Span<'a>, $ret, E>
-            where E: ParseError<Span<'a>>,
+            where E: ParseError<Span<'a>> + ContextError<Span<'a>>,
         {
             $body
         }
@@ -110,8 +193,128 @@ fn span_to_str<'a>(span: Span<'a>) -> &'a str {
     span.fragment()
 }
 
+// Fails without backtracking on a bracket which is never closed.
+fn unclosed_bracket<'a, O, E>(bracket: Span<'a>) -> IResult<Span<'a>, O, E>
+where
+    E: ParseError<Span<'a>> + ContextError<Span<'a>>,
+{
+    let err = E::from_char(bracket, '}');
+    Err(nom::Err::Failure(E::add_context(
+        bracket,
+        UNCLOSED_BRACKET,
+        err,
+    )))
+}
+
+// Fails without backtracking after the leading whitespaces of `input`, the
+// context being one of the contexts above or a message explaining what is
+// expected there.
+fn failure<'a, O, E>(input: Span<'a>, context: &'static str) -> IResult<Span<'a>, O, E>
+where
+    E: ParseError<Span<'a>> + ContextError<Span<'a>>,
+{
+    let fragment = input.fragment();
+    let input = input.slice(fragment.len() - fragment.trim_start().len()..);
+    let err = E::from_error_kind(input, ErrorKind::Verify);
+    Err(nom::Err::Failure(E::add_context(input, context, err)))
+}
+
+// Fails on a `key = value` pair which cannot be parsed, with the first of
+// the messages when the value is invalid, the second when the `=` is
+// missing and the last when there is no key.
+fn key_value_error<'a, O, E>(
+    input: Span<'a>,
+    messages: [&'static str; 3],
+) -> IResult<Span<'a>, O, E>
+where
+    E: ParseError<Span<'a>> + ContextError<Span<'a>>,
+{
+    let [value, equals, key] = messages;
+    if let Ok((rest, _)) = tuple((pws!(ident::<E>), dws!(_char('='))))(input) {
+        return failure(rest, value);
+    }
+    match pws!(ident::<E>)(input) {
+        Ok((rest, _)) => failure(rest, equals),
+        Err(_) => failure(input, key),
+    }
+}
+
+/// Name an entry in error messages, from the input starting at its `@`.
+///
+/// Bibliographies are named by their citation key and other entries by
+/// their type.
+pub fn entry_name(entry: &str) -> String {
+    type Error<'a> = (Span<'a>, ErrorKind);
+
+    let (rest, entry_t) = match entry_type::<Error>(mkspan(entry)) {
+        Ok(v) => v,
+        Err(_) => return "@".into(),
+    };
+    let entry_t = entry_t.to_lowercase();
+    if ["comment", "string", "preamble"].contains(&entry_t.as_str()) {
+        return format!("@{}", entry_t);
+    }
+    let opening = alt((_char::<_, Error>('{'), _char('(')));
+    match preceded(pws!(opening), take_until(","))(rest) {
+        Ok((_, key)) if !key.fragment().contains('\n') => key.fragment().trim().into(),
+        _ => format!("@{}", entry_t),
+    }
+}
+
+/// Get the lowercase entry type of an entry, from the input starting at its
+/// `@`.
+pub fn entry_type_name(entry: &str) -> String {
+    type Error<'a> = (Span<'a>, ErrorKind);
+
+    match entry_type::<Error>(mkspan(entry)) {
+        Ok((_, entry_t)) => entry_t.to_lowercase(),
+        Err(_) => String::new(),
+    }
+}
+
+/// Get the name of a field, from the input starting at the field: the text
+/// before the `=` of its line.
+pub fn field_name(tag: &str) -> String {
+    let line = tag.lines().next().unwrap_or("");
+    line.split('=').next().unwrap_or("").trim().into()
+}
+
+/// Check that a name can be written as a field or string variable name.
+pub fn is_valid_name(name: &str) -> bool {
+    type Error<'a> = (Span<'a>, ErrorKind);
+
+    matches!(ident::<Error>(mkspan(name)), Ok((rest, _)) if rest.fragment().is_empty())
+}
+
+/// Check that a citation key can be written in an entry, without
+/// whitespaces or delimiters.
+#[cfg(feature = "csl")]
+pub fn is_valid_citation_key(citation_key: &str) -> bool {
+    !citation_key.is_empty() && citation_key.chars().all(is_citation_key_char)
+}
+
+// Whether a character can be part of a citation key.
+fn is_citation_key_char(c: char) -> bool {
+    !c.is_whitespace() && !",{}()=\"#".contains(c)
+}
+
+/// Check that the braces of a value are balanced, so that it can be written
+/// delimited.
+pub fn has_balanced_braces(value: &str) -> bool {
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return false,
+            '}' => depth -= 1,
+            _ => continue,
+        }
+    }
+    depth == 0
+}
+
 // Parses a single identifier
-def_parser!(ident(input) -> &str; {
+def_parser!(ident(input) -> &'a str; {
     map(
         take_while1(|c: char| c.is_alphanum() || c == '_' || c == '-'),
         span_to_str
@@ -126,10 +329,21 @@ def_parser!(abbreviation_only(input) -> StringValueType; {
     )(input)
 });
 
-// Only used for bibliography tags.
-def_parser!(bracketed_string(input) -> &str; {
+// Parses a bare number, which is not part of an identifier such as 2nd.
+def_parser!(number(input) -> StringValueType; {
+    map(
+        terminated(digit1, not(ident)),
+        |v| StringValueType::Str(span_to_str(v).into())
+    )(input)
+});
+
+// Parses a value delimited by brackets, keeping the inner brackets.
+//
+// Brackets are counted rather than parsed recursively, so that deeply nested
+// values cannot overflow the stack.
+def_parser!(bracketed_string(input) -> &'a str; {
     // We are not in a bracketed_string.
-    match input.fragment().chars().nth(0) {
+    match input.fragment().chars().next() {
         Some('{') => {},
         Some(_) => {
             return Err(nom::Err::Error(E::from_char(input, '{')));
@@ -141,27 +355,56 @@ def_parser!(bracketed_string(input) -> &str; {
 
     let mut brackets_queue = 0;
 
-    let mut last_idx = 0;
     for (i, c) in input.fragment().char_indices().skip(1) {
-        last_idx = i+1;
-        match c as char {
+        match c {
             '{' => brackets_queue += 1,
             '}' => if brackets_queue == 0 {
-                break;
+                return Ok((
+                    input.slice(i+1..),
+                    span_to_str(input.slice(1..i)).trim()
+                ));
             } else {
                 brackets_queue -= 1;
             },
             _ => continue,
         }
     }
-    Ok((
-        input.slice(last_idx..),
-        span_to_str(input.slice(1..last_idx-1)).trim()
-    ))
+    unclosed_bracket(input)
 });
 
-def_parser!(quoted_string(input) -> &str; {
-    match input.fragment().chars().nth(0) {
+// Parses a value delimited by parentheses, keeping the inner brackets and
+// parentheses. As in a bracketed value, the brackets must be balanced.
+def_parser!(parenthesized_string(input) -> &'a str; {
+    match input.fragment().chars().next() {
+        Some('(') => {},
+        Some(_) => {
+            return Err(nom::Err::Error(E::from_char(input, '(')));
+        }
+        None => {
+            return Err(nom::Err::Incomplete(NEEDED_ONE));
+        }
+    }
+
+    let mut brackets_queue = 0;
+
+    for (i, c) in input.fragment().char_indices().skip(1) {
+        match c {
+            '{' => brackets_queue += 1,
+            '}' if brackets_queue > 0 => brackets_queue -= 1,
+            ')' if brackets_queue == 0 => {
+                return Ok((
+                    input.slice(i+1..),
+                    span_to_str(input.slice(1..i)).trim()
+                ));
+            }
+            _ => continue,
+        }
+    }
+    unclosed_bracket(input)
+});
+
+def_parser!(quoted_string(input) -> &'a str; {
+    match input.fragment().chars().next() {
         Some('"') => {},
         Some(_) => {
             return Err(nom::Err::Error(E::from_char(input, '"')));
@@ -171,27 +414,34 @@ def_parser!(quoted_string(input) -> &str; {
         }
     }
     let mut brackets_queue = 0;
-    let mut last_idx = 0;
+    // Whether the previous character is a backslash which is not escaped,
+    // a \" being a quote kept in the value, as in the LaTeX umlaut.
+    let mut escaped = false;
     for (i, c) in input.fragment().char_indices().skip(1) {
-        last_idx = i+1;
-        match c as char {
+        let after_backslash = escaped;
+        escaped = c == '\\' && !after_backslash;
+        match c {
+            '"' if after_backslash => continue,
             '{' => brackets_queue += 1,
             '}' => {
                 brackets_queue -= 1;
                 if brackets_queue < 0 {
-                    return Err(nom::Err::Error(E::from_char(input, '"')));
+                    return failure(input.slice(i..), UNBALANCED_BRACES);
                 }
             }
             '"' => if brackets_queue == 0 {
-                break;
+                return Ok((
+                    input.slice(i+1..),
+                    span_to_str(input.slice(1..i))
+                ));
             },
             _ => continue,
         }
     }
-    Ok((
-        input.slice(last_idx..),
-        span_to_str(input.slice(1..last_idx-1))
-    ))
+    if brackets_queue > 0 {
+        return failure(input, UNBALANCED_BRACES);
+    }
+    Err(nom::Err::Error(E::from_char(input, '"')))
 });
 
 def_parser!(pub abbreviation_string(input) -> Vec<StringValueType>; {
@@ -199,8 +449,10 @@ def_parser!(pub abbreviation_string(input) -> Vec<StringValueType>; {
         pws!(_char('#')),
         pws!(
             alt((
+                number,
                 abbreviation_only,
-                map(quoted_string, |v: &str| StringValueType::Str(v.into()))
+                map(quoted_string, |v: &str| StringValueType::Str(v.into())),
+                map(bracketed_string, |v: &str| StringValueType::Str(v.into()))
             ))
         )
     )(input)
@@ -210,7 +462,7 @@ def_parser!(pub abbreviation_string(input) -> Vec<StringValueType>; {
 // @type{ ...
 //
 // But don't consume the last bracket.
-def_parser!(entry_type(input) -> &str; {
+def_parser!(entry_type(input) -> &'a str; {
     delimited(
         pws!(_char('@')),
         pws!(ident),
@@ -220,158 +472,409 @@ def_parser!(entry_type(input) -> &str; {
 
 // Parse key value pair which has the form:
 // key="value"
+//
+// The value can be a concatenation such as key = "value" # var.
 def_parser!(variable_key_value_pair(input) -> KeyValue; {
     map(
         separated_pair(
             pws!(ident),
             dws!(_char('=')),
-            alt((
-                map(quoted_string, |v: &str| vec!(StringValueType::Str(v.into()))),
-                abbreviation_string,
-                map(abbreviation_only, |v| vec!(v)),
-            ))
+            abbreviation_string
         ),
-        |v: (&str, Vec<StringValueType>)| KeyValue::new(v.0.into(), v.1.into())
+        |v: (&str, Vec<StringValueType>)| KeyValue::new(v.0.into(), v.1)
     )(input)
 });
 
-// String variable can be delimited by brackets or parenthesis.
-def_parser!(handle_variable(input) -> KeyValue; {
-    alt((
-        delimited(
-            pws!(_char('{')),
-            dws!(variable_key_value_pair),
-            peek(_char('}'))
-        ),
-        delimited(
-            pws!(_char('(')),
-            dws!(variable_key_value_pair),
-            peek(_char(')'))
-        )
-    ))(input)
-});
-
 // Handle a string variable from the bibtex format:
 // @String (key = "value") or @String {key = "value"}
 def_parser!(variable(input) -> Entry; {
     chain_parsers!(input, rest;
         entry_type,
-        handle_variable => key_val,
-        alt((_char('}'), _char(')')))
+        pws!(alt((_char('{'), _char('(')))) => opening
     );
+    let (rest, key_val) = match dws!(variable_key_value_pair::<E>)(rest) {
+        Err(nom::Err::Error(_)) | Err(nom::Err::Incomplete(_)) => {
+            return key_value_error(rest, [
+                "expected string value",
+                "expected `=` after variable name",
+                "expected variable name",
+            ]);
+        }
+        result => result?,
+    };
+    let message = match closing_delimiter(opening) {
+        '}' => "expected `}` after string value",
+        _ => "expected `)` after string value",
+    };
+    let (rest, _) = match _char::<_, E>(closing_delimiter(opening))(rest) {
+        Err(nom::Err::Error(_)) => return failure(rest, message),
+        result => result?,
+    };
     Ok((rest, Entry::Variable(key_val)))
 });
 
 // Handle a preamble of the format:
 // @Preamble { "my preamble" }
+// or
+// @Preamble ( "my preamble" )
 def_parser!(preamble(input) -> Entry; {
     chain_parsers!(input, rest;
         entry_type,
-        pws!(_char('{')),
-        abbreviation_string => preamble,
-        pws!(_char('}'))
+        pws!(alt((_char('{'), _char('(')))) => opening
     );
+    let (rest, preamble) = match abbreviation_string::<E>(rest) {
+        Err(nom::Err::Error(_)) | Err(nom::Err::Incomplete(_)) => return failure(rest, "expected preamble value"),
+        result => result?,
+    };
+    let message = match closing_delimiter(opening) {
+        '}' => "expected `}` after preamble value",
+        _ => "expected `)` after preamble value",
+    };
+    let (rest, _) = match pws!(_char::<_, E>(closing_delimiter(opening)))(rest) {
+        Err(nom::Err::Error(_)) => return failure(rest, message),
+        result => result?,
+    };
     Ok((rest, Entry::Preamble(preamble)))
 });
 
+// Parse a tag of a bibliography entry.
+//
+// Failures are given the start of the tag to name its field in errors.
+def_parser!(bib_tag(input) -> KeyValue; {
+    let result = map(
+        separated_pair(
+            ident,
+            dws!(_char('=')),
+            alt((
+                abbreviation_string,
+                map(quoted_string, |v| vec![StringValueType::Str(v.into())]),
+                map(bracketed_string, |v| vec![StringValueType::Str(v.into())]),
+                map(abbreviation_only, |v| vec![v]),
+            ))
+        ),
+        |v: (&str, Vec<StringValueType>)| KeyValue::new(v.0.into(), v.1)
+    )(input);
+    match result {
+        Err(nom::Err::Failure(e)) => Err(nom::Err::Failure(E::add_context(input, FIELD, e))),
+        result => result,
+    }
+});
+
+// Handle the whitespaces between two tags when the comma is missing, the
+// next tag starting on another line.
+def_parser!(missing_comma(input) -> (); {
+    let (rest, spaces) = multispace1(input)?;
+    if !spaces.fragment().contains('\n') {
+        return Err(nom::Err::Error(E::from_char(input, ',')));
+    }
+    let (rest, _) = peek(tuple((ident, multispace0, _char('='))))(rest)?;
+    Ok((rest, ()))
+});
+
 // Parse all the tags used by one bibliography entry separated by a comma.
-def_parser!(bib_tags(input) -> Vec<KeyValue>; {
-    separated_list0(
-        dws!(_char(',')),
-        map(
-            separated_pair(
-                ident,
-                dws!(_char('=')),
-                alt((
-                    map(digit1, |v| vec!(StringValueType::Str(span_to_str(v).into()))),
-                    abbreviation_string,
-                    map(quoted_string, |v| vec![StringValueType::Str(v.into())]),
-                    map(bracketed_string, |v| vec![StringValueType::Str(v.into())]),
-                    map(abbreviation_only, |v| vec![v]),
-                ))
-            ),
-            |v: (&str, Vec<StringValueType>)| KeyValue::new(v.0.into(), v.1.into())
-        )
-    )(input)
+//
+// When `missing_commas` is set, the locations of the missing commas are
+// recorded instead of stopping at the first one.
+def_parser!(bib_tags(input, missing_commas, Option<&RefCell<Vec<SourceLocation>>>) -> Vec<KeyValue>; {
+    let missing_commas = match missing_commas {
+        Some(missing_commas) => missing_commas,
+        None => return separated_list0(dws!(_char(',')), bib_tag)(input),
+    };
+
+    let mut tags = vec![];
+    let mut rest = match bib_tag::<E>(input) {
+        Ok((rest, tag)) => {
+            tags.push(tag);
+            rest
+        }
+        Err(nom::Err::Error(_)) => return Ok((input, tags)),
+        Err(e) => return Err(e),
+    };
+    loop {
+        let (next, missing) = match dws!(_char::<_, E>(','))(rest) {
+            Ok((next, _)) => (next, false),
+            Err(nom::Err::Error(_)) => match missing_comma::<E>(rest) {
+                Ok((next, _)) => (next, true),
+                Err(nom::Err::Error(_)) => break,
+                Err(e) => return Err(e),
+            },
+            Err(e) => return Err(e),
+        };
+        match bib_tag::<E>(next) {
+            Ok((next, tag)) => {
+                if missing {
+                    missing_commas.borrow_mut().push(SourceLocation {
+                        line: rest.location_line(),
+                        column: rest.get_utf8_column(),
+                        offset: rest.location_offset(),
+                    });
+                }
+                tags.push(tag);
+                rest = next;
+            }
+            Err(nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((rest, tags))
 });
 
+// The closing delimiter of an entry opened with a bracket or a parenthesis.
+fn closing_delimiter(opening: char) -> char {
+    if opening == '(' {
+        ')'
+    } else {
+        '}'
+    }
+}
+
 // Handle a bibliography entry of the format:
 // @entry_type { citation_key,
 //     tag1,
 //     tag2
 // }
-def_parser!(bibliography_entry(input) -> Entry; {
-    chain_parsers! (input, rem;
+//
+// Parentheses can be used instead of the brackets around the tags. The entry
+// is unclosed when the next entry, the end of the input or the other closing
+// delimiter is found instead of the last one.
+//
+// As in BibTeX, the comma after the last tag is optional, as well as the one
+// after the citation key of an entry without tags.
+def_parser!(bibliography_entry(input, missing_commas, Option<&RefCell<Vec<SourceLocation>>>) -> Entry; {
+    chain_parsers! (input, rest;
         entry_type => entry_t ,
-        dws!(_char('{')),
-        map(take_until(","), span_to_str) => citation_key,
-        dws!(_char(',')),
-        bib_tags => tags ,
-        opt(pws!(_char(','))),
-        pws!(_char('}'))
+        multispace0
     );
-    Ok((rem, Entry::Bibliography(entry_t.into(), citation_key.into(), tags.into())))
+    let bracket = rest;
+    let no_tags = tuple::<_, _, E, _>((
+        dws!(alt((_char('{'), _char('(')))),
+        map(take_while1(is_citation_key_char), span_to_str),
+        multispace0,
+    ))(rest);
+    if let Ok((rem, (opening, citation_key, _))) = no_tags {
+        if let Ok((rem, _)) = _char::<_, E>(closing_delimiter(opening))(rem) {
+            return Ok((rem, Entry::Bibliography(entry_t.into(), citation_key.into(), vec![])));
+        }
+    }
+    let (rest, opening) = dws!(alt((_char('{'), _char('('))))(rest)?;
+    if rest.fragment().starts_with([',', closing_delimiter(opening)]) {
+        return failure(rest, EMPTY_CITATION_KEY);
+    }
+    let (rest, citation_key) = match take_until::<_, _, E>(",")(rest) {
+        Err(nom::Err::Error(_)) => return failure(rest, "expected `,` after citation key"),
+        result => result?,
+    };
+    let (rest, _) = dws!(_char(','))(rest)?;
+    let (rest, tags) = match bib_tags::<E>(rest, missing_commas) {
+        // The input ends in a value.
+        Err(nom::Err::Incomplete(_)) => return unclosed_bracket(bracket),
+        result => result?,
+    };
+    let (rest, comma) = opt(pws!(_char(',')))(rest)?;
+    let (rem, _) = match pws!(_char::<_, E>(closing_delimiter(opening)))(rest) {
+        Err(nom::Err::Error(_))
+            if rest.fragment().trim_start().is_empty()
+                || rest.fragment().trim_start().starts_with(['@', '}', ')']) =>
+        {
+            return unclosed_bracket(bracket);
+        }
+        Err(nom::Err::Error(_)) if !tags.is_empty() && comma.is_none() => {
+            let message = match closing_delimiter(opening) {
+                '}' => "expected `,` or `}` after field value",
+                _ => "expected `,` or `)` after field value",
+            };
+            return failure(rest, message);
+        }
+        Err(nom::Err::Error(_)) if invalid_field_name(rest.fragment()) => {
+            return failure(rest, INVALID_FIELD_NAME);
+        }
+        Err(nom::Err::Error(_)) => {
+            return key_value_error(rest, [
+                "expected field value",
+                "expected `=` after field name",
+                "expected field name",
+            ]);
+        }
+        result => result?,
+    };
+    Ok((rem, Entry::Bibliography(entry_t.into(), span_to_str(citation_key).into(), tags)))
 });
 
+// Whether the text before the `=` of the first line of the tags is not an
+// identifier. Lines without `=` are other errors.
+fn invalid_field_name(tags: &str) -> bool {
+    let line = tags.trim_start().lines().next().unwrap_or("");
+    match line.split_once('=') {
+        Some((name, _)) => {
+            let name = name.trim();
+            name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_alphanum() || c == '_' || c == '-')
+        }
+        None => false,
+    }
+}
+
 // Handle a comment of the format:
 // @Comment { my comment }
+// or
+// @Comment ( my comment )
 def_parser!(type_comment(input) -> Entry; {
-    chain_parsers!(input, rem;
-        entry_type,
-        bracketed_string => comment
-    );
-    Ok((rem, Entry::Comment(comment.into())))
+    let (rest, _) = entry_type(input)?;
+    let (rem, comment) = alt((bracketed_string, parenthesized_string))(rest)?;
+    Ok((rem, Entry::CommentBlock(comment.into())))
+});
+
+// Handle a comment without delimiters, up to the end of the line:
+// @Comment my comment
+def_parser!(line_comment(input) -> Entry; {
+    let (rest, _) = pws!(tag_no_case("@comment"))(input)?;
+    if rest.fragment().starts_with(|c: char| !c.is_whitespace()) {
+        return Err(nom::Err::Error(E::from_error_kind(rest, ErrorKind::Space)));
+    }
+    let (rem, comment) = not_line_ending(rest)?;
+    Ok((rem, Entry::CommentBlock(span_to_str(comment).trim().into())))
 });
 
 // Same as entry_type but with peek so it doesn't consume the
 // entry type.
-def_parser!(peeked_entry_type(input) -> &str; {
+def_parser!(peeked_entry_type(input) -> &'a str; {
     peek(entry_type)(input)
 });
 
 // Parse any entry which starts with a @.
-def_parser!(entry_with_type(input) -> Entry; {
+//
+// Failures are given the start of the entry to name it in errors.
+def_parser!(entry_with_type(input, lenience, &Lenience) -> Entry; {
     let entry_type = peeked_entry_type::<E>(input)?;
 
-    match entry_type.1.to_lowercase().as_ref() {
+    let result = match entry_type.1.to_lowercase().as_ref() {
         "comment" => type_comment(input),
         "string" => variable(input),
         "preamble" => preamble(input),
-        _ => bibliography_entry(input),
+        _ => bibliography_entry(input, lenience.missing_commas.as_ref()),
+    };
+    match result {
+        Err(nom::Err::Failure(e)) => Err(nom::Err::Failure(E::add_context(input, ENTRY, e))),
+        result => result,
+    }
+});
+
+// Handle data beginning without an @ which are considered comments, up to
+// a line starting with a %.
+def_parser!(no_type_comment(input) -> &'a str; {
+    let (rest, comment) = is_not("@")(input)?;
+    match percent_line_start(comment.fragment()) {
+        Some(end) => Ok((input.slice(end..), &input.fragment()[..end])),
+        None => Ok((rest, span_to_str(comment))),
     }
 });
 
-// Handle data beginning without an @ which are considered comments.
-def_parser!(no_type_comment(input) -> &str; {
-    map(is_not("@"), span_to_str)(input)
+// Handle a TeX comment, from a % to the end of the line:
+// % my comment
+def_parser!(percent_comment(input) -> Entry; {
+    let (rest, comment) = preceded(peek(_char('%')), not_line_ending)(input)?;
+    Ok((rest, Entry::Comment(span_to_str(comment).trim_end().into())))
 });
 
+// The offset of the first % starting a line after the first one, the
+// whitespaces before it being ignored.
+fn percent_line_start(text: &str) -> Option<usize> {
+    text.match_indices('%').map(|(i, _)| i).find(|&i| {
+        text[..i]
+            .rfind('\n')
+            .is_some_and(|line| text[line..i].trim().is_empty())
+    })
+}
+
 // Parse any entry in a bibtex file.
 // A good entry starts with a @ otherwise, it's
-// considered as a comment.
-def_parser!(entry(input) -> Entry; {
-    pws!(
+// considered as a comment. A line starting with a % is a comment up to its
+// end, so that its @ do not start entries.
+#[cfg(test)]
+def_parser!(pub entry(input) -> Entry; {
+    entry_with(input, &Lenience::default())
+});
+
+// Same as entry, tolerating some errors.
+def_parser!(pub entry_with(input, lenience, &Lenience) -> Entry; {
+    let comment = |i| if lenience.comments {
+        lenient_comment(i)
+    } else {
+        no_type_comment(i)
+    };
+    let result = pws!(
         alt((
-            entry_with_type,
-            map(no_type_comment, |v| Entry::Comment(v.to_string().trim().into()))
+            |i| entry_with_type(i, lenience),
+            line_comment,
+            percent_comment,
+            map(comment, |v| Entry::Comment(v.trim().into()))
         ))
-    )(input)
+    )(input);
+    match result {
+        Err(nom::Err::Error(e)) => invalid_entry(input, e),
+        result => result,
+    }
+});
+
+// Fails on a @ which starts neither an entry nor a comment. Other errors
+// are returned unchanged.
+fn invalid_entry<'a, O, E>(input: Span<'a>, err: E) -> IResult<Span<'a>, O, E>
+where
+    E: ParseError<Span<'a>> + ContextError<Span<'a>>,
+{
+    let rest = match pws!(_char::<_, E>('@'))(input) {
+        Ok((rest, _)) => rest,
+        Err(_) => return Err(nom::Err::Error(err)),
+    };
+    let rest = match pws!(ident::<E>)(rest) {
+        Ok((rest, _)) => rest,
+        Err(_) => return failure(rest, "expected entry type after `@`"),
+    };
+    match pws!(alt((_char::<_, E>('{'), _char('('))))(rest) {
+        Ok(_) => Err(nom::Err::Error(err)),
+        Err(_) => failure(rest, "expected `{` or `(` after entry type"),
+    }
+}
+
+// Handle data up to the next @ starting an entry or the next line starting
+// with a %, the other @ being part of the comment.
+def_parser!(lenient_comment(input) -> &'a str; {
+    let fragment = input.fragment();
+    let end = fragment
+        .match_indices('@')
+        .map(|(i, _)| i)
+        .find(|&i| {
+            let rest = input.slice(i..);
+            peeked_entry_type::<E>(rest).is_ok() || line_comment::<E>(rest).is_ok()
+        })
+        .unwrap_or(fragment.len());
+    let end = percent_line_start(&fragment[..end]).unwrap_or(end);
+    if end == 0 {
+        return Err(nom::Err::Error(E::from_error_kind(input, ErrorKind::IsNot)));
+    }
+    Ok((input.slice(end..), &fragment[..end]))
 });
 
 // Parses a whole bibtex file to yield a list of entries
+#[cfg(test)]
 def_parser!(pub entries(input) -> Vec<Entry>; {
-    if input.fragment().trim().is_empty() {
-        Ok((input, vec!()))
-    }
-    else {
-        let (rest_slice, new_entry) = entry(input)?;
-        let (remaining_slice, mut rest_entries) = entries(rest_slice)?;
-        // NOTE: O(n) insertions, could cause issues in the future
-        rest_entries.insert(0, new_entry);
-        Ok((remaining_slice, rest_entries))
+    let mut entries = vec!();
+    let mut input = input;
+    while !is_end(input) {
+        let (rest, new_entry) = entry(input)?;
+        entries.push(new_entry);
+        input = rest;
     }
+    Ok((input, entries))
 });
 
+// Only whitespaces are remaining after the last entry.
+pub fn is_end(input: Span) -> bool {
+    input.fragment().trim().is_empty()
+}
+
 #[cfg(test)]
 mod tests {
     // Each time we are using `separated_list`, we need to add a trailing
@@ -429,7 +932,10 @@ mod tests {
             ),
         ];
         assert_eq!(
-            str_err!(entry_with_type::<Error>(mkspan(bib_str))),
+            str_err!(entry_with_type::<Error>(
+                mkspan(bib_str),
+                &Lenience::default()
+            )),
             Ok((
                 "",
                 Entry::Bibliography("misc".to_string(), "patashnik-bibtexing".to_string(), tags)
@@ -478,7 +984,10 @@ mod tests {
             ),
         ];
         assert_eq!(
-            str_err!(entry_with_type::<Error>(mkspan(bib_str))),
+            str_err!(entry_with_type::<Error>(
+                mkspan(bib_str),
+                &Lenience::default()
+            )),
             Ok((
                 "",
                 Entry::Bibliography("misc".to_string(), "patashnik-bibtexing".to_string(), tags)
@@ -498,11 +1007,81 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_percent_comment() {
+        assert_eq!(
+            str_err!(entry::<Error>(mkspan("  % see @misc{ a } \n@misc{ b }"))),
+            Ok(("\n@misc{ b }", Entry::Comment("% see @misc{ a }".into())))
+        );
+        assert_eq!(
+            str_err!(no_type_comment::<Error>(mkspan("text 100%\n  % note\n"))),
+            Ok(("% note\n", "text 100%\n  "))
+        );
+    }
+
+    #[test]
+    fn test_lenient_comments() {
+        let lenience = Lenience {
+            comments: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            str_err!(entry_with::<Error>(
+                mkspan(" mail a@b.com @ home\n@misc{ a, }"),
+                &lenience
+            )),
+            Ok(("@misc{ a, }", Entry::Comment("mail a@b.com @ home".into())))
+        );
+        assert_eq!(
+            str_err!(entry_with::<Error>(mkspan("@ note"), &lenience)),
+            Ok(("", Entry::Comment("@ note".into())))
+        );
+        assert!(entry_with::<Error>(mkspan("@misc{ a, title = = }"), &lenience).is_err());
+    }
+
+    #[test]
+    fn test_missing_commas() {
+        let tags_str = "title = {Title}
+            author = \"Author\",
+            year = 2000
+            note = {Note} month = jan
+            }";
+        let (_, tags) = bib_tags::<Error>(mkspan(tags_str), None).unwrap();
+        assert_eq!(tags.len(), 1);
+
+        let missing_commas = RefCell::default();
+        let (rest, tags) = bib_tags::<Error>(mkspan(tags_str), Some(&missing_commas)).unwrap();
+        assert!(rest.fragment().starts_with(" month = jan"));
+        assert_eq!(
+            tags.iter().map(|tag| tag.key.as_str()).collect::<Vec<_>>(),
+            ["title", "author", "year", "note"]
+        );
+        let after = |value: &str| tags_str.find(value).unwrap() + value.len();
+        assert_eq!(
+            missing_commas.take(),
+            [
+                SourceLocation {
+                    line: 1,
+                    column: 16,
+                    offset: after("{Title}")
+                },
+                SourceLocation {
+                    line: 3,
+                    column: 24,
+                    offset: after("2000")
+                }
+            ]
+        );
+    }
+
     #[test]
     fn test_entry_with_type() {
         assert_eq!(
-            str_err!(entry_with_type::<Error>(mkspan("@Comment{test}"))),
-            Ok(("", Entry::Comment("test".to_string())))
+            str_err!(entry_with_type::<Error>(
+                mkspan("@Comment{test}"),
+                &Lenience::default()
+            )),
+            Ok(("", Entry::CommentBlock("test".to_string())))
         );
 
         let kv = KeyValue::new(
@@ -510,14 +1089,18 @@ mod tests {
             vec![StringValueType::Str("value".to_string())],
         );
         assert_eq!(
-            str_err!(entry_with_type::<Error>(mkspan("@String{key=\"value\"}"))),
+            str_err!(entry_with_type::<Error>(
+                mkspan("@String{key=\"value\"}"),
+                &Lenience::default()
+            )),
             Ok(("", Entry::Variable(kv)))
         );
 
         assert_eq!(
-            str_err!(entry_with_type::<Error>(mkspan(
-                "@preamble{name # \"'s preamble\"}"
-            ))),
+            str_err!(entry_with_type::<Error>(
+                mkspan("@preamble{name # \"'s preamble\"}"),
+                &Lenience::default()
+            )),
             Ok((
                 "",
                 Entry::Preamble(vec![
@@ -547,7 +1130,10 @@ mod tests {
             ),
         ];
         assert_eq!(
-            str_err!(entry_with_type::<Error>(mkspan(bib_str))),
+            str_err!(entry_with_type::<Error>(
+                mkspan(bib_str),
+                &Lenience::default()
+            )),
             Ok((
                 "",
                 Entry::Bibliography("misc".to_string(), "patashnik-bibtexing".to_string(), tags)
@@ -562,7 +1148,10 @@ mod tests {
             vec![StringValueType::Str("value".to_string())],
         );
         assert_eq!(
-            str_err!(entry_with_type::<Error>(mkspan("@ String{key=\"value\"}"))),
+            str_err!(entry_with_type::<Error>(
+                mkspan("@ String{key=\"value\"}"),
+                &Lenience::default()
+            )),
             Ok(("", Entry::Variable(kv)))
         );
     }
@@ -573,8 +1162,40 @@ mod tests {
 
         assert_eq!(
             str_err!(parse),
-            Ok(("", Entry::Comment("test".to_string())))
+            Ok(("", Entry::CommentBlock("test".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_type_comment_delimiters() {
+        assert_eq!(
+            str_err!(type_comment::<Error>(mkspan(
+                "@comment{ @article{fake, title = {a {b}}} } rest"
+            ))),
+            Ok((
+                " rest",
+                Entry::CommentBlock("@article{fake, title = {a {b}}}".to_string())
+            ))
+        );
+        assert_eq!(
+            str_err!(type_comment::<Error>(mkspan("@comment( {a) b} (c ) rest"))),
+            Ok((" rest", Entry::CommentBlock("{a) b} (c".to_string())))
+        );
+        assert!(type_comment::<Error>(mkspan("@comment( {a) b}")).is_err());
+    }
+
+    #[test]
+    fn test_line_comment() {
+        assert_eq!(
+            str_err!(line_comment::<Error>(mkspan(
+                "@comment jabref-meta: {x}\nrest"
+            ))),
+            Ok((
+                "\nrest",
+                Entry::CommentBlock("jabref-meta: {x}".to_string())
+            ))
         );
+        assert!(line_comment::<Error>(mkspan("@commentary text")).is_err());
     }
 
     #[test]
@@ -588,6 +1209,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parenthesized_preamble() {
+        assert_eq!(
+            str_err!(preamble::<Error>(mkspan("@preamble ( \"my preamble\" )"))),
+            Ok((
+                "",
+                Entry::Preamble(vec![StringValueType::Str("my preamble".to_string())])
+            ))
+        );
+        assert!(preamble::<Error>(mkspan("@preamble{\"my preamble\")")).is_err());
+    }
+
     #[test]
     fn test_variable() {
         let kv1 = KeyValue::new(
@@ -622,6 +1255,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_variable_with_bracketed_value() {
+        let kv = KeyValue::new(
+            "key".to_string(),
+            vec![StringValueType::Str(
+                "a \"value\", {with} # signs".to_string(),
+            )],
+        );
+
+        assert_eq!(
+            str_err!(variable::<Error>(mkspan(
+                "@string{key = {a \"value\", {with} # signs}}"
+            ))),
+            Ok(("", Entry::Variable(kv)))
+        );
+    }
+
     #[test]
     fn test_variable_key_value_pair() {
         let kv = KeyValue::new(
@@ -640,6 +1290,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_variable_key_value_pair_concatenation() {
+        let kv = KeyValue::new(
+            "key".to_string(),
+            vec![
+                StringValueType::Str("a".to_string()),
+                StringValueType::Abbreviation("b".to_string()),
+                StringValueType::Str("c".to_string()),
+            ],
+        );
+
+        assert_eq!(
+            str_err!(variable_key_value_pair::<Error>(mkspan(
+                "key = \"a\" # b # {c}}"
+            ))),
+            Ok(("}", kv))
+        );
+    }
+
     #[test]
     fn test_bibliography_entry() {
         let bib_str = "@misc{ patashnik-bibtexing,
@@ -662,7 +1331,7 @@ mod tests {
             ),
         ];
         assert_eq!(
-            str_err!(bibliography_entry::<Error>(mkspan(bib_str))),
+            str_err!(bibliography_entry::<Error>(mkspan(bib_str), None)),
             Ok((
                 "",
                 Entry::Bibliography("misc".to_string(), "patashnik-bibtexing".to_string(), tags)
@@ -679,7 +1348,7 @@ mod tests {
             vec![StringValueType::Str("1988".to_string())],
         )];
         assert_eq!(
-            str_err!(bibliography_entry::<Error>(mkspan(bib_str))),
+            str_err!(bibliography_entry::<Error>(mkspan(bib_str), None)),
             Ok((
                 "",
                 Entry::Bibliography("misc".to_string(), "patashnik-bibtexing".to_string(), tags)
@@ -687,6 +1356,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parenthesized_bibliography_entry() {
+        let tags = vec![KeyValue::new(
+            "year".to_string(),
+            vec![StringValueType::Str("1988".to_string())],
+        )];
+        assert_eq!(
+            str_err!(bibliography_entry::<Error>(
+                mkspan("@misc( patashnik-bibtexing, year = {1988} )"),
+                None
+            )),
+            Ok((
+                "",
+                Entry::Bibliography("misc".to_string(), "patashnik-bibtexing".to_string(), tags)
+            ))
+        );
+        assert!(matches!(
+            bibliography_entry::<Error>(mkspan("@misc( key, year = {1988} }"), None),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_bibliography_entry_without_tags() {
+        for bib_str in [
+            "@misc{key}",
+            "@misc{ key }",
+            "@misc( key )",
+            "@misc{ key, }",
+        ] {
+            assert_eq!(
+                str_err!(bibliography_entry::<Error>(mkspan(bib_str), None)),
+                Ok((
+                    "",
+                    Entry::Bibliography("misc".to_string(), "key".to_string(), vec![])
+                ))
+            );
+        }
+        assert!(bibliography_entry::<Error>(mkspan("@misc{ key )"), None).is_err());
+        assert!(bibliography_entry::<Error>(mkspan("@misc{ key title = {T} }"), None).is_err());
+    }
+
     #[test]
     fn test_bib_tags() {
         let tags_str = "author= \"Oren Patashnik\",
@@ -716,7 +1427,7 @@ mod tests {
             ),
         ];
         assert_eq!(
-            str_err!(bib_tags::<Error>(mkspan(tags_str))),
+            str_err!(bib_tags::<Error>(mkspan(tags_str), None)),
             Ok(("}", result))
         );
     }
@@ -753,6 +1464,41 @@ mod tests {
                 ]
             ))
         );
+        assert_eq!(
+            str_err!(abbreviation_string::<Error>(mkspan("{string} # var,"))),
+            Ok((
+                ",",
+                vec![
+                    StringValueType::Str("string".to_string()),
+                    StringValueType::Abbreviation("var".to_string()),
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_abbreviation_string_with_numbers() {
+        assert_eq!(
+            str_err!(abbreviation_string::<Error>(mkspan("vol # 12 # 2nd,"))),
+            Ok((
+                ",",
+                vec![
+                    StringValueType::Abbreviation("vol".to_string()),
+                    StringValueType::Str("12".to_string()),
+                    StringValueType::Abbreviation("2nd".to_string()),
+                ]
+            ))
+        );
+        assert_eq!(
+            str_err!(abbreviation_string::<Error>(mkspan("12 # \"-draft\","))),
+            Ok((
+                ",",
+                vec![
+                    StringValueType::Str("12".to_string()),
+                    StringValueType::Str("-draft".to_string()),
+                ]
+            ))
+        );
     }
 
     #[test]
@@ -819,6 +1565,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_nested_bracketed_string() {
+        let value = "We study {N}-body {{really} {{{nested}}}} systems";
+        assert_eq!(
+            str_err!(bracketed_string::<Error>(mkspan(&format!("{{{}}}", value)))),
+            Ok(("", value))
+        );
+
+        let depth = 100_000;
+        let value = format!("{}{}", "{".repeat(depth), "}".repeat(depth));
+        assert_eq!(
+            str_err!(bracketed_string::<Error>(mkspan(&value))),
+            Ok(("", &value[1..value.len() - 1]))
+        );
+        assert!(matches!(
+            bracketed_string::<Error>(mkspan(&value[..value.len() - 1])),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_unclosed_bracketed_string() {
+        assert!(matches!(
+            bracketed_string::<Error>(mkspan("{ {test} é")),
+            Err(nom::Err::Failure(_))
+        ));
+        assert!(matches!(
+            bibliography_entry::<Error>(mkspan("@misc{ key, title = {T}\n@misc"), None),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_entry_name() {
+        assert_eq!(entry_name("@misc{ key , title = {T"), "key");
+        assert_eq!(entry_name("@Comment{ text"), "@comment");
+        assert_eq!(entry_name("@misc{ text"), "@misc");
+    }
+
     #[test]
     fn test_quoted_string() {
         assert_eq!(
@@ -847,6 +1632,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_quoted_string_with_escaped_quotes() {
+        assert_eq!(
+            str_err!(quoted_string::<Error>(mkspan(r#""A \"quoted\" word", "#))),
+            Ok((", ", r#"A \"quoted\" word"#))
+        );
+        assert_eq!(
+            str_err!(quoted_string::<Error>(mkspan(
+                r#""M\"uller {and \"} {"}" rest"#
+            ))),
+            Ok((" rest", r#"M\"uller {and \"} {"}"#))
+        );
+        assert_eq!(
+            str_err!(quoted_string::<Error>(mkspan(r#""path\\" rest"#))),
+            Ok((" rest", r#"path\\"#))
+        );
+        assert!(quoted_string::<Error>(mkspan(r#""unclosed \""#)).is_err());
+    }
+
     #[test]
     fn test_variable_with_underscore() {
         let kv1 = KeyValue::new(
@@ -0,0 +1,165 @@
+//! Export of bibliographies to the *RIS* format of reference managers.
+//!
+//! See `Bibliography::to_ris` to convert a single bibliography. Each
+//! bibliography is written as a record starting with its `TY` type and
+//! ending with `ER`. Values are converted to plain text: *LaTeX* accents are
+//! decoded and brackets are removed. Tags without *RIS* equivalent are kept
+//! in `N1` notes as `key: value`.
+//!
+//! ```
+//! use nom_bibtex::Bibtex;
+//!
+//! let bibtex = Bibtex::parse(
+//!     "@article{ key,
+//!         author = {Einstein, Albert},
+//!         title = {On the electrodynamics of moving bodies},
+//!         pages = {891--921}
+//!     }",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(
+//!     nom_bibtex::ris::to_ris(&bibtex),
+//!     "TY  - JOUR
+//! ID  - key
+//! AU  - Einstein, Albert
+//! TI  - On the electrodynamics of moving bodies
+//! SP  - 891
+//! EP  - 921
+//! ER  - \n"
+//! );
+//! ```
+
+use crate::latex::plain_text;
+use crate::model::{Bibliography, Bibtex, EntryType, Pages};
+use crate::name::{self, Name};
+
+// Tags written with the same value, by lowercase key.
+const TABLE_TAGS: [(&str, &str); 20] = [
+    ("title", "TI"),
+    ("journal", "T2"),
+    ("booktitle", "T2"),
+    ("series", "T3"),
+    ("volume", "VL"),
+    ("number", "IS"),
+    ("chapter", "SE"),
+    ("edition", "ET"),
+    ("publisher", "PB"),
+    ("school", "PB"),
+    ("institution", "PB"),
+    ("organization", "PB"),
+    ("address", "CY"),
+    ("doi", "DO"),
+    ("url", "UR"),
+    ("isbn", "SN"),
+    ("issn", "SN"),
+    ("abstract", "AB"),
+    ("language", "LA"),
+    ("note", "N1"),
+];
+
+/// Convert all the bibliographies to *RIS* records, separated by an empty
+/// line.
+pub fn to_ris(bibtex: &Bibtex) -> String {
+    bibtex
+        .bibliographies()
+        .iter()
+        .map(to_ris_record)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Convert a bibliography to a *RIS* record, see `Bibliography::to_ris`.
+pub(crate) fn to_ris_record(biblio: &Bibliography) -> String {
+    let mut out = String::new();
+    let mut push = |tag: &str, value: &str| {
+        if !value.is_empty() {
+            out.push_str(&format!("{}  - {}\n", tag, value));
+        }
+    };
+
+    push("TY", ris_type(&biblio.entry_type_enum()));
+    push("ID", biblio.citation_key());
+
+    let mut has_date = false;
+    for (key, value) in biblio.iter_tags() {
+        match key {
+            "author" | "editor" => {
+                let tag = if key == "author" { "AU" } else { "ED" };
+                match name::parse_names(value) {
+                    Ok(names) => names.iter().for_each(|n| push(tag, &ris_name(n))),
+                    Err(_) => push(tag, &plain_text(value)),
+                }
+            }
+            "year" | "date" | "month" => {
+                if has_date {
+                    continue;
+                }
+                has_date = true;
+                let date = match biblio.date() {
+                    Some(date) => date,
+                    None => continue,
+                };
+                match date.year() {
+                    Some(year) => push("PY", &year.to_string()),
+                    None => push("PY", &date.to_string()),
+                }
+                if let (Some(year), Some(month)) = (date.year(), date.month()) {
+                    let day = date.day().map(|d| format!("{:02}", d)).unwrap_or_default();
+                    push("DA", &format!("{:04}/{:02}/{}", year, month, day));
+                }
+            }
+            "pages" => match Pages::parse(value) {
+                Some(Pages::Range { start, end }) => {
+                    push("SP", &start);
+                    push("EP", &end);
+                }
+                Some(Pages::Single(page)) | Some(Pages::Other(page)) => push("SP", &page),
+                None => {}
+            },
+            "keywords" => biblio
+                .keywords()
+                .iter()
+                .for_each(|keyword| push("KW", &plain_text(keyword))),
+            "crossref" => {}
+            key => match TABLE_TAGS.iter().find(|(k, _)| *k == key) {
+                Some((_, tag)) => push(tag, &plain_text(value)),
+                None => push("N1", &format!("{}: {}", key, plain_text(value))),
+            },
+        }
+    }
+
+    out.push_str("ER  - \n");
+    out
+}
+
+fn ris_type(entry_type: &EntryType) -> &'static str {
+    match entry_type {
+        EntryType::Article => "JOUR",
+        EntryType::Book => "BOOK",
+        EntryType::Booklet => "PAMP",
+        EntryType::InBook | EntryType::InCollection => "CHAP",
+        EntryType::Conference | EntryType::InProceedings | EntryType::Proceedings => "CONF",
+        EntryType::MastersThesis | EntryType::PhdThesis => "THES",
+        EntryType::TechReport => "RPRT",
+        EntryType::Unpublished => "UNPB",
+        EntryType::Manual | EntryType::Misc | EntryType::Other(_) => "GEN",
+    }
+}
+
+// Format a name as `von Last, First, Jr`.
+fn ris_name(name: &Name) -> String {
+    let mut out = [&name.von, &name.last]
+        .iter()
+        .filter(|part| !part.is_empty())
+        .map(|part| plain_text(part))
+        .collect::<Vec<_>>()
+        .join(" ");
+    for part in [&name.first, &name.jr] {
+        if !part.is_empty() {
+            out.push_str(", ");
+            out.push_str(&plain_text(part));
+        }
+    }
+    out
+}
@@ -0,0 +1,526 @@
+//! Parsing of large *BibTeX* files one entry at a time.
+//!
+//! `Entries` yields the raw entries of a file without keeping them, and
+//! `ExpandingIter` expands their string variables as they are read. Since
+//! string variables are only known once read, an entry can only use the
+//! variables defined before it.
+//!
+//! ```
+//! use nom_bibtex::stream::{ExpandedEntry, ExpandingIter};
+//! use nom_bibtex::Bibtex;
+//!
+//! let bib_str = "@string{ knuth = {Donald Knuth} }
+//!     @book{ taocp, author = knuth }
+//!     @misc{ other, author = {Someone} }";
+//!
+//! let books = ExpandingIter::new(Bibtex::iter_entries(bib_str))
+//!     .filter_map(|entry| match entry {
+//!         Ok(ExpandedEntry::Bibliography(b)) if b.entry_type() == "book" => Some(b),
+//!         _ => None,
+//!     })
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(books.len(), 1);
+//! assert_eq!(books[0].get_tag("author"), Some("Donald Knuth"));
+//! ```
+
+use crate::error::{BibtexError, VariableUse};
+use crate::model::{Bibliography, Bibtex, SourceLocation};
+use crate::options::DEFAULT_MAX_NESTING_DEPTH;
+use crate::parser::{self, mkspan, Entry, Lenience, Span};
+use crate::warning::Warning;
+use nom::error::VerboseError;
+use nom::Slice;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::result;
+
+type Result<T> = result::Result<T, BibtexError>;
+
+// An entry with its location and the offset just after its end.
+pub(crate) type LocatedEntry = (SourceLocation, usize, Entry);
+
+/// An iterator over the raw entries of a file, see `Bibtex::iter_entries`.
+pub struct Entries<'a> {
+    source: &'a str,
+    input: Span<'a>,
+    failed: bool,
+    lenience: Lenience,
+    max_nesting_depth: usize,
+    // Warnings about the entries read so far.
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Entries<'a> {
+    pub fn new(source: &'a str) -> Entries<'a> {
+        Entries {
+            source,
+            input: mkspan(source),
+            failed: false,
+            lenience: Lenience::default(),
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            warnings: vec![],
+        }
+    }
+
+    /// Consider the `@` which do not start an entry as part of the comments,
+    /// see `ParseOptions::lenient_comments`.
+    pub fn lenient_comments(mut self, lenient: bool) -> Self {
+        self.lenience.comments = lenient;
+        self
+    }
+
+    // Tolerate the commas missing between tags, with a warning.
+    pub(crate) fn lenient_commas(mut self, lenient: bool) -> Self {
+        self.lenience.missing_commas = lenient.then(RefCell::default);
+        self
+    }
+
+    /// Fail on the entries with brackets nested deeper than `depth`, see
+    /// `ParseOptions::max_nesting_depth`.
+    pub fn max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = depth;
+        self
+    }
+
+    // Take the warnings about the entries read so far.
+    pub(crate) fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub(crate) fn next_located(&mut self) -> Option<Result<LocatedEntry>> {
+        if self.failed || parser::is_end(self.input) {
+            return None;
+        }
+        let start = self.entry_start();
+        let parsed = parser::entry_with::<VerboseError<Span>>(self.input, &self.lenience);
+        if let Some(missing_commas) = &self.lenience.missing_commas {
+            let missing_commas = missing_commas.take();
+            if let Ok((_, Entry::Bibliography(_, citation_key, _))) = &parsed {
+                self.warnings
+                    .extend(
+                        missing_commas
+                            .into_iter()
+                            .map(|location| Warning::MissingComma {
+                                citation_key: citation_key.clone(),
+                                location,
+                            }),
+                    );
+            }
+        }
+        match parsed {
+            Ok((rest, entry)) => {
+                if !matches!(entry, Entry::Comment(_)) {
+                    if let Err(err) = check_nesting(start, rest, self.max_nesting_depth) {
+                        // The entry is skipped as an invalid one.
+                        self.failed = true;
+                        return Some(Err(err));
+                    }
+                }
+                self.input = rest;
+                let location = SourceLocation {
+                    line: start.location_line(),
+                    column: start.get_utf8_column(),
+                    offset: start.location_offset(),
+                };
+                Some(Ok((location, rest.location_offset(), entry)))
+            }
+            Err(e) => {
+                // The rest of the input cannot be split in entries.
+                self.failed = true;
+                Some(Err(BibtexError::with_context(self.source, e)))
+            }
+        }
+    }
+
+    // Skip the entry which failed to parse, up to the next line starting
+    // with a `@`, and return the input at the start of the skipped entry.
+    pub(crate) fn skip_failed_entry(&mut self) -> Span<'a> {
+        let entry = self.entry_start();
+
+        let mut next = entry.fragment().len();
+        let mut line_start = false;
+        for (i, c) in entry.fragment().char_indices() {
+            match c {
+                '\n' => line_start = true,
+                '@' if line_start => {
+                    next = i;
+                    break;
+                }
+                c if !c.is_whitespace() => line_start = false,
+                _ => {}
+            }
+        }
+        self.input = entry.slice(next..);
+        self.failed = false;
+        entry
+    }
+
+    // The input without the whitespaces before the next entry.
+    fn entry_start(&self) -> Span<'a> {
+        let fragment = self.input.fragment();
+        self.input
+            .slice(fragment.len() - fragment.trim_start().len()..)
+    }
+}
+
+impl Iterator for Entries<'_> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_located()?.map(|(_, _, entry)| entry))
+    }
+}
+
+// Fail when the brackets of the entry between `start` and `end` are nested
+// deeper than `max_depth`, without counting the bracket delimiting the entry.
+fn check_nesting(start: Span, end: Span, max_depth: usize) -> Result<()> {
+    let entry = &start.fragment()[..end.location_offset() - start.location_offset()];
+    let delimiter = entry.find(['{', '(']);
+    let mut depth = 0;
+    for (i, c) in entry.char_indices() {
+        match c {
+            '{' if Some(i) != delimiter => {
+                depth += 1;
+                if depth > max_depth {
+                    let bracket = start.slice(i..);
+                    return Err(BibtexError::NestingTooDeep {
+                        entry: parser::entry_name(entry),
+                        line: bracket.location_line(),
+                        column: bracket.get_utf8_column(),
+                        max_depth,
+                    });
+                }
+            }
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// An iterator over the raw entries read from a reader, see
+/// `Bibtex::parse_reader`.
+///
+/// Only the entry being parsed is kept in memory. The input is read a line
+/// at a time up to the next line starting with a `@`, and an entry is only
+/// parsed once its opening brace is closed. The iteration stops after the
+/// first error.
+pub struct ReaderEntries<R> {
+    reader: BufReader<R>,
+    // The unparsed input, starting at the beginning of a line.
+    buffer: String,
+    // Position of the next entry in the buffer.
+    pos: usize,
+    // Number of lines before the buffer.
+    lines: u32,
+    // Position in the buffer up to which the braces of the entry at `pos`
+    // were counted, with their depth.
+    scanned: usize,
+    depth: usize,
+    // Whether the entry at `pos` may be complete, so that it is worth
+    // parsing.
+    ready: bool,
+    eof: bool,
+    failed: bool,
+    lenience: Lenience,
+    max_nesting_depth: usize,
+}
+
+impl<R: Read> ReaderEntries<R> {
+    pub fn new(reader: R) -> ReaderEntries<R> {
+        ReaderEntries {
+            reader: BufReader::new(reader),
+            buffer: String::new(),
+            pos: 0,
+            lines: 0,
+            scanned: 0,
+            depth: 0,
+            ready: false,
+            eof: false,
+            failed: false,
+            lenience: Lenience::default(),
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+        }
+    }
+
+    /// Consider the `@` which do not start an entry as part of the comments,
+    /// see `ParseOptions::lenient_comments`.
+    pub fn lenient_comments(mut self, lenient: bool) -> Self {
+        self.lenience.comments = lenient;
+        self
+    }
+
+    /// Fail on the entries with brackets nested deeper than `depth`, see
+    /// `ParseOptions::max_nesting_depth`.
+    pub fn max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = depth;
+        self
+    }
+
+    // Read the lines up to the next one starting with a `@`.
+    fn read_more(&mut self) -> Result<()> {
+        loop {
+            let start = self.buffer.len();
+            if self.reader.read_line(&mut self.buffer)? == 0 {
+                self.eof = true;
+                return Ok(());
+            }
+            if self.buffer[start..].trim_start().starts_with('@') {
+                return Ok(());
+            }
+        }
+    }
+
+    // Drop the complete lines before the end of the parsed entry.
+    fn consume(&mut self, end: usize) {
+        match self.buffer[..end].rfind('\n') {
+            Some(i) => {
+                self.lines += self.buffer[..=i].matches('\n').count() as u32;
+                self.buffer.drain(..=i);
+                self.pos = end - i - 1;
+            }
+            None => self.pos = end,
+        }
+        self.scanned = self.pos;
+        self.depth = 0;
+        self.ready = false;
+    }
+
+    // Check if the entry at `pos` may be complete, counting the braces read
+    // since the last call. An entry starting with `@{` is complete once its
+    // first brace is closed, whereas the comments and the entries delimited
+    // by parentheses are parsed after each read.
+    fn may_be_complete(&mut self) -> bool {
+        if self.ready {
+            return true;
+        }
+        let entry = self.buffer[self.pos..].trim_start();
+        if !entry.starts_with('@') {
+            self.ready = true;
+            return true;
+        }
+        for c in self.buffer[self.scanned..].chars() {
+            match c {
+                '(' if self.depth == 0 => self.ready = true,
+                '{' => self.depth += 1,
+                '}' if self.depth == 1 => self.ready = true,
+                '}' => self.depth = self.depth.saturating_sub(1),
+                _ => continue,
+            }
+            if self.ready {
+                break;
+            }
+        }
+        self.scanned = self.buffer.len();
+        self.ready
+    }
+
+    // Locate an error from the start of the reader.
+    fn shift_error(&self, err: BibtexError) -> BibtexError {
+        match err {
+            BibtexError::ParsingError {
+                line,
+                column,
+                message,
+                source_line,
+            } => BibtexError::ParsingError {
+                line: line + self.lines,
+                column,
+                message,
+                source_line,
+            },
+            BibtexError::UnclosedBracket {
+                entry,
+                line,
+                column,
+            } => BibtexError::UnclosedBracket {
+                entry,
+                line: line + self.lines,
+                column,
+            },
+            BibtexError::InvalidFieldName {
+                entry,
+                name,
+                line,
+                column,
+            } => BibtexError::InvalidFieldName {
+                entry,
+                name,
+                line: line + self.lines,
+                column,
+            },
+            BibtexError::UnbalancedBraces {
+                entry,
+                field,
+                line,
+                column,
+            } => BibtexError::UnbalancedBraces {
+                entry,
+                field,
+                line: line + self.lines,
+                column,
+            },
+            BibtexError::EmptyCitationKey { entry_type, line } => BibtexError::EmptyCitationKey {
+                entry_type,
+                line: line.map(|line| line + self.lines),
+            },
+            BibtexError::NestingTooDeep {
+                entry,
+                line,
+                column,
+                max_depth,
+            } => BibtexError::NestingTooDeep {
+                entry,
+                line: line + self.lines,
+                column,
+                max_depth,
+            },
+            err => err,
+        }
+    }
+}
+
+impl<R: Read> Iterator for ReaderEntries<R> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            let at_end = self.buffer[self.pos..].trim().is_empty();
+            if at_end && self.eof {
+                return None;
+            }
+            if !at_end && (self.eof || self.may_be_complete()) {
+                let input = mkspan(&self.buffer).slice(self.pos..);
+                // An entry followed by whitespaces only may still go on in
+                // the next lines.
+                match parser::entry_with::<VerboseError<Span>>(input, &self.lenience) {
+                    Ok((rest, entry)) if self.eof || !parser::is_end(rest) => {
+                        let start =
+                            input.slice(input.len() - input.fragment().trim_start().len()..);
+                        let nesting = match entry {
+                            Entry::Comment(_) => Ok(()),
+                            _ => check_nesting(start, rest, self.max_nesting_depth),
+                        };
+                        if let Err(err) = nesting {
+                            self.failed = true;
+                            return Some(Err(self.shift_error(err)));
+                        }
+                        let end = rest.location_offset();
+                        self.consume(end);
+                        return Some(Ok(entry));
+                    }
+                    Err(e) if self.eof => {
+                        let err = BibtexError::with_context(&self.buffer, e);
+                        self.failed = true;
+                        return Some(Err(self.shift_error(err)));
+                    }
+                    _ => {}
+                }
+            }
+            if let Err(err) = self.read_more() {
+                self.failed = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// An entry with its string variables expanded.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExpandedEntry {
+    Preamble(String),
+    /// Text outside of the entries.
+    Comment(String),
+    /// The content of a `@comment` entry.
+    CommentBlock(String),
+    /// A string variable with its key, lowercase, and expanded value.
+    Variable(String, String),
+    Bibliography(Bibliography),
+}
+
+/// An iterator expanding the string variables of raw entries.
+///
+/// An entry using an unknown variable yields an error without stopping the
+/// iteration.
+pub struct ExpandingIter<I> {
+    entries: I,
+    variables: HashMap<String, String>,
+    constants: HashMap<String, String>,
+    preambles: usize,
+}
+
+impl<I> ExpandingIter<I>
+where
+    I: Iterator<Item = Result<Entry>>,
+{
+    pub fn new(entries: I) -> ExpandingIter<I> {
+        ExpandingIter {
+            entries,
+            variables: HashMap::new(),
+            constants: Bibtex::constants(),
+            preambles: 0,
+        }
+    }
+
+    /// Get the string variables read so far with their expanded value.
+    pub fn variables(&self) -> &HashMap<String, String> {
+        &self.variables
+    }
+
+    fn expand(&mut self, entry: Entry) -> Result<ExpandedEntry> {
+        let expand = |value, used_in: &dyn Fn() -> VariableUse| {
+            Bibtex::expand_str_abbreviations(value, &self.variables, &self.constants, used_in, None)
+        };
+
+        let entry = match entry {
+            Entry::Preamble(v) => {
+                let number = self.preambles + 1;
+                let value = expand(v, &|| VariableUse::Preamble(number))?;
+                self.preambles = number;
+                ExpandedEntry::Preamble(value)
+            }
+            Entry::Comment(v) => ExpandedEntry::Comment(v),
+            Entry::CommentBlock(v) => ExpandedEntry::CommentBlock(v),
+            Entry::Variable(v) => {
+                let key = v.key.to_lowercase();
+                let value = expand(v.value, &|| VariableUse::Variable(key.clone()))?;
+                self.variables.insert(key.clone(), value.clone());
+                ExpandedEntry::Variable(key, value)
+            }
+            Entry::Bibliography(entry_t, citation_key, tags) => {
+                let tags = tags
+                    .into_iter()
+                    .map(|tag| {
+                        let used_in = || VariableUse::Tag {
+                            citation_key: citation_key.clone(),
+                            field: tag.key.to_lowercase(),
+                        };
+                        let value = expand(tag.value, &used_in)?;
+                        Ok((tag.key, value))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                ExpandedEntry::Bibliography(Bibliography::new(entry_t, citation_key, tags))
+            }
+        };
+        Ok(entry)
+    }
+}
+
+impl<I> Iterator for ExpandingIter<I>
+where
+    I: Iterator<Item = Result<Entry>>,
+{
+    type Item = Result<ExpandedEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.entries.next()? {
+            Ok(entry) => Some(self.expand(entry)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Validation of the fields expected by the standard entry types.
+//!
+//! Each standard *BibTeX* entry type has required fields, without which
+//! *BibTeX* emits a warning, and some recommended fields that most styles
+//! print. A requirement can be satisfied by one of several alternatives, such
+//! as `author` or `editor` for a `@book`. Entry types which are not standard
+//! are never reported.
+
+use crate::model::{Bibliography, EntryType};
+use std::fmt;
+
+/// How important a missing field is.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Level {
+    Required,
+    Recommended,
+}
+
+/// A missing field of a bibliography.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ValidationIssue {
+    pub citation_key: String,
+    /// The missing field, or its alternatives when none of them is defined.
+    pub fields: Vec<String>,
+    pub level: Level,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.level {
+            Level::Required => "required",
+            Level::Recommended => "recommended",
+        };
+        write!(f, "{}: missing {} field ", self.citation_key, level)?;
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" or ")?;
+            }
+            write!(f, "`{}`", field)?;
+        }
+        Ok(())
+    }
+}
+
+// Each requirement is a list of alternative fields.
+type Requirements = &'static [&'static [&'static str]];
+
+// A year can also be given as a *BibLaTeX* date.
+const YEAR: &[&str] = &["year", "date"];
+const AUTHOR_OR_EDITOR: &[&str] = &["author", "editor"];
+
+// Required and recommended fields of an entry type.
+fn requirements(entry_type: &EntryType) -> Option<(Requirements, Requirements)> {
+    let requirements: (Requirements, Requirements) = match entry_type {
+        EntryType::Article => (
+            &[&["author"], &["title"], &["journal"], YEAR],
+            &[&["volume"], &["pages"]],
+        ),
+        EntryType::Book => (
+            &[AUTHOR_OR_EDITOR, &["title"], &["publisher"], YEAR],
+            &[&["address"]],
+        ),
+        EntryType::Booklet => (&[&["title"]], &[&["author"], &["howpublished"], YEAR]),
+        EntryType::InBook => (
+            &[
+                AUTHOR_OR_EDITOR,
+                &["title"],
+                &["chapter", "pages"],
+                &["publisher"],
+                YEAR,
+            ],
+            &[&["address"]],
+        ),
+        EntryType::InCollection => (
+            &[
+                &["author"],
+                &["title"],
+                &["booktitle"],
+                &["publisher"],
+                YEAR,
+            ],
+            &[&["editor"], &["pages"]],
+        ),
+        EntryType::Conference | EntryType::InProceedings => (
+            &[&["author"], &["title"], &["booktitle"], YEAR],
+            &[&["pages"]],
+        ),
+        EntryType::Manual => (&[&["title"]], &[&["author"], &["organization"], YEAR]),
+        EntryType::MastersThesis | EntryType::PhdThesis => (
+            &[&["author"], &["title"], &["school"], YEAR],
+            &[&["address"]],
+        ),
+        EntryType::Misc => (&[], &[&["title"]]),
+        EntryType::Proceedings => (&[&["title"], YEAR], &[&["editor"], &["publisher"]]),
+        EntryType::TechReport => (
+            &[&["author"], &["title"], &["institution"], YEAR],
+            &[&["number"]],
+        ),
+        EntryType::Unpublished => (&[&["author"], &["title"], &["note"]], &[YEAR]),
+        EntryType::Other(_) => return None,
+    };
+    Some(requirements)
+}
+
+// Fields with an empty value are considered missing.
+fn is_defined(biblio: &Bibliography, field: &str) -> bool {
+    biblio
+        .get_tag(field)
+        .is_some_and(|value| !value.trim().is_empty())
+}
+
+/// Get the missing fields of a bibliography.
+///
+/// Fields with an empty value are considered missing.
+pub fn validate(biblio: &Bibliography) -> Vec<ValidationIssue> {
+    let (required, recommended) = match requirements(&biblio.entry_type_enum()) {
+        Some(requirements) => requirements,
+        None => return vec![],
+    };
+
+    let requirements = required
+        .iter()
+        .map(|fields| (fields, Level::Required))
+        .chain(
+            recommended
+                .iter()
+                .map(|fields| (fields, Level::Recommended)),
+        );
+
+    requirements
+        .filter(|(fields, _)| !fields.iter().any(|field| is_defined(biblio, field)))
+        .map(|(fields, level)| ValidationIssue {
+            citation_key: biblio.citation_key().into(),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            level,
+        })
+        .collect()
+}
+
+/// Get the required fields missing from a bibliography.
+///
+/// A requirement with alternatives is reported by its first field, such as
+/// `author` when neither `author` nor `editor` is defined.
+pub fn missing_required_fields(biblio: &Bibliography) -> Vec<&'static str> {
+    let required = match requirements(&biblio.entry_type_enum()) {
+        Some((required, _)) => required,
+        None => return vec![],
+    };
+
+    required
+        .iter()
+        .filter(|fields| !fields.iter().any(|field| is_defined(biblio, field)))
+        .map(|fields| fields[0])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn biblio(entry_type: &str, tags: &[(&str, &str)]) -> Bibliography {
+        Bibliography::new(
+            entry_type.into(),
+            "key".into(),
+            tags.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+        )
+    }
+
+    fn issue(fields: &[&str], level: Level) -> ValidationIssue {
+        ValidationIssue {
+            citation_key: "key".into(),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            level,
+        }
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let article = biblio(
+            "article",
+            &[("author", "A"), ("title", "T"), ("journal", " ")],
+        );
+        assert_eq!(
+            validate(&article),
+            vec![
+                issue(&["journal"], Level::Required),
+                issue(&["year", "date"], Level::Required),
+                issue(&["volume"], Level::Recommended),
+                issue(&["pages"], Level::Recommended),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alternative_fields() {
+        let tags = [("title", "T"), ("publisher", "P"), ("date", "2020")];
+        assert_eq!(
+            validate(&biblio("Book", &tags)),
+            vec![
+                issue(&["author", "editor"], Level::Required),
+                issue(&["address"], Level::Recommended),
+            ]
+        );
+
+        let tags = [
+            ("editor", "E"),
+            ("title", "T"),
+            ("publisher", "P"),
+            ("year", "2020"),
+            ("address", "A"),
+        ];
+        assert_eq!(validate(&biblio("book", &tags)), vec![]);
+    }
+
+    #[test]
+    fn test_missing_required_fields() {
+        let article = biblio("article", &[("title", "T"), ("journal", " ")]);
+        assert_eq!(
+            missing_required_fields(&article),
+            vec!["author", "journal", "year"]
+        );
+
+        let tags = [("editor", "E"), ("title", "T"), ("date", "2020")];
+        assert_eq!(
+            missing_required_fields(&biblio("book", &tags)),
+            vec!["publisher"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_entry_types() {
+        assert_eq!(validate(&biblio("dataset", &[])), vec![]);
+        assert!(missing_required_fields(&biblio("dataset", &[])).is_empty());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            issue(&["author", "editor"], Level::Required).to_string(),
+            "key: missing required field `author` or `editor`"
+        );
+        assert_eq!(
+            issue(&["pages"], Level::Recommended).to_string(),
+            "key: missing recommended field `pages`"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Non-fatal problems found while parsing, see `Bibtex::parse_verbose`.
+
+use crate::model::SourceLocation;
+use std::fmt;
+
+/// A problem which does not prevent parsing.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Warning {
+    /// A citation key used by several bibliographies, with the locations of
+    /// the first one and of the duplicate.
+    DuplicateCitationKey {
+        citation_key: String,
+        first: SourceLocation,
+        duplicate: SourceLocation,
+    },
+    /// A tag defined several times in a bibliography, with its lowercase
+    /// key and the location of the bibliography rather than of the tag. As in
+    /// *BibTeX*, the last value is kept, at the position of the first
+    /// definition.
+    DuplicateField {
+        citation_key: String,
+        key: String,
+        location: SourceLocation,
+    },
+    /// A tag whose value is empty or only made of whitespaces.
+    EmptyTag {
+        citation_key: String,
+        key: String,
+        location: SourceLocation,
+    },
+    /// A `month` tag which is not understood by `Month::parse`.
+    UnknownMonth {
+        citation_key: String,
+        value: String,
+        location: SourceLocation,
+    },
+    /// A comma missing between two tags, see
+    /// `ParseOptions::lenient_commas`. The location is where the comma is
+    /// missing, after the value of the first tag.
+    MissingComma {
+        citation_key: String,
+        location: SourceLocation,
+    },
+}
+
+impl Warning {
+    /// The citation key of the bibliography the warning is about.
+    pub fn citation_key(&self) -> &str {
+        match self {
+            Warning::DuplicateCitationKey { citation_key, .. }
+            | Warning::DuplicateField { citation_key, .. }
+            | Warning::EmptyTag { citation_key, .. }
+            | Warning::UnknownMonth { citation_key, .. }
+            | Warning::MissingComma { citation_key, .. } => citation_key,
+        }
+    }
+
+    /// The location of the bibliography the warning is about, the duplicate
+    /// for `Warning::DuplicateCitationKey` and the missing comma for
+    /// `Warning::MissingComma`.
+    pub fn location(&self) -> SourceLocation {
+        match self {
+            Warning::DuplicateCitationKey { duplicate, .. } => *duplicate,
+            Warning::DuplicateField { location, .. }
+            | Warning::EmptyTag { location, .. }
+            | Warning::UnknownMonth { location, .. }
+            | Warning::MissingComma { location, .. } => *location,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::DuplicateCitationKey {
+                citation_key,
+                first,
+                duplicate,
+            } => write!(
+                f,
+                "Duplicate citation key {} at line {}, first defined at line {}",
+                citation_key, duplicate.line, first.line
+            ),
+            Warning::DuplicateField {
+                citation_key,
+                key,
+                location,
+            } => write!(
+                f,
+                "Duplicate tag {} in {} at line {}, the last value is kept",
+                key, citation_key, location.line
+            ),
+            Warning::EmptyTag {
+                citation_key,
+                key,
+                location,
+            } => write!(
+                f,
+                "Empty tag {} in {} at line {}",
+                key, citation_key, location.line
+            ),
+            Warning::UnknownMonth {
+                citation_key,
+                value,
+                location,
+            } => write!(
+                f,
+                "Unknown month {} in {} at line {}",
+                value, citation_key, location.line
+            ),
+            Warning::MissingComma {
+                citation_key,
+                location,
+            } => write!(
+                f,
+                "Missing comma in {} at line {}, column {}",
+                citation_key, location.line, location.column
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_impls() {
+        let location = |line| SourceLocation {
+            line,
+            column: 1,
+            offset: 0,
+        };
+        let warning = Warning::DuplicateCitationKey {
+            citation_key: "<key>".into(),
+            first: location(1),
+            duplicate: location(7),
+        };
+        assert_eq!(
+            format!("{}", warning),
+            "Duplicate citation key <key> at line 7, first defined at line 1"
+        );
+
+        let warning = Warning::DuplicateField {
+            citation_key: "<key>".into(),
+            key: "<tag>".into(),
+            location: location(3),
+        };
+        assert_eq!(
+            format!("{}", warning),
+            "Duplicate tag <tag> in <key> at line 3, the last value is kept"
+        );
+
+        let warning = Warning::EmptyTag {
+            citation_key: "<key>".into(),
+            key: "<tag>".into(),
+            location: location(3),
+        };
+        assert_eq!(format!("{}", warning), "Empty tag <tag> in <key> at line 3");
+
+        let warning = Warning::UnknownMonth {
+            citation_key: "<key>".into(),
+            value: "<month>".into(),
+            location: location(3),
+        };
+        assert_eq!(
+            format!("{}", warning),
+            "Unknown month <month> in <key> at line 3"
+        );
+
+        let warning = Warning::MissingComma {
+            citation_key: "<key>".into(),
+            location: location(3),
+        };
+        assert_eq!(
+            format!("{}", warning),
+            "Missing comma in <key> at line 3, column 1"
+        );
+    }
+}
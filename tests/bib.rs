@@ -1,6 +1,20 @@
 extern crate nom_bibtex;
 
-use nom_bibtex::Bibtex;
+use nom_bibtex::encoding::Encoding;
+use nom_bibtex::error::{BibtexError, VariableUse};
+use nom_bibtex::key::KeyScheme;
+use nom_bibtex::model::Doi;
+use nom_bibtex::options::{
+    Delimiter, DuplicatePolicy, ParseOptions, WriteOptions, DEFAULT_MAX_NESTING_DEPTH,
+};
+use nom_bibtex::ris;
+use nom_bibtex::stream::{ExpandedEntry, ExpandingIter};
+use nom_bibtex::validation::Level;
+use nom_bibtex::warning::Warning;
+use nom_bibtex::{
+    BibDate, Bibliography, Bibtex, BibtexBuilder, DatePart, DedupPolicy, Entry, EntryRef,
+    EntryType, KeyValue, MergePolicy, Month, Pages, SourceLocation, StringValueType, TitlePart,
+};
 use std::fs::File;
 use std::io::prelude::*;
 
@@ -18,25 +32,3274 @@ fn test_bib() {
     let bibtex = Bibtex::parse(&bib_str).unwrap();
 
     let vars = bibtex.variables();
-    assert_eq!(vars[0], ("donald".into(), "Donald Knuth".into()));
-    assert_eq!(vars[1], ("mass".into(), "Massachusetts".into()));
+    assert_eq!(vars["donald"], "Donald Knuth");
+    assert_eq!(vars["mass"], "Massachusetts");
 
     assert_eq!(bibtex.preambles()[0], "Why not a preamble".to_string());
 
     let b0 = &bibtex.bibliographies()[0];
     assert_eq!(b0.entry_type(), "article");
     assert_eq!(b0.citation_key(), "einstein");
-    assert_eq!(b0.tags()[0], ("author".into(), "Albert Einstein".into()));
-    assert_eq!(b0.tags()[4], ("number".into(), "10".into()));
+    assert_eq!(b0.tags()["author"], "Albert Einstein");
+    assert_eq!(b0.tags()["number"], "10");
 
     let b1 = &bibtex.bibliographies()[1];
     assert_eq!(b1.citation_key(), "latexcompanion");
+    assert_eq!(b1.tags()["address"], "Reading, Massachusetts");
+
+    let b2 = &bibtex.bibliographies()[2];
+    assert_eq!(b2.citation_key(), "knuthwebsite");
+    assert_eq!(b2.tags()["author"], "Donald Knuth");
+}
+
+#[test]
+fn test_to_bibtex_string_round_trip() {
+    let bib_str = read_file("samples/test.bib");
+    let bibtex = Bibtex::parse(&bib_str).unwrap();
+
+    let serialized = bibtex.to_bibtex_string();
+    let reparsed = Bibtex::parse(&serialized).unwrap();
+    assert_eq!(reparsed, bibtex);
+    assert_eq!(reparsed.to_bibtex_string(), serialized);
+}
+
+#[test]
+fn test_to_bibtex_string_keeps_special_characters() {
+    let bib_str = r#"@string{ key = {a "value", {with} # signs} }
+        @misc{ citation,
+            title = "A {bunch {of} braces}, @ and # signs",
+            note = {50\% of "quotes"}
+        }"#;
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let reparsed = Bibtex::parse(&bibtex.to_bibtex_string()).unwrap();
+    assert_eq!(reparsed.variables()["key"], "a \"value\", {with} # signs");
+    let tags = reparsed.bibliographies()[0].tags();
+    assert_eq!(tags["title"], "A {bunch {of} braces}, @ and # signs");
+    assert_eq!(tags["note"], "50\\% of \"quotes\"");
+}
+
+#[test]
+fn test_to_bibtex_string_keeps_abbreviations() {
+    let bib_str = r#"@string{ first = "Jo" }
+        @string{ jacm = first # " Journal of " # acm }
+        @string{ acm = {ACM} }
+        @misc{ a, journal = jacm, month = jan, note = "See " # first }
+        @misc{ b, journal = jacm, title = {Title} }"#;
+    let mut bibtex = Bibtex::parse(bib_str).unwrap();
+    bibtex
+        .get_mut("b")
+        .unwrap()
+        .insert_tag("journal", "Other".into())
+        .unwrap();
+
+    let serialized = bibtex.to_bibtex_string();
+    assert_eq!(
+        serialized,
+        r#"@string{first = {Jo}}
+@string{jacm = {Jo Journal of ACM}}
+@string{acm = {ACM}}
+
+@misc{a,
+    journal = jacm,
+    month = jan,
+    note = "See " # first
+}
+
+@misc{b,
+    journal = {Other},
+    title = {Title}
+}
+"#
+    );
+    let reparsed = Bibtex::parse(&serialized).unwrap();
+    assert_eq!(reparsed, bibtex);
+    assert_eq!(reparsed.to_bibtex_string(), serialized);
+}
+
+#[test]
+fn test_to_bibtex_string_pretty() {
+    let bib_str = r#"@string{ acm = "ACM" }
+        @article{ key,
+            Author = {Hoare, C. A. R.},
+            title = {An axiomatic basis for {computer programming} in Stra\ss e and \ curly},
+            journal = "Comm. of the " # acm,
+            note = {Say "hi"},
+            url = {https://example.com/a/very/long/path/that/cannot/be/broken}
+        }"#;
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    assert_eq!(
+        bibtex.to_bibtex_string_pretty(&WriteOptions::new()),
+        bibtex.to_bibtex_string()
+    );
+
+    let options = WriteOptions::new()
+        .indent(2)
+        .max_line_length(40)
+        .align_equals(true)
+        .delimiter(Delimiter::Quotes);
+    let serialized = bibtex.to_bibtex_string_pretty(&options);
+    assert_eq!(
+        serialized,
+        r#"@string{acm = "ACM"}
+
+@article{key,
+  Author  = "Hoare, C. A. R.",
+  title   = "An axiomatic basis for
+             {computer programming} in
+             Stra\ss e and \ curly",
+  journal = "Comm. of the " # acm,
+  note    = {Say "hi"},
+  url     = "https://example.com/a/very/long/path/that/cannot/be/broken"
+}
+"#
+    );
+    let options = ParseOptions::new().normalize_whitespace(true);
+    let reparsed = Bibtex::parse_with(&serialized, &options).unwrap();
+    assert_eq!(reparsed, bibtex);
+}
+
+#[test]
+fn test_bibliography_display_sorted_fields() {
+    let bibtex = Bibtex::parse(
+        r#"@article{ key,
+            volume = 3,
+            Year = 2001,
+            journal = {J. {ACM}},
+            title = " A {"quoted"} title",
+            author = {Doe, Jane}
+        }"#,
+    )
+    .unwrap();
+    let biblio = bibtex.get("key").unwrap();
+
+    assert_eq!(
+        format!("{:#}", biblio),
+        r#"@article{key,
+    author = {Doe, Jane},
+    title = " A {"quoted"} title",
+    Year = {2001},
+    journal = {J. {ACM}},
+    volume = {3}
+}"#
+    );
+    assert_eq!(
+        biblio.to_bibtex_string_pretty(&WriteOptions::new().sort_fields(true)),
+        format!("{:#}\n", biblio)
+    );
+    assert!(format!("{}", biblio).starts_with("@article{key,\n    volume = {3},"));
+
+    let reparsed = Bibtex::parse(&format!("{:#}", biblio)).unwrap();
+    let other = reparsed.get("key").unwrap();
+    assert_eq!(other.tags(), biblio.tags());
+    assert_eq!(other.original_key("year"), Some("Year"));
+}
+
+#[test]
+fn test_bibliography_hash_and_ord() {
+    use std::collections::{BTreeSet, HashSet};
+
+    let bibtex = Bibtex::parse(
+        "@misc{ b, title = {T}, year = 2020 }
+        @book{ a, title = {T} }
+        @misc{ b, title = {T}, year = 2020 }
+        @article{ b, title = {T} }
+        @misc{ b, title = {Other} }",
+    )
+    .unwrap();
+    let biblios = bibtex.bibliographies();
+
+    let unique = biblios.iter().cloned().collect::<HashSet<_>>();
+    assert_eq!(unique.len(), 4);
+    let built = Bibliography::new(
+        "misc".into(),
+        "b".into(),
+        vec![("title".into(), "T".into()), ("year".into(), "2020".into())],
+    );
+    assert!(unique.contains(&built));
+
+    let sorted = biblios.iter().collect::<BTreeSet<_>>();
+    let keys = sorted
+        .iter()
+        .map(|b| (b.citation_key(), b.entry_type()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keys,
+        [
+            ("a", "book"),
+            ("b", "article"),
+            ("b", "misc"),
+            ("b", "misc")
+        ]
+    );
+
+    let mut fork = bibtex.clone();
+    fork.retain(|b| b.entry_type() == "misc");
+    assert_eq!(fork.bibliographies().len(), 3);
+    assert_eq!(bibtex.bibliographies().len(), 5);
+    assert_eq!(bibtex.clone(), bibtex);
+}
+
+#[test]
+fn test_display_reparses_into_equal_value() {
+    let bib_str = r#"@preamble{ "  padded preamble " }
+        @misc{ citation,
+            title = " padded, {title} ",
+            note = "a, b" # {c}
+        }"#;
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let reparsed = Bibtex::parse(&format!("{}", bibtex)).unwrap();
+    assert_eq!(reparsed, bibtex);
+    assert_eq!(reparsed.preambles()[0], "  padded preamble ");
+    assert_eq!(
+        reparsed.bibliographies()[0].tags()["title"],
+        " padded, {title} "
+    );
+}
+
+#[test]
+fn test_tags_ordered_follow_file_order() {
+    let bib_str = read_file("samples/test.bib");
+    let bibtex = Bibtex::parse(&bib_str).unwrap();
+
+    let keys = bibtex.bibliographies()[0]
+        .tags_ordered()
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>();
     assert_eq!(
-        b1.tags()[4],
+        keys,
+        vec!["author", "title", "journal", "volume", "number", "pages", "year", "doi"]
+    );
+    assert_eq!(
+        bibtex.bibliographies()[1].tags_ordered()[4],
         ("address".into(), "Reading, Massachusetts".into())
     );
+}
+
+#[test]
+fn test_authors() {
+    let bib_str = read_file("samples/test.bib");
+    let bibtex = Bibtex::parse(&bib_str).unwrap();
+
+    let authors = bibtex.bibliographies()[1].authors().unwrap();
+    let last_names = authors.iter().map(|n| n.last.as_str()).collect::<Vec<_>>();
+    assert_eq!(last_names, vec!["Goossens", "Mittelbach", "Samarin"]);
+    assert_eq!(authors[0].first, "Michel");
+}
+
+#[test]
+fn test_authors_and_editors() {
+    let bibtex = Bibtex::parse(
+        "@book{ key,
+            author = {von Neumann, John and Doe, Jr., John},
+            editor = {Jane Smith and {Barnes and Noble}}
+        }",
+    )
+    .unwrap();
+    let biblio = &bibtex.bibliographies()[0];
+
+    let authors = biblio.authors().unwrap();
+    assert_eq!(authors.len(), 2);
+    assert_eq!(
+        (authors[0].first.as_str(), authors[0].von.as_str()),
+        ("John", "von")
+    );
+    assert_eq!(authors[0].last, "Neumann");
+    assert_eq!(
+        (authors[1].last.as_str(), authors[1].jr.as_str()),
+        ("Doe", "Jr.")
+    );
+
+    let editors = biblio.editors().unwrap();
+    assert_eq!(editors.len(), 2);
+    assert_eq!(editors[0].last, "Smith");
+    assert_eq!(editors[1].last, "{Barnes and Noble}");
+
+    let misc = Bibtex::parse("@misc{ key, title = {T} }").unwrap();
+    assert_eq!(misc.bibliographies()[0].editors(), Ok(vec![]));
+}
+
+#[test]
+fn test_get_tag_is_case_insensitive() {
+    let bib_str = read_file("samples/test.bib");
+    let bibtex = Bibtex::parse(&bib_str).unwrap();
+
+    let b0 = &bibtex.bibliographies()[0];
+    assert_eq!(b0.get_tag("author"), Some("Albert Einstein"));
+    assert_eq!(b0.get_tag("Author"), Some("Albert Einstein"));
+    assert_eq!(b0.get_tag("AUTHOR"), Some("Albert Einstein"));
+    assert_eq!(b0.get_tag("editor"), None);
+    assert!(b0.has_tag("Doi"));
+    assert!(!b0.has_tag("isbn"));
+}
+
+#[test]
+fn test_original_keys_are_kept() {
+    let bibtex = Bibtex::parse(
+        "@misc{ key,
+            DOI = {10.1000/182},
+            Timestamp = {2020.01.01},
+            title = {Title}
+        }",
+    )
+    .unwrap();
+
+    let biblio = &bibtex.bibliographies()[0];
+    assert_eq!(biblio.tags().get("doi"), Some(&"10.1000/182".to_string()));
+    assert_eq!(biblio.original_key("doi"), Some("DOI"));
+    assert_eq!(biblio.original_key("TIMESTAMP"), Some("Timestamp"));
+    assert_eq!(biblio.original_key("isbn"), None);
+    assert_eq!(
+        biblio.original_keys().collect::<Vec<_>>(),
+        vec!["DOI", "Timestamp", "title"]
+    );
+
+    assert_eq!(biblio.original_field_name("timestamp"), Some("Timestamp"));
+    assert_eq!(biblio.original_field_name("Timestamp"), None);
+
+    let serialized = bibtex.to_bibtex_string();
+    assert!(serialized.contains("    DOI = {10.1000/182},\n"));
+    assert_eq!(Bibtex::parse(&serialized).unwrap(), bibtex);
+}
+
+#[test]
+fn test_tag_decoded() {
+    let bib_str = read_file("samples/test.bib");
+    let bibtex = Bibtex::parse(&bib_str).unwrap();
+
+    let b0 = &bibtex.bibliographies()[0];
+    assert!(b0.tag_decoded("title").unwrap().contains("bewegter Körper"));
+    assert_eq!(b0.tag_decoded("author"), Some("Albert Einstein".into()));
+    assert_eq!(b0.tag_decoded("isbn"), None);
 
     let b2 = &bibtex.bibliographies()[2];
-    assert_eq!(b2.citation_key(), "knuthwebsite");
-    assert_eq!(b2.tags()[0], ("author".into(), "Donald Knuth".into()));
+    assert_eq!(
+        b2.tag_decoded("url"),
+        Some("http://www-cs-faculty.stanford.edu/~uno/abcde.html".into())
+    );
+}
+
+#[test]
+fn test_month_number() {
+    let bib_str = "@misc{ a, month = jan }
+        @misc{ b, month = {3} }
+        @misc{ c, month = \"SEP\" }
+        @misc{ d, month = {December} }
+        @misc{ e, month = {13} }
+        @misc{ f, month = {spring} }
+        @misc{ g, year = 2000 }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let months = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.month_number())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        months,
+        vec![Some(1), Some(3), Some(9), Some(12), None, None, None]
+    );
+}
+
+#[test]
+fn test_month() {
+    let bibtex = Bibtex::parse(
+        "@misc{ a, month = {\"sep\"} }
+        @misc{ b, month = 9 }
+        @misc{ c, month = {  October } }",
+    )
+    .unwrap();
+
+    let months = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.month())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        months,
+        vec![
+            Some(Month::September),
+            Some(Month::September),
+            Some(Month::October)
+        ]
+    );
+
+    let month = Month::September;
+    assert_eq!(month.number(), 9);
+    assert_eq!(month.abbrev(), "sep");
+    assert_eq!(month.full_name(), "September");
+    assert_eq!(Month::from_number(0), None);
+    assert!(Month::January < Month::December);
+}
+
+#[test]
+fn test_parse_with_variables() {
+    let bib_str = "@string{ jacm = \"J. ACM\" }
+        @string{ jacm_short = jacm # \" (short)\" }
+        @misc{ a, journal = jacm, note = jacm_short, publisher = acm }";
+    let vars = vec![
+        ("jacm".to_string(), "Journal of the ACM".to_string()),
+        ("acm".to_string(), "ACM Press".to_string()),
+    ]
+    .into_iter()
+    .collect();
+    let bibtex = Bibtex::parse_with_variables(bib_str, vars).unwrap();
+
+    let tags = bibtex.bibliographies()[0].tags();
+    assert_eq!(tags["journal"], "J. ACM");
+    assert_eq!(tags["note"], "J. ACM (short)");
+    assert_eq!(tags["publisher"], "ACM Press");
+
+    assert!(Bibtex::parse(bib_str).is_err());
+}
+
+#[test]
+fn test_parse_with_constants() {
+    let bib_str = "@string{ acm = {ACM} }
+        @misc{ a, journal = prl, publisher = acm, month = feb, note = jan }";
+    let constants = vec![
+        ("prl", "Phys. Rev. Lett."),
+        ("acm", "Unused"),
+        ("jan", "Jan."),
+    ]
+    .into_iter()
+    .collect();
+    let bibtex = Bibtex::parse_with_constants(bib_str, &constants).unwrap();
+
+    let tags = bibtex.bibliographies()[0].tags();
+    assert_eq!(tags["journal"], "Phys. Rev. Lett.");
+    assert_eq!(tags["publisher"], "ACM");
+    assert_eq!(tags["month"], "February");
+    assert_eq!(tags["note"], "Jan.");
+
+    assert_eq!(
+        Bibtex::parse_with_constants("@misc{ a, journal = pra }", &constants),
+        Err(BibtexError::StringVariableNotFound {
+            var: "pra".into(),
+            used_in: VariableUse::Tag {
+                citation_key: "a".into(),
+                field: "journal".into(),
+            },
+        })
+    );
+}
+
+#[test]
+fn test_missing_variable_context() {
+    let missing = |bib_str| match Bibtex::parse(bib_str) {
+        Err(BibtexError::StringVariableNotFound { var, used_in }) => (var, used_in),
+        other => panic!("Unexpected result: {:?}", other),
+    };
+
+    assert_eq!(
+        missing("@misc{ smith2020, Journal = {J.} # foo }"),
+        (
+            "foo".into(),
+            VariableUse::Tag {
+                citation_key: "smith2020".into(),
+                field: "journal".into()
+            }
+        )
+    );
+    assert_eq!(
+        missing("@string{ a = b } @string{ b = {B} # foo }"),
+        ("foo".into(), VariableUse::Variable("b".into()))
+    );
+    assert_eq!(
+        missing("@preamble{ \"A\" } @preamble{ \"B\" # foo }"),
+        ("foo".into(), VariableUse::Preamble(2))
+    );
+}
+
+#[test]
+fn test_preamble_concatenation() {
+    let bib_str = r#"@string{ extravar = "\renewcommand{\y}{z}" }
+        @preamble{ "\newcommand{\x}{y}" # extravar }
+        @preamble{ extravar # { and } # "literal" # jan }
+        @string{ later = "later" }
+        @preamble{ later # extravar }"#;
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    assert_eq!(
+        bibtex.preambles(),
+        [
+            "\\newcommand{\\x}{y}\\renewcommand{\\y}{z}",
+            "\\renewcommand{\\y}{z}andliteralJanuary",
+            "later\\renewcommand{\\y}{z}",
+        ]
+    );
+
+    let bib_str = "@preamble{ \"A\" } @preamble{ foo }";
+    let results: Vec<_> = ExpandingIter::new(Bibtex::iter_entries(bib_str)).collect();
+    assert_eq!(
+        results[1],
+        Err(BibtexError::StringVariableNotFound {
+            var: "foo".into(),
+            used_in: VariableUse::Preamble(2)
+        })
+    );
+}
+
+#[test]
+fn test_concatenation_with_numbers() {
+    let bibtex = Bibtex::parse(&read_file("samples/concatenation.bib")).unwrap();
+
+    let biblio = bibtex.get("concatenation").unwrap();
+    assert_eq!(biblio.get_tag("title"), Some("Concatenated and more"));
+    assert_eq!(biblio.get_tag("year"), Some("2020-draft"));
+    assert_eq!(biblio.get_tag("volume"), Some("Vol. 12"));
+    assert_eq!(biblio.get_tag("edition"), Some("2"));
+    assert_eq!(biblio.get_tag("month"), Some("September"));
+    assert_eq!(biblio.get_tag("note"), Some("3 Concatenated 4"));
+    assert_eq!(
+        biblio.raw_tag("volume"),
+        Some(
+            &[
+                StringValueType::Abbreviation("vol".into()),
+                StringValueType::Str("12".into())
+            ][..]
+        )
+    );
+}
+
+#[test]
+fn test_variables_are_case_insensitive() {
+    let bib_str = "@string{ ACM = {Association for Computing Machinery} }
+        @string{ Full = acm # \" Press\" }
+        @misc{ a, publisher = Acm, note = FULL, month = JAN }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let biblio = bibtex.get("a").unwrap();
+    assert_eq!(
+        biblio.get_tag("publisher"),
+        Some("Association for Computing Machinery")
+    );
+    assert_eq!(
+        biblio.get_tag("note"),
+        Some("Association for Computing Machinery Press")
+    );
+    assert_eq!(biblio.get_tag("month"), Some("January"));
+
+    let constants = vec![("PRL", "Phys. Rev. Lett.")].into_iter().collect();
+    let bibtex = Bibtex::parse_with_constants("@misc{ a, journal = prl }", &constants).unwrap();
+    assert_eq!(
+        bibtex.get("a").unwrap().get_tag("journal"),
+        Some("Phys. Rev. Lett.")
+    );
+}
+
+#[test]
+fn test_variables_keep_their_spelling() {
+    let bib_str = "@STRING{ JACM = \"Journal of the ACM\" }
+        @String{ shortName = \"J. ACM\" }
+        @string{ jacm = shortname }
+        @misc{ a, journal = Jacm, note = SHORTNAME, month = Sep }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let biblio = bibtex.get("a").unwrap();
+    assert_eq!(biblio.get_tag("journal"), Some("J. ACM"));
+    assert_eq!(biblio.get_tag("note"), Some("J. ACM"));
+    assert_eq!(biblio.get_tag("month"), Some("September"));
+
+    let variables = bibtex.variables();
+    assert_eq!(variables.len(), 2);
+    assert_eq!(variables["JACM"], "J. ACM");
+    assert_eq!(bibtex.variable("jacm"), Some("J. ACM"));
+    assert_eq!(bibtex.variable("SHORTNAME"), Some("J. ACM"));
+    assert_eq!(bibtex.variable("sep"), None);
+    assert_eq!(variables["shortName"], "J. ACM");
+    assert_eq!(
+        bibtex.entries_in_order().nth(1),
+        Some(EntryRef::Variable("shortName", "J. ACM"))
+    );
+    assert!(bibtex
+        .to_bibtex_string()
+        .contains("@string{JACM = {J. ACM}}\n@string{shortName = {J. ACM}}\n"));
+}
+
+#[test]
+fn test_variables_out_of_order() {
+    let bib_str = "@string{ full = first # \" and \" # second }
+        @misc{ a, note = full }
+        @string{ first = {First} }
+        @string{ second = first # {, second} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    assert_eq!(bibtex.variables()["full"], "First and First, second");
+    assert_eq!(
+        bibtex.get("a").unwrap().get_tag("note"),
+        Some("First and First, second")
+    );
+
+    // A redefinition can use the previous value.
+    let bibtex = Bibtex::parse(
+        "@string{ name = {A} }
+        @string{ name = name # {B} }
+        @misc{ a, note = name }",
+    )
+    .unwrap();
+    assert_eq!(bibtex.get("a").unwrap().get_tag("note"), Some("AB"));
+}
+
+#[test]
+fn test_cyclic_variables() {
+    let bib_str = "@string{ a = {A} # b }
+        @string{ b = {B} # a }
+        @misc{ key, note = a }";
+    assert_eq!(
+        Bibtex::parse(bib_str),
+        Err(BibtexError::CyclicStringVariable("a".into()))
+    );
+    assert_eq!(
+        Bibtex::parse("@string{ a = a } @misc{ key, note = a }"),
+        Err(BibtexError::CyclicStringVariable("a".into()))
+    );
+
+    let (bibtex, errors) = Bibtex::parse_lossy(bib_str);
+    assert_eq!(bibtex.variables()["a"], "ABa");
+    assert_eq!(
+        errors,
+        vec![
+            BibtexError::CyclicStringVariable("a".into()),
+            BibtexError::CyclicStringVariable("b".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_with_warnings() {
+    let bib_str = "@misc{ first, title = { }, month = jan }
+        @misc{ second, month = {13}, note = {Note} }
+        @misc{ first, month = {Smarch}, author = {} }";
+    let (bibtex, warnings) = Bibtex::parse_with_warnings(bib_str);
+    assert_eq!(bibtex, Bibtex::parse(bib_str));
+
+    let location = |line, column, offset| SourceLocation {
+        line,
+        column,
+        offset,
+    };
+    assert_eq!(
+        warnings,
+        vec![
+            Warning::EmptyTag {
+                citation_key: "first".into(),
+                key: "title".into(),
+                location: location(1, 1, 0),
+            },
+            Warning::UnknownMonth {
+                citation_key: "second".into(),
+                value: "13".into(),
+                location: location(2, 9, 49),
+            },
+            Warning::UnknownMonth {
+                citation_key: "first".into(),
+                value: "Smarch".into(),
+                location: location(3, 9, 102),
+            },
+            Warning::EmptyTag {
+                citation_key: "first".into(),
+                key: "author".into(),
+                location: location(3, 9, 102),
+            },
+        ]
+    );
+
+    let options = ParseOptions::new().duplicate_citation_keys(DuplicatePolicy::Warn);
+    let (_, warnings) = Bibtex::parse_verbose(bib_str, &options).unwrap();
+    assert_eq!(warnings.len(), 5);
+    assert!(matches!(warnings[2], Warning::DuplicateCitationKey { .. }));
+
+    let (bibtex, warnings) = Bibtex::parse_with_warnings("@misc{ key, title = {A}");
+    assert!(bibtex.is_err());
+    assert_eq!(warnings, vec![]);
+
+    // The warnings found before a failure are kept.
+    let (bibtex, warnings) = Bibtex::parse_with_warnings(
+        "@misc{ first, title = {}, year = 1, Year = 2 }
+        @misc{ second, title = {A}",
+    );
+    assert!(bibtex.is_err());
+    assert_eq!(
+        warnings,
+        vec![Warning::DuplicateField {
+            citation_key: "first".into(),
+            key: "year".into(),
+            location: location(1, 1, 0),
+        }]
+    );
+    let (bibtex, warnings) = Bibtex::parse_with_warnings(
+        "@misc{ first, title = {}, year = 1, Year = 2 }
+        @misc{ second, title = unknown }",
+    );
+    assert!(bibtex.is_err());
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn test_duplicate_fields() {
+    let bib_str = "@misc{ key,
+            year = {2019},
+            title = {Title},
+            Year = {2020},
+            YEAR = {2021}
+        }
+        @misc{ other, title = {A}, note = {B} }";
+    let (bibtex, warnings) = Bibtex::parse_with_warnings(bib_str);
+
+    // The last value is kept at the position of the first one.
+    let biblio = bibtex.as_ref().unwrap().get("key").unwrap();
+    assert_eq!(
+        biblio.iter_tags().collect::<Vec<_>>(),
+        vec![("year", "2021"), ("title", "Title")]
+    );
+    assert_eq!(
+        warnings,
+        vec![Warning::DuplicateField {
+            citation_key: "key".into(),
+            key: "year".into(),
+            location: SourceLocation {
+                line: 1,
+                column: 1,
+                offset: 0
+            },
+        }]
+    );
+}
+
+#[test]
+fn test_deeply_nested_brackets() {
+    let bibtex = Bibtex::parse(
+        "@misc{ key, abstract = {We study {N}-body {{really} {{{nested}}}} systems} }",
+    )
+    .unwrap();
+    let biblio = bibtex.get("key").unwrap();
+    assert_eq!(
+        biblio.get_tag("abstract"),
+        Some("We study {N}-body {{really} {{{nested}}}} systems")
+    );
+    assert_eq!(
+        biblio.raw_tags()["abstract"],
+        vec![StringValueType::Str(
+            "We study {N}-body {{really} {{{nested}}}} systems".into()
+        )]
+    );
+
+    let depth = 100_000;
+    let nested = format!("{}x{}", "{".repeat(depth), "}".repeat(depth));
+    let options = ParseOptions::new().max_nesting_depth(depth);
+    let bib_str = format!("@misc{{ key, title = {} }}", nested);
+    let bibtex = Bibtex::parse_with(&bib_str, &options).unwrap();
+    assert_eq!(
+        bibtex.get("key").unwrap().get_tag("title"),
+        Some(&nested[1..nested.len() - 1])
+    );
+    assert!(matches!(
+        Bibtex::parse(&format!(
+            "@misc{{ key, title = {}",
+            &nested[..nested.len() - 1]
+        )),
+        Err(BibtexError::UnclosedBracket { .. })
+    ));
+}
+
+#[test]
+fn test_get_by_citation_key() {
+    let bib_str = "@misc{ first, note = {1} }
+        @misc{ second, note = {2} }
+        @misc{ first, note = {3} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    assert_eq!(bibtex.get("second").unwrap().get_tag("note"), Some("2"));
+    assert_eq!(bibtex.get("first").unwrap().get_tag("note"), Some("1"));
+    assert!(bibtex.get("First").is_none());
+    assert!(bibtex.get("missing").is_none());
+
+    let keys = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.citation_key())
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["first", "second", "first"]);
+}
+
+#[test]
+fn test_rename_tag() {
+    let mut bibtex = Bibtex::parse(
+        "@string{ jt = {Journal} }
+        @article{ key, JournalTitle = jt, abstract = {Long}, journal = {Old} }",
+    )
+    .unwrap();
+    let biblio = bibtex.get_mut("key").unwrap();
+
+    assert_eq!(biblio.rename_tag("journaltitle", "Journal"), Ok(true));
+    assert_eq!(biblio.rename_tag("journaltitle", "journal"), Ok(false));
+    assert_eq!(biblio.get_tag("journal"), Some("Journal"));
+    assert_eq!(biblio.original_key("journal"), Some("Journal"));
+    assert_eq!(
+        biblio.raw_tag("journal"),
+        Some(&[StringValueType::Abbreviation("jt".into())][..])
+    );
+    assert_eq!(biblio.raw_tag("journaltitle"), None);
+
+    assert_eq!(biblio.rename_tag("ABSTRACT", "abstract"), Ok(true));
+    assert_eq!(biblio.original_key("abstract"), Some("abstract"));
+    assert_eq!(
+        biblio.original_keys().collect::<Vec<_>>(),
+        ["Journal", "abstract"]
+    );
+    assert_eq!(
+        biblio.to_string(),
+        "@article{key,\n    Journal = {Journal},\n    abstract = {Long}\n}"
+    );
+
+    // A name which could not be parsed back is rejected.
+    assert_eq!(
+        biblio.rename_tag("abstract", "my title = {x}, t"),
+        Err(BibtexError::InvalidTagName {
+            entry: "key".into(),
+            name: "my title = {x}, t".into()
+        })
+    );
+    assert_eq!(biblio.get_tag("abstract"), Some("Long"));
+    assert_eq!(Bibtex::parse(&bibtex.to_bibtex_string()).unwrap(), bibtex);
+}
+
+#[test]
+fn test_editing_bibliographies() {
+    let bib_str = read_file("samples/test.bib");
+    let mut bibtex = Bibtex::parse(&bib_str).unwrap();
+
+    let b0 = bibtex.get_mut("einstein").unwrap();
+    assert_eq!(b0.insert_tag("Note", "Famous".into()), Ok(None));
+    assert_eq!(
+        b0.insert_tag("year", "1906".into()),
+        Ok(Some("1905".into()))
+    );
+    assert_eq!(
+        b0.insert_tag("note", "unbalanced } here".into()),
+        Err(BibtexError::UnbalancedTagValue {
+            entry: "einstein".into(),
+            key: "note".into()
+        })
+    );
+    assert_eq!(
+        b0.insert_tag("Ti tle", "Title".into()),
+        Err(BibtexError::InvalidTagName {
+            entry: "einstein".into(),
+            name: "Ti tle".into()
+        })
+    );
+    assert_eq!(b0.get_tag("note"), Some("Famous"));
+    assert!(!b0.has_tag("ti tle"));
+    assert_eq!(
+        b0.remove_tag("DOI"),
+        Some("http://dx.doi.org/10.1002/andp.19053221004".into())
+    );
+    assert_eq!(b0.remove_tag("doi"), None);
+    b0.set_citation_key("einstein1905".into());
+
+    assert!(bibtex.get("einstein").is_none());
+    let b0 = bibtex.get("einstein1905").unwrap();
+    assert_eq!(b0.tags_ordered()[6], ("year".into(), "1906".into()));
+    assert_eq!(
+        b0.tags_ordered().last().unwrap(),
+        &("note".into(), "Famous".into())
+    );
+
+    let removed = bibtex.remove_bibliography("latexcompanion").unwrap();
+    assert_eq!(removed.entry_type(), "book");
+    assert!(bibtex.get("latexcompanion").is_none());
+    assert!(bibtex.remove_bibliography("latexcompanion").is_none());
+
+    bibtex.push_bibliography(removed);
+    assert_eq!(bibtex.bibliographies().len(), 3);
+    assert_eq!(bibtex.bibliographies()[2].citation_key(), "latexcompanion");
+    assert!(bibtex.get("latexcompanion").is_some());
+}
+
+#[test]
+fn test_percent_comments_between_entries() {
+    let bib_str = "@article{ first, title = {First} }
+        % TODO: check the pages of @article{ second }
+        %
+        @article{ second, title = {Second} }
+        Some text % not a TeX comment
+        % 50% of the last @article
+        ";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let keys = bibtex
+        .iter()
+        .map(|biblio| biblio.citation_key())
+        .collect::<Vec<_>>();
+    assert_eq!(keys, ["first", "second"]);
+    assert_eq!(
+        bibtex.comments(),
+        [
+            "% TODO: check the pages of @article{ second }",
+            "%",
+            "Some text % not a TeX comment",
+            "% 50% of the last @article",
+        ]
+    );
+
+    let reparsed = Bibtex::parse(&bibtex.to_bibtex_string()).unwrap();
+    assert_eq!(reparsed.comments(), bibtex.comments());
+}
+
+#[test]
+fn test_field_accessors() {
+    let bib_str = "@article{ key,
+        Author = {Einstein, Albert},
+        TITLE = {On the electrodynamics of moving bodies},
+        journaltitle = {Annalen der Physik},
+        year = {1905},
+        doi = {10.1002/andp.19053221004}
+    }
+    @misc{ empty }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let biblio = bibtex.get("key").unwrap();
+    assert_eq!(biblio.author(), Some("Einstein, Albert"));
+    assert_eq!(
+        biblio.title(),
+        Some("On the electrodynamics of moving bodies")
+    );
+    assert_eq!(biblio.journal(), Some("Annalen der Physik"));
+    assert_eq!(biblio.year(), Some(1905));
+    assert_eq!(biblio.doi().unwrap().as_str(), "10.1002/andp.19053221004");
+
+    let empty = bibtex.get("empty").unwrap();
+    assert_eq!(empty.title(), None);
+    assert_eq!(empty.author(), None);
+    assert_eq!(empty.journal(), None);
+}
+
+#[test]
+fn test_parsing_error_location() {
+    let bib_str = "@misc{ first, note = {1} }\r\n\
+        \r\n\
+        héhé @misc{ second, note = }";
+
+    match Bibtex::parse(bib_str) {
+        Err(BibtexError::ParsingError {
+            line,
+            column,
+            source_line,
+            ..
+        }) => {
+            assert_eq!(line, 3);
+            assert_eq!(column, 28);
+            assert_eq!(source_line, "héhé @misc{ second, note = }");
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_parsing_error_messages() {
+    let message = |bib_str| match Bibtex::parse(bib_str) {
+        Err(BibtexError::ParsingError { message, .. }) => message,
+        other => panic!("Unexpected result: {:?}", other),
+    };
+    assert_eq!(
+        message("@misc{ key, title = {T} year = 2000 }"),
+        "expected `,` or `}` after field value"
+    );
+    assert_eq!(
+        message("@misc( key, title = {T} year = 2000 )"),
+        "expected `,` or `)` after field value"
+    );
+    assert_eq!(message("@misc{ key, title = }"), "expected field value");
+    assert_eq!(
+        message("@misc{ key, title {T} }"),
+        "expected `=` after field name"
+    );
+    assert_eq!(
+        message("@misc{ key title = {T} }"),
+        "expected `,` after citation key"
+    );
+    assert_eq!(
+        message("@string{ name }"),
+        "expected `=` after variable name"
+    );
+    assert_eq!(message("@preamble{ }"), "expected preamble value");
+    assert_eq!(message("@misc key"), "expected `{` or `(` after entry type");
+
+    let err =
+        Bibtex::parse("@misc{ first }\n@misc{ key,\n  title = {T}\n  year = 2000\n}").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Parsing error at line 4, column 3: expected `,` or `}` after field value\n  \
+         year = 2000\n  ^"
+    );
+}
+
+#[test]
+fn test_iter_tags_and_serialization_keep_source_order() {
+    let bib_str = "@misc{ key, year = 2000, title = {T}, author = {A} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let biblio = &bibtex.bibliographies()[0];
+    assert_eq!(
+        biblio.iter_tags().collect::<Vec<_>>(),
+        vec![("year", "2000"), ("title", "T"), ("author", "A")]
+    );
+
+    let reparsed = Bibtex::parse(&bibtex.to_bibtex_string()).unwrap();
+    let keys = reparsed.bibliographies()[0]
+        .iter_tags()
+        .map(|(k, _)| k)
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["year", "title", "author"]);
+}
+
+#[test]
+fn test_parse_strict_rejects_duplicate_citation_keys() {
+    let bib_str = "@article{ smith2020, title = {A} }
+        @article{ Smith2020, title = {B} }";
+    assert!(Bibtex::parse_strict(bib_str).is_ok());
+
+    let bib_str = "@article{ smith2020, title = {A} }
+        @book{ smith2020, title = {B} }";
+    assert_eq!(
+        Bibtex::parse_strict(bib_str),
+        Err(BibtexError::DuplicateCitationKey {
+            key: "smith2020".into(),
+            first: Some(SourceLocation {
+                line: 1,
+                column: 1,
+                offset: 0
+            }),
+            duplicate: Some(SourceLocation {
+                line: 2,
+                column: 9,
+                offset: 43
+            }),
+        })
+    );
+    assert_eq!(Bibtex::parse(bib_str).unwrap().bibliographies().len(), 2);
+}
+
+#[test]
+fn test_duplicate_policies() {
+    let bib_str = "@misc{ key, title = {First} }
+        @comment{Comment}
+        @misc{ other, title = {Other} }
+        @misc{ key, title = {Last} }";
+    let parse = |policy| {
+        Bibtex::parse_verbose(
+            bib_str,
+            &ParseOptions::new().duplicate_citation_keys(policy),
+        )
+        .unwrap()
+    };
+    let titles = |bibtex: &Bibtex| {
+        bibtex
+            .entries_in_order()
+            .filter_map(|entry| match entry {
+                EntryRef::Bibliography(b) => b.get_tag("title").map(String::from),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let (bibtex, warnings) = parse(DuplicatePolicy::Allow);
+    assert_eq!(titles(&bibtex), ["First", "Other", "Last"]);
+    assert_eq!(warnings, vec![]);
+    assert_eq!(bibtex, Bibtex::parse(bib_str).unwrap());
+
+    let (bibtex, warnings) = parse(DuplicatePolicy::KeepFirst);
+    assert_eq!(titles(&bibtex), ["First", "Other"]);
+    assert_eq!(bibtex.get("key").unwrap().get_tag("title"), Some("First"));
+    assert_eq!(warnings, vec![]);
+
+    let (bibtex, _) = parse(DuplicatePolicy::KeepLast);
+    assert_eq!(titles(&bibtex), ["Other", "Last"]);
+    assert_eq!(bibtex.get("key").unwrap().get_tag("title"), Some("Last"));
+    assert_eq!(bibtex.entries_in_order().count(), 3);
+
+    let (bibtex, warnings) = parse(DuplicatePolicy::Warn);
+    assert_eq!(titles(&bibtex), ["First", "Other", "Last"]);
+    assert!(matches!(
+        &warnings[..],
+        [Warning::DuplicateCitationKey { citation_key, first, duplicate }]
+            if citation_key == "key" && (first.line, duplicate.line) == (1, 4)
+    ));
+
+    let options = ParseOptions::new().duplicate_citation_keys(DuplicatePolicy::Error);
+    assert!(matches!(
+        Bibtex::parse_with(bib_str, &options),
+        Err(BibtexError::DuplicateCitationKey { key, .. }) if key == "key"
+    ));
+}
+
+#[test]
+fn test_comment_entry_bodies() {
+    let bib_str = "@comment{jabref-meta: databaseType:bibtex;}
+        @Comment{
+            @article{fake, title = {Not {an} entry}}
+            {nested {groups}}
+        }
+        @comment(Parenthesized {with ) inside} @misc{also, fake})
+        @comment Up to the end of the line {
+        @misc{ key, title = {Title} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    assert_eq!(
+        bibtex.comment_blocks(),
+        [
+            "jabref-meta: databaseType:bibtex;",
+            "@article{fake, title = {Not {an} entry}}\n            {nested {groups}}",
+            "Parenthesized {with ) inside} @misc{also, fake}",
+            "Up to the end of the line {",
+        ]
+    );
+    assert_eq!(bibtex.bibliographies().len(), 1);
+    assert_eq!(bibtex.get("key").unwrap().get_tag("title"), Some("Title"));
+
+    let options = ParseOptions::new().lenient_comments(true);
+    let bibtex = Bibtex::parse_with("Text @ here\n@comment line", &options).unwrap();
+    assert_eq!(bibtex.comments(), ["Text @ here"]);
+    assert_eq!(bibtex.comment_blocks(), ["line"]);
+}
+
+#[test]
+fn test_constant_option() {
+    let bib_str = "@string{ acm = {ACM} }
+        @misc{ a, journal = JACM, publisher = acm, month = jan, note = dec }";
+    let options = ParseOptions::new()
+        .constant("jacm", "Journal of the ACM")
+        .constant("ACM", "Unused")
+        .constant("Jan", "Janvier");
+    let bibtex = Bibtex::parse_with(bib_str, &options).unwrap();
+
+    let biblio = bibtex.get("a").unwrap();
+    assert_eq!(biblio.get_tag("journal"), Some("Journal of the ACM"));
+    assert_eq!(biblio.get_tag("publisher"), Some("ACM"));
+    assert_eq!(biblio.get_tag("month"), Some("Janvier"));
+    assert_eq!(biblio.get_tag("note"), Some("December"));
+    assert!(!bibtex.variables().contains_key("jacm"));
+
+    assert!(Bibtex::parse(bib_str).is_err());
+}
+
+#[test]
+fn test_parse_options_fixture() {
+    let bib_str = read_file("samples/options.bib");
+    let titles = |bibtex: &Bibtex| {
+        bibtex
+            .bibliographies()
+            .iter()
+            .map(|b| b.get_tag("title").unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // The @ of the comment is not an entry.
+    assert!(Bibtex::parse(&bib_str).is_err());
+    let options = ParseOptions::new().lenient_comments(true);
+    assert!(matches!(
+        Bibtex::parse_with(&bib_str, &options),
+        Err(BibtexError::StringVariableNotFound { var, .. }) if var == "jacm"
+    ));
+
+    let options = options.constant("JACM", "Journal of the ACM");
+    let bibtex = Bibtex::parse_with(&bib_str, &options).unwrap();
+    assert_eq!(
+        bibtex.comments(),
+        ["Maintained by someone@example.com, see the @ sign."]
+    );
+    assert_eq!(
+        bibtex.get("knuth1974").unwrap().get_tag("journal"),
+        Some("Journal of the ACM")
+    );
+    assert_eq!(bibtex.bibliographies().len(), 4);
+    assert_eq!(
+        bibtex.get("erdos1935").unwrap().get_tag("author"),
+        Some("Paul Erd{\\\"o}s and George Szekeres")
+    );
+
+    let options = options.duplicate_citation_keys(DuplicatePolicy::KeepFirst);
+    let bibtex = Bibtex::parse_with(&bib_str, &options).unwrap();
+    assert_eq!(
+        titles(&bibtex),
+        [
+            "A combinatorial problem in geometry",
+            "Go to statement considered harmful",
+            "Structured Programming with go to Statements"
+        ]
+    );
+
+    let options = options.decode_latex(true);
+    let bibtex = Bibtex::parse_with(&bib_str, &options).unwrap();
+    assert_eq!(
+        bibtex.get("erdos1935").unwrap().get_tag("author"),
+        Some("Paul Erdös and George Szekeres")
+    );
+}
+
+#[test]
+fn test_lenient_commas() {
+    let bib_str = "@misc{ first,
+            title = {First}
+            note = {Note}
+        }
+        @book{ second, title = {Second},
+            author = {Author}
+            year = 2000, }";
+    assert!(Bibtex::parse(bib_str).is_err());
+
+    let options = ParseOptions::new().lenient_commas(true);
+    let (bibtex, warnings) = Bibtex::parse_verbose(bib_str, &options).unwrap();
+    assert_eq!(bibtex.get("first").unwrap().get_tag("note"), Some("Note"));
+    assert_eq!(bibtex.get("second").unwrap().get_tag("year"), Some("2000"));
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|w| (w.citation_key(), w.location().line))
+            .collect::<Vec<_>>(),
+        [("first", 2), ("second", 6)]
+    );
+    assert!(matches!(warnings[0], Warning::MissingComma { .. }));
+    assert_eq!(
+        warnings[1].to_string(),
+        "Missing comma in second at line 6, column 30"
+    );
+
+    assert!(Bibtex::parse_with("@misc{ a, title = {A} note = {B} }", &options).is_err());
+}
+
+#[test]
+fn test_lenient_comments() {
+    let bib_str = "Mail me at someone@example.com, see @ the end.
+
+        @misc{ key, title = {Title} }
+
+        Some notes { about @misc } entries.
+        @comment{Comment}";
+    let options = ParseOptions::new().lenient_comments(true);
+    let bibtex = Bibtex::parse_with(bib_str, &options).unwrap();
+
+    assert_eq!(
+        bibtex.comments(),
+        [
+            "Mail me at someone@example.com, see @ the end.",
+            "Some notes { about @misc } entries."
+        ]
+    );
+    assert_eq!(bibtex.comment_blocks(), ["Comment"]);
+    assert_eq!(bibtex.bibliographies().len(), 1);
+
+    assert!(Bibtex::parse(bib_str).is_err());
+    assert!(Bibtex::parse_with("Note @misc{ key, title = = }", &options).is_err());
+
+    let entries = Bibtex::iter_entries_with(bib_str, &options)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries.len(), 4);
+    let entries = Bibtex::parse_reader_with(bib_str.as_bytes(), &options)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries.len(), 4);
+    assert!(Bibtex::parse_reader(bib_str.as_bytes()).any(|e| e.is_err()));
+}
+
+#[test]
+fn test_entries_in_order() {
+    let mut bibtex = Bibtex::parse(
+        "@preamble{{Preamble}}
+        @misc{ first, title = {First} }
+        @comment{Comment}
+        @string{ name = {Old} }
+        @misc{ second, title = {Second} }
+        @string{ name = {New} }",
+    )
+    .unwrap();
+
+    let entries = bibtex.entries_in_order().collect::<Vec<_>>();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[0], EntryRef::Preamble("Preamble"));
+    assert!(matches!(entries[1], EntryRef::Bibliography(b) if b.citation_key() == "first"));
+    assert_eq!(entries[2], EntryRef::CommentBlock("Comment"));
+    assert_eq!(entries[3], EntryRef::Variable("name", "New"));
+    assert!(matches!(entries[4], EntryRef::Bibliography(b) if b.citation_key() == "second"));
+
+    bibtex.remove_bibliography("first");
+    let keys = bibtex
+        .entries_in_order()
+        .filter_map(|entry| match entry {
+            EntryRef::Bibliography(b) => Some(b.citation_key()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["second"]);
+}
+
+#[test]
+fn test_entry_type_enum() {
+    let bibtex = Bibtex::parse(
+        "@ARTICLE{ a, title = {A} }
+        @InProceedings{ b, title = {B} }
+        @Dataset{ c, title = {C} }",
+    )
+    .unwrap();
+
+    let types = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.entry_type_enum())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        vec![
+            EntryType::Article,
+            EntryType::InProceedings,
+            EntryType::Other("Dataset".into())
+        ]
+    );
+
+    assert_eq!(types[0].to_string(), "article");
+    assert_eq!(types[2].to_string(), "Dataset");
+    assert_eq!("phdThesis".parse(), Ok(EntryType::PhdThesis));
+}
+
+#[test]
+fn test_validate_all() {
+    let bib_str = read_file("samples/test.bib");
+    assert_eq!(Bibtex::parse(&bib_str).unwrap().validate_all(), vec![]);
+
+    let bibtex = Bibtex::parse(
+        "@article{ first, author = {A}, title = {T}, year = 2020, volume = 1, pages = 2 }
+        @dataset{ second, note = {N} }",
+    )
+    .unwrap();
+    let issues = bibtex.validate_all();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].citation_key, "first");
+    assert_eq!(issues[0].fields, vec!["journal"]);
+    assert_eq!(issues[0].level, Level::Required);
+}
+
+#[test]
+fn test_resolve_crossrefs() {
+    let mut bibtex = Bibtex::parse(
+        "@inproceedings{ child, title = {Child}, crossref = {parent} }
+        @proceedings{ parent, title = {Parent}, booktitle = {Proceedings},
+                      crossref = {series} }
+        @misc{ series, Publisher = {Publisher}, year = 2020 }",
+    )
+    .unwrap();
+    bibtex.resolve_crossrefs().unwrap();
+
+    let child = bibtex.get("child").unwrap();
+    assert_eq!(child.get_tag("title"), Some("Child"));
+    assert_eq!(child.get_tag("crossref"), Some("parent"));
+    assert_eq!(child.get_tag("booktitle"), Some("Proceedings"));
+    assert_eq!(child.get_tag("year"), Some("2020"));
+    assert_eq!(child.original_key("publisher"), Some("Publisher"));
+    assert_eq!(bibtex.get("parent").unwrap().get_tag("year"), Some("2020"));
+}
+
+#[test]
+fn test_resolve_crossrefs_child_fields_win() {
+    let mut bibtex = Bibtex::parse(
+        "@inproceedings{ first, year = 2021, crossref = {Conf} }
+        @inproceedings{ second, crossref = {conf} }
+        @proceedings{ conf, booktitle = {Conference}, year = 2020 }",
+    )
+    .unwrap();
+    bibtex.resolve_crossrefs().unwrap();
+
+    let first = bibtex.get("first").unwrap();
+    assert_eq!(first.get_tag("year"), Some("2021"));
+    assert_eq!(first.get_tag("booktitle"), Some("Conference"));
+    let second = bibtex.get("second").unwrap();
+    assert_eq!(second.get_tag("year"), Some("2020"));
+    assert_eq!(
+        second.iter_tags().map(|(k, _)| k).collect::<Vec<_>>(),
+        vec!["crossref", "booktitle", "year"]
+    );
+}
+
+#[test]
+fn test_resolve_crossrefs_errors() {
+    let mut bibtex = Bibtex::parse("@inproceedings{ child, crossref = {missing} }").unwrap();
+    assert_eq!(
+        bibtex.resolve_crossrefs(),
+        Err(BibtexError::CrossrefNotFound {
+            citation_key: "child".into(),
+            crossref: "missing".into()
+        })
+    );
+
+    let mut bibtex = Bibtex::parse(
+        "@misc{ first, crossref = {second} }
+        @misc{ second, crossref = {first} }",
+    )
+    .unwrap();
+    assert_eq!(
+        bibtex.resolve_crossrefs(),
+        Err(BibtexError::CyclicCrossref("first".into()))
+    );
+}
+
+#[test]
+fn test_set_and_xdata_entries() {
+    let mut bibtex = Bibtex::parse(
+        "@set{ both, entryset = { first, second } }
+        @article{ first, xdata = {journal, pub}, volume = 1 }
+        @article{ second, xdata = {journal}, volume = 2, year = 2021 }
+        @XData{ journal, journal = {Journal}, xdata = {pub}, year = 2020 }
+        @xdata{ pub, publisher = {Publisher}, journal = {Other} }",
+    )
+    .unwrap();
+    assert_eq!(bibtex.bibliographies().len(), 5);
+    let set = bibtex.get("both").unwrap();
+    assert_eq!(set.entry_type(), "set");
+    assert_eq!(set.entry_set(), ["first", "second"]);
+    assert_eq!(bibtex.get("first").unwrap().entry_set(), Vec::<&str>::new());
+    assert!(bibtex.get("journal").unwrap().is_xdata());
+
+    bibtex.resolve_xdata().unwrap();
+    let first = bibtex.get("first").unwrap();
+    assert_eq!(first.get_tag("journal"), Some("Journal"));
+    assert_eq!(first.get_tag("publisher"), Some("Publisher"));
+    assert_eq!(first.get_tag("year"), Some("2020"));
+    let second = bibtex.get("second").unwrap();
+    assert_eq!(second.get_tag("year"), Some("2021"));
+    assert_eq!(second.get_tag("publisher"), Some("Publisher"));
+    assert_eq!(
+        bibtex.get("journal").unwrap().get_tag("publisher"),
+        Some("Publisher")
+    );
+}
+
+#[test]
+fn test_resolve_xdata_errors() {
+    let mut bibtex = Bibtex::parse(
+        "@misc{ key, xdata = {data} }
+        @misc{ data, note = {Not an xdata entry} }",
+    )
+    .unwrap();
+    assert_eq!(
+        bibtex.resolve_xdata(),
+        Err(BibtexError::XdataNotFound {
+            citation_key: "key".into(),
+            xdata: "data".into()
+        })
+    );
+
+    let mut bibtex = Bibtex::parse(
+        "@misc{ key, xdata = {first} }
+        @xdata{ first, xdata = {second} }
+        @xdata{ second, xdata = {first} }",
+    )
+    .unwrap();
+    assert_eq!(
+        bibtex.resolve_xdata(),
+        Err(BibtexError::CyclicXdata("key".into()))
+    );
+}
+
+#[test]
+fn test_concatenation() {
+    let bibtex = Bibtex::parse(
+        "@string{ b = {, b,} }
+        @string{ d = \"a\" # b # \" d\" }
+        @misc{ key, title = \"a\" # b # \"c\", note = d }",
+    )
+    .unwrap();
+
+    let biblio = &bibtex.bibliographies()[0];
+    assert_eq!(biblio.get_tag("title"), Some("a, b,c"));
+    assert_eq!(biblio.get_tag("note"), Some("a, b, d"));
+}
+
+#[test]
+fn test_builder() {
+    let bibtex = BibtexBuilder::new()
+        .add_comment_block("Comment".into())
+        .add_preamble("Preamble".into())
+        .add_string_variable("Name".into(), "Value".into())
+        .add_bibliography(
+            "misc".into(),
+            "key".into(),
+            vec![("Title".into(), "Title".into())],
+        )
+        .build()
+        .unwrap();
+
+    assert_eq!(bibtex.entries_in_order().count(), 4);
+    assert_eq!(
+        bibtex.to_bibtex_string(),
+        "@comment{Comment}
+
+@preamble{{Preamble}}
+
+@string{Name = {Value}}
+
+@misc{key,
+    Title = {Title}
+}
+"
+    );
+    assert_eq!(Bibtex::parse(&bibtex.to_bibtex_string()).unwrap(), bibtex);
+
+    let result = BibtexBuilder::new()
+        .add_bibliography("misc".into(), " ".into(), vec![])
+        .build();
+    assert_eq!(
+        result,
+        Err(BibtexError::EmptyCitationKey {
+            entry_type: "misc".into(),
+            line: None
+        })
+    );
+
+    // Tags and variables which could not be parsed back are rejected.
+    let result = BibtexBuilder::new()
+        .add_bibliography(
+            "misc".into(),
+            "key".into(),
+            vec![("Ti tle".into(), "Title".into())],
+        )
+        .build();
+    assert_eq!(
+        result,
+        Err(BibtexError::InvalidTagName {
+            entry: "key".into(),
+            name: "Ti tle".into()
+        })
+    );
+    let result = BibtexBuilder::new()
+        .add_string_variable("pub".into(), "{Publisher".into())
+        .build();
+    assert_eq!(
+        result,
+        Err(BibtexError::UnbalancedTagValue {
+            entry: "@string".into(),
+            key: "pub".into()
+        })
+    );
+}
+
+#[test]
+fn test_doi() {
+    let bib_str = "@article{ a, doi = {https://dx.doi.org/10.1145/3290605.3300233} }
+        @article{ b, doi = {DOI:10.1002/(SICI)1097-4571(199806)49:8} }
+        @misc{ c, doi = {}, url = {http://example.com/paper}, note = {See 10.5555/12345678.} }
+        @misc{ d, url = {https://doi.org/10.1016/j.cell.2020.01.001} }
+        @misc{ e, note = {(DOI 10.1000/xyz123), version 10.2} }
+        @misc{ f, doi = {10.12/short}, note = {Page 10.5} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    let doi = |key| bibtex.get(key).unwrap().doi().map(|doi| doi.to_string());
+
+    assert_eq!(doi("a").as_deref(), Some("10.1145/3290605.3300233"));
+    assert_eq!(
+        doi("b").as_deref(),
+        Some("10.1002/(SICI)1097-4571(199806)49:8")
+    );
+    assert_eq!(doi("c").as_deref(), Some("10.5555/12345678"));
+    assert_eq!(doi("d").as_deref(), Some("10.1016/j.cell.2020.01.001"));
+    assert_eq!(doi("e").as_deref(), Some("10.1000/xyz123"));
+    assert_eq!(doi("f"), None);
+    assert_eq!(
+        bibtex.get("a").unwrap().doi().unwrap().as_url(),
+        "https://doi.org/10.1145/3290605.3300233"
+    );
+    assert_eq!(Doi::parse("110.1000/abc"), None);
+}
+
+#[test]
+fn test_title_parts() {
+    let bib_str = r#"@misc{ a, title = {The {GNU} Project} }
+        @misc{ b, title = "{{LaTeX} Companion}: {T}he {\"o}l" }
+        @misc{ c, title = {{Whole}} }
+        @misc{ d, note = {No title} }"#;
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let a = bibtex.get("a").unwrap();
+    assert_eq!(a.get_tag("title"), Some("The {GNU} Project"));
+    assert_eq!(
+        a.title_parts(),
+        [
+            TitlePart::Plain("The ".into()),
+            TitlePart::Protected("GNU".into()),
+            TitlePart::Plain(" Project".into()),
+        ]
+    );
+    assert_eq!(
+        bibtex.get("b").unwrap().title_parts(),
+        [
+            TitlePart::Protected("{LaTeX} Companion".into()),
+            TitlePart::Plain(": ".into()),
+            TitlePart::Protected("T".into()),
+            TitlePart::Plain("he ".into()),
+            TitlePart::Protected("\\\"o".into()),
+            TitlePart::Plain("l".into()),
+        ]
+    );
+    assert_eq!(
+        bibtex.get("c").unwrap().title_parts(),
+        [TitlePart::Protected("Whole".into())]
+    );
+    assert_eq!(bibtex.get("d").unwrap().title_parts(), []);
+    assert_eq!(
+        TitlePart::split("{Unclosed"),
+        [TitlePart::Protected("Unclosed".into())]
+    );
+
+    let serialized = bibtex.to_bibtex_string();
+    assert!(serialized.contains("title = {The {GNU} Project}"));
+    assert!(serialized.contains("title = {{Whole}}"));
+    let reparsed = Bibtex::parse(&serialized).unwrap();
+    for biblio in bibtex.bibliographies() {
+        let other = reparsed.get(biblio.citation_key()).unwrap();
+        assert_eq!(other.title_parts(), biblio.title_parts());
+    }
+}
+
+#[test]
+fn test_pages() {
+    let bibtex = Bibtex::parse(
+        "@misc{ a, pages = {101--110} }
+        @misc{ b, pages = { 101 - 110 } }
+        @misc{ c, pages = 101 }
+        @misc{ d, pages = {e1234} }
+        @misc{ e, pages = {7--12, 20} }
+        @misc{ f, pages = {} }
+        @misc{ g, title = {No pages} }",
+    )
+    .unwrap();
+
+    let pages = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.pages())
+        .collect::<Vec<_>>();
+    let range = Pages::Range {
+        start: "101".into(),
+        end: "110".into(),
+    };
+    assert_eq!(
+        pages,
+        vec![
+            Some(range.clone()),
+            Some(range.clone()),
+            Some(Pages::Single("101".into())),
+            Some(Pages::Single("e1234".into())),
+            Some(Pages::Other("7--12, 20".into())),
+            None,
+            None,
+        ]
+    );
+
+    assert_eq!(range.display_bibtex(), "101--110");
+    assert_eq!(
+        Pages::Other("7--12, 20".into()).display_bibtex(),
+        "7--12, 20"
+    );
+}
+
+#[test]
+fn test_from_file() {
+    let bibtex = Bibtex::from_file("samples/test.bib").unwrap();
+    assert_eq!(
+        bibtex,
+        Bibtex::parse(&read_file("samples/test.bib")).unwrap()
+    );
+
+    let dir = std::env::temp_dir().join(format!("nom-bibtex-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let strings = dir.join("strings.bib");
+    let entries = dir.join("entries.bib");
+    std::fs::write(&strings, "\u{feff}@string{ name = {Name} }").unwrap();
+    std::fs::write(&entries, "@misc{ key, author = name }").unwrap();
+
+    let bibtex = Bibtex::from_files(&[&strings, &entries]).unwrap();
+    assert_eq!(bibtex.variables()["name"], "Name");
+    assert_eq!(bibtex.get("key").unwrap().get_tag("author"), Some("Name"));
+    assert_eq!(bibtex.entries_in_order().count(), 2);
+
+    let err = Bibtex::from_files(&[&entries, &strings]).unwrap_err();
+    assert_eq!(
+        err,
+        BibtexError::File {
+            path: entries.clone(),
+            err: Box::new(BibtexError::StringVariableNotFound {
+                var: "name".into(),
+                used_in: VariableUse::Tag {
+                    citation_key: "key".into(),
+                    field: "author".into(),
+                },
+            }),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{}: String variable not found: name (used in field author of entry key)",
+            entries.display()
+        )
+    );
+
+    // The abbreviations of the first files are kept when writing.
+    let abbreviations = dir.join("abbreviations.bib");
+    let uses = dir.join("uses.bib");
+    std::fs::write(
+        &abbreviations,
+        "@string{ first = {Donald} }\n@string{ full = first # \" Knuth\" }",
+    )
+    .unwrap();
+    std::fs::write(&uses, "@misc{ key, author = full }").unwrap();
+    let bibtex = Bibtex::from_files(&[&abbreviations, &uses]).unwrap();
+    assert_eq!(
+        bibtex.get("key").unwrap().get_tag("author"),
+        Some("Donald Knuth")
+    );
+    let written = bibtex.to_bibtex_string();
+    assert!(written.contains("full = first # \" Knuth\""));
+    assert_eq!(Bibtex::parse(&written).unwrap(), bibtex);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(matches!(
+        Bibtex::from_file("samples/missing.bib"),
+        Err(BibtexError::File { err, .. }) if matches!(*err, BibtexError::Io(_))
+    ));
+}
+
+#[test]
+fn test_parse_bytes() {
+    let bib_str = read_file("samples/test.bib");
+    let (bibtex, encoding) = Bibtex::parse_bytes(bib_str.as_bytes()).unwrap();
+    assert_eq!(bibtex, Bibtex::parse(&bib_str).unwrap());
+    assert_eq!(encoding, Encoding::Utf8);
+
+    let mut bytes = b"\xef\xbb\xbf".to_vec();
+    bytes.extend(bib_str.as_bytes());
+    assert_eq!(
+        Bibtex::parse_bytes(&bytes).unwrap(),
+        (Bibtex::parse(&bib_str).unwrap(), Encoding::Utf8)
+    );
+
+    let (bibtex, encoding) =
+        Bibtex::parse_bytes(b"@misc{ key, title = {Caf\xe9 \x96 \x80} }").unwrap();
+    assert_eq!(encoding, Encoding::Windows1252);
+    assert_eq!(
+        bibtex.get("key").unwrap().get_tag("title"),
+        Some("Café – €")
+    );
+
+    assert!(matches!(
+        Bibtex::parse_bytes(b"@misc{ key, title = {\x8d} }"),
+        Err(BibtexError::Encoding(_))
+    ));
+}
+
+#[test]
+fn test_parse_bytes_as() {
+    let bytes = b"@misc{ key, title = {Caf\xe9 \x96 \x80} }";
+    let title = |encoding| {
+        Bibtex::parse_bytes_as(bytes, encoding).map(|bibtex| {
+            bibtex
+                .get("key")
+                .unwrap()
+                .get_tag("title")
+                .unwrap()
+                .to_owned()
+        })
+    };
+    assert_eq!(title(Encoding::Windows1252).unwrap(), "Café – €");
+    assert_eq!(title(Encoding::Latin1).unwrap(), "Café \u{96} \u{80}");
+    assert_eq!(
+        title(Encoding::Utf8),
+        Err(BibtexError::Encoding("invalid UTF-8 at offset 24".into()))
+    );
+
+    let bib_str = read_file("samples/test.bib");
+    assert_eq!(
+        Bibtex::parse_bytes_as(bib_str.as_bytes(), Encoding::Utf8).unwrap(),
+        Bibtex::parse(&bib_str).unwrap()
+    );
+}
+
+#[test]
+fn test_empty_input() {
+    assert_eq!(Bibtex::parse(""), Err(BibtexError::EmptyInput));
+    assert_eq!(Bibtex::parse(" \n\t\n"), Err(BibtexError::EmptyInput));
+    assert_eq!(Bibtex::parse_strict(""), Err(BibtexError::EmptyInput));
+    assert_eq!(
+        Bibtex::parse_lossy(""),
+        (Bibtex::default(), vec![BibtexError::EmptyInput])
+    );
+
+    let bibtex = Bibtex::parse("@comment{Nothing yet}").unwrap();
+    assert_eq!(bibtex.comment_blocks(), &["Nothing yet"]);
+    let bibtex = Bibtex::parse("Some notes").unwrap();
+    assert_eq!(bibtex.comments(), &["Some notes"]);
+}
+
+#[test]
+fn test_queries() {
+    let bibtex = Bibtex::parse(
+        "@article{ a, year = 2021, journal = {J} }
+        @misc{ b, year = 2021 }
+        @ARTICLE{ c, year = 2019 }
+        @inproceedings{ d, year = 2021 }",
+    )
+    .unwrap();
+    let keys = |biblios: &mut dyn Iterator<Item = &Bibliography>| {
+        biblios
+            .map(|b| b.citation_key().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(keys(&mut bibtex.by_entry_type("article")), ["a", "c"]);
+    assert_eq!(keys(&mut bibtex.by_entry_type("Misc")), ["b"]);
+    assert_eq!(
+        keys(&mut bibtex.by_entry_type("book")),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        keys(&mut bibtex.filter(|b| b.year() == Some(2021) && !b.has_tag("journal"))),
+        ["b", "d"]
+    );
+}
+
+#[test]
+fn test_ris() {
+    let bibtex = Bibtex::parse(&read_file("samples/test.bib")).unwrap();
+    assert_eq!(ris::to_ris(&bibtex), read_file("samples/test.ris"));
+
+    let bibtex = Bibtex::parse(
+        "@inproceedings{ key,
+            author = {Ludwig van Beethoven and King, Jr, Martin Luther},
+            editor = {Doe, Jane},
+            booktitle = {Proc{\\'e}edings},
+            year = 2020, month = mar,
+            keywords = {a, b; c},
+            crossref = {parent},
+            eventtitle = {Event}
+        }",
+    )
+    .unwrap();
+    assert_eq!(
+        bibtex.get("key").unwrap().to_ris(),
+        "TY  - CONF
+ID  - key
+AU  - van Beethoven, Ludwig
+AU  - King, Martin Luther, Jr
+ED  - Doe, Jane
+T2  - Proc\u{e9}edings
+PY  - 2020
+DA  - 2020/03/
+KW  - a
+KW  - b
+KW  - c
+N1  - eventtitle: Event
+ER  - \n"
+    );
+}
+
+#[test]
+fn test_date() {
+    let bibtex = Bibtex::parse(
+        "@misc{ a, date = {2019-03-05} }
+        @misc{ b, date = {2019-03/2019-06} }
+        @misc{ c, date = {2019/} }
+        @misc{ d, date = {../2019-06} }
+        @misc{ e, date = {2019-13}, year = 2019 }
+        @misc{ f, year = 2020, month = mar }
+        @misc{ g, year = {2020} }
+        @misc{ h, year = {To appear} }
+        @misc{ i, title = {No date} }",
+    )
+    .unwrap();
+
+    let day = |year, month, day| DatePart {
+        year,
+        month: Some(month),
+        day,
+    };
+    let dates = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.date())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        dates,
+        vec![
+            Some(BibDate::Single(day(2019, 3, Some(5)))),
+            Some(BibDate::Range {
+                start: Some(day(2019, 3, None)),
+                end: Some(day(2019, 6, None)),
+            }),
+            Some(BibDate::Range {
+                start: Some(DatePart {
+                    year: 2019,
+                    month: None,
+                    day: None
+                }),
+                end: None,
+            }),
+            Some(BibDate::Range {
+                start: None,
+                end: Some(day(2019, 6, None)),
+            }),
+            Some(BibDate::Raw("2019-13".into())),
+            Some(BibDate::Single(day(2020, 3, None))),
+            Some(BibDate::Single(DatePart {
+                year: 2020,
+                month: None,
+                day: None
+            })),
+            Some(BibDate::Raw("To appear".into())),
+            None,
+        ]
+    );
+
+    let date = dates[0].as_ref().unwrap();
+    assert_eq!(
+        (date.year(), date.month(), date.day()),
+        (Some(2019), Some(3), Some(5))
+    );
+    assert_eq!(dates[3].as_ref().unwrap().year(), Some(2019));
+    assert!(dates[1].as_ref().unwrap().start() < dates[1].as_ref().unwrap().end());
+    assert_eq!(dates[2].as_ref().unwrap().to_string(), "2019/");
+    assert_eq!(dates[5].as_ref().unwrap().to_string(), "2020-03");
+}
+
+#[test]
+fn test_escaped_quotes() {
+    let bibtex = Bibtex::parse(
+        r#"@misc{ key,
+            title = "A \"quoted\" word",
+            author = "M\"uller, {J\"org}",
+            note = "With {braces \" and} " # "\"quotes\""
+        }"#,
+    )
+    .unwrap();
+
+    let biblio = bibtex.get("key").unwrap();
+    assert_eq!(biblio.get_tag("title"), Some(r#"A \"quoted\" word"#));
+    assert_eq!(biblio.get_tag("author"), Some(r#"M\"uller, {J\"org}"#));
+    assert_eq!(
+        biblio.tag_decoded("author").as_deref(),
+        Some("Müller, {Jörg}")
+    );
+    assert_eq!(
+        biblio.get_tag("note"),
+        Some(r#"With {braces \" and} \"quotes\""#)
+    );
+}
+
+#[test]
+fn test_tag_normalized() {
+    let bibtex = Bibtex::parse(
+        "@misc{ key,
+            abstract = {  First line,
+                second\tline.\r\n
+                Last line.  },
+            title = \" Padded \",
+            note = {}
+        }",
+    )
+    .unwrap();
+
+    let biblio = bibtex.get("key").unwrap();
+    assert_eq!(
+        biblio.tag_normalized("abstract").as_deref(),
+        Some("First line, second line. Last line.")
+    );
+    assert!(biblio.get_tag("abstract").unwrap().contains('\n'));
+    assert_eq!(biblio.tag_normalized("title").as_deref(), Some("Padded"));
+    assert_eq!(biblio.get_tag("title"), Some(" Padded "));
+    assert_eq!(biblio.tag_normalized("note").as_deref(), Some(""));
+    assert_eq!(biblio.tag_normalized("author"), None);
+}
+
+#[test]
+fn test_decode_latex() {
+    let mut bibtex = Bibtex::parse(
+        "@preamble{ {\\newcommand{\\noop}[1]{}} }
+        @misc{ key,
+            author = {M{\\\"u}ller, J\\'er\\^ome and Stra\\ss{}e, \\`A\\~na},
+            title = {\\unknown{\\o}}
+        }",
+    )
+    .unwrap();
+    bibtex.decode_latex();
+
+    let biblio = &bibtex.bibliographies()[0];
+    assert_eq!(
+        biblio.get_tag("author"),
+        Some("Müller, Jérôme and Straße, Àña")
+    );
+    assert_eq!(biblio.get_tag("title"), Some("\\unknown{ø}"));
+    assert_eq!(bibtex.preambles(), &["\\newcommand{\\noop}[1]{}"]);
+}
+
+#[test]
+fn test_encode_latex() {
+    let bib_str = "@string{ city = {Zürich} }
+        @misc{ key,
+            author = {Müller, Jérôme and Straße, J\\\"urgen},
+            title = {Ħ and ħ – ‘quoted’},
+            address = city
+        }";
+    let mut bibtex = Bibtex::parse(bib_str).unwrap();
+    assert_eq!(bibtex.encode_latex(), ['Ħ', 'ħ']);
+
+    let biblio = &bibtex.bibliographies()[0];
+    assert_eq!(
+        biblio.get_tag("author"),
+        Some("M\\\"{u}ller, J\\'{e}r\\^{o}me and Stra{\\ss}e, J\\\"urgen")
+    );
+    assert_eq!(biblio.get_tag("title"), Some("Ħ and ħ -- `quoted'"));
+    assert_eq!(biblio.get_tag("address"), Some("Z\\\"{u}rich"));
+    assert_eq!(bibtex.variable("city"), Some("Zürich"));
+
+    let serialized = bibtex.to_bibtex_string();
+    assert!(serialized.contains("address = {Z\\\"{u}rich}"));
+    let mut reparsed = Bibtex::parse(&serialized).unwrap();
+    reparsed.decode_latex();
+    let biblio = &reparsed.bibliographies()[0];
+    assert_eq!(
+        biblio.get_tag("author"),
+        Some("Müller, Jérôme and Straße, Jürgen")
+    );
+    assert_eq!(biblio.get_tag("address"), Some("Zürich"));
+}
+
+#[test]
+fn test_year() {
+    let bibtex = Bibtex::parse(
+        "@string{ y = {2010} }
+        @misc{ a, year = 1905 }
+        @misc{ b, year = { {1993} } }
+        @misc{ c, year = y }
+        @misc{ d, date = {2021-06-01} }
+        @misc{ e, date = {2020/2021} }
+        @misc{ f, year = {to appear} }
+        @misc{ g, title = {No year} }",
+    )
+    .unwrap();
+
+    let years = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.year())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        years,
+        vec![
+            Some(1905),
+            Some(1993),
+            Some(2010),
+            Some(2021),
+            Some(2020),
+            None,
+            None
+        ]
+    );
+}
+
+#[test]
+fn test_unclosed_brackets() {
+    let bib_str = "@misc{ first, note = {1} }
+
+        @misc{ second, title = {Unclosed
+        @misc{ third, title = {Title} }";
+    assert_eq!(
+        Bibtex::parse(bib_str),
+        Err(BibtexError::UnclosedBracket {
+            entry: "second".into(),
+            line: 3,
+            column: 32
+        })
+    );
+
+    let bib_str = "@misc{ first, title = {Title}
+        @comment{ Unclosed";
+    assert_eq!(
+        Bibtex::parse(bib_str),
+        Err(BibtexError::UnclosedBracket {
+            entry: "first".into(),
+            line: 1,
+            column: 6
+        })
+    );
+
+    assert_eq!(
+        Bibtex::parse("@comment{ Unclosed {comment} é"),
+        Err(BibtexError::UnclosedBracket {
+            entry: "@comment".into(),
+            line: 1,
+            column: 9
+        })
+    );
+}
+
+#[test]
+fn test_granular_errors() {
+    let bib_str = "@misc{ first, title = {First} }
+        @misc{ second, ti.tle = {Second} }";
+    assert_eq!(
+        Bibtex::parse(bib_str),
+        Err(BibtexError::InvalidFieldName {
+            entry: "second".into(),
+            name: "ti.tle".into(),
+            line: 2,
+            column: 24
+        })
+    );
+
+    let bib_str = "@misc{ key,
+        Title = \"Unbalanced}\"
+    }";
+    assert_eq!(
+        Bibtex::parse(bib_str),
+        Err(BibtexError::UnbalancedBraces {
+            entry: "key".into(),
+            field: "title".into(),
+            line: 2,
+            column: 28
+        })
+    );
+
+    let bib_str = "@misc{ key, title = {Title} }\n@Book{ , title = {Title} }";
+    assert_eq!(
+        Bibtex::parse(bib_str),
+        Err(BibtexError::EmptyCitationKey {
+            entry_type: "book".into(),
+            line: Some(2)
+        })
+    );
+    assert!(matches!(
+        Bibtex::parse("@misc{}"),
+        Err(BibtexError::EmptyCitationKey { line: Some(1), .. })
+    ));
+
+    // Other failures are parsing errors.
+    assert!(matches!(
+        Bibtex::parse("@misc{ key, title {Title} }"),
+        Err(BibtexError::ParsingError { .. })
+    ));
+
+    // The errors are kept when skipping the invalid entries, and located
+    // from the start of a reader.
+    let bib_str = "@misc{ first, title = {First} }
+        @misc{ second, title = \"}\" }
+        @misc{ third, title = {Third} }";
+    let unbalanced = || BibtexError::UnbalancedBraces {
+        entry: "second".into(),
+        field: "title".into(),
+        line: 2,
+        column: 33,
+    };
+    let (bibtex, errors) = Bibtex::parse_lossy(bib_str);
+    assert_eq!(bibtex.bibliographies().len(), 2);
+    assert_eq!(errors, [unbalanced()]);
+    let mut entries = Bibtex::parse_reader(bib_str.as_bytes());
+    assert!(entries.next().unwrap().is_ok());
+    assert_eq!(entries.next(), Some(Err(unbalanced())));
+}
+
+#[test]
+fn test_iter_entries() {
+    let bib_str = read_file("samples/test.bib");
+    let entries = Bibtex::iter_entries(&bib_str)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries, Bibtex::raw_parse(&bib_str).unwrap());
+
+    let mut entries = Bibtex::iter_entries("@misc{ a, title = {A} } @misc{ b, title = {B");
+    assert!(entries.next().unwrap().is_ok());
+    assert!(matches!(
+        entries.next(),
+        Some(Err(BibtexError::UnclosedBracket { .. }))
+    ));
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn test_parse_reader() {
+    let bib_str = read_file("samples/test.bib");
+    let entries = Bibtex::parse_reader(bib_str.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries, Bibtex::raw_parse(&bib_str).unwrap());
+
+    let entries = Bibtex::parse_reader(File::open("samples/test.bib").unwrap())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries, Bibtex::raw_parse(&bib_str).unwrap());
+
+    // Entries on the same line and errors after the first lines.
+    let bib_str = "@misc{ a, title = {A} } @misc{ b, title = {B} }
+        Free text
+        @misc{ c,
+            title = {C}
+        }
+
+        @misc{ d, title = {D";
+    let mut entries = Bibtex::parse_reader(bib_str.as_bytes());
+    let mut expected = Bibtex::iter_entries(bib_str);
+    for _ in 0..4 {
+        assert_eq!(entries.next().unwrap(), expected.next().unwrap());
+    }
+    assert_eq!(
+        entries.next(),
+        Some(Err(BibtexError::UnclosedBracket {
+            entry: "d".into(),
+            line: 7,
+            column: 27
+        }))
+    );
+    assert!(entries.next().is_none());
+
+    // Lines starting with a `@` inside an entry, and entries delimited by
+    // parentheses.
+    let bib_str = "@misc{ a, abstract = {First line
+        @ second line {with braces}
+        @ third line}, title = {A} }
+        % A comment {
+        @misc( b, title = {B} )
+        @misc( c, title = {C
+        @ continued} )
+        @misc{ d, title = {D} }";
+    let entries = Bibtex::parse_reader(bib_str.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries, Bibtex::raw_parse(bib_str).unwrap());
+
+    // A long entry is parsed once complete rather than after each line.
+    let abstract_ = "\n@ line".repeat(50_000);
+    let bib_str = format!("@misc{{ a, abstract = {{{}}} }}\n@misc{{ b }}", abstract_);
+    let entries = Bibtex::parse_reader(bib_str.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+
+    let mut entries = Bibtex::parse_reader(&b"@misc{ a, title = {\xe9} }"[..]);
+    assert!(matches!(entries.next(), Some(Err(BibtexError::Io(_)))));
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn test_expanding_iter() {
+    let bib_str = "@string{ first = {First} }
+        @misc{ a, title = first # \" and \" # jan }
+        @misc{ b, title = second }
+        @string{ second = first # {, second} }
+        @misc{ c, title = second }";
+
+    let mut entries = ExpandingIter::new(Bibtex::iter_entries(bib_str));
+    assert_eq!(
+        entries.next(),
+        Some(Ok(ExpandedEntry::Variable("first".into(), "First".into())))
+    );
+    match entries.next() {
+        Some(Ok(ExpandedEntry::Bibliography(b))) => {
+            assert_eq!(b.get_tag("title"), Some("First and January"))
+        }
+        other => panic!("Unexpected entry: {:?}", other),
+    }
+    assert_eq!(
+        entries.next(),
+        Some(Err(BibtexError::StringVariableNotFound {
+            var: "second".into(),
+            used_in: VariableUse::Tag {
+                citation_key: "b".into(),
+                field: "title".into(),
+            },
+        }))
+    );
+    assert!(entries.next().unwrap().is_ok());
+    match entries.next() {
+        Some(Ok(ExpandedEntry::Bibliography(b))) => {
+            assert_eq!(b.get_tag("title"), Some("First, second"))
+        }
+        other => panic!("Unexpected entry: {:?}", other),
+    }
+    assert_eq!(entries.variables().len(), 2);
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn test_to_bibtex_string_keeps_entry_order() {
+    let bib_str = "@string{ name = {Name} }
+% hello
+@article{ first, author = name }
+
+@comment{Between}
+
+@preamble{ {P} }
+
+Free text
+
+@article{ second, title = {Second} }
+";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    let written = bibtex.to_bibtex_string();
+    assert_eq!(
+        written,
+        "@string{name = {Name}}
+
+% hello
+
+@article{first,
+    author = name
+}
+
+@comment{Between}
+
+@preamble{{P}}
+
+Free text
+
+@article{second,
+    title = {Second}
+}
+"
+    );
+
+    let reparsed = Bibtex::parse(&written).unwrap();
+    assert_eq!(reparsed, bibtex);
+    assert_eq!(
+        reparsed.entries_in_order().collect::<Vec<_>>(),
+        bibtex.entries_in_order().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_comment_kinds() {
+    let bib_str = "Free text before
+        @comment{A block}
+        @misc{ key, title = {Title} }
+        Free text after";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    assert_eq!(bibtex.comments(), &["Free text before", "Free text after"]);
+    assert_eq!(bibtex.comment_blocks(), &["A block"]);
+    assert_eq!(
+        bibtex.entries_in_order().take(2).collect::<Vec<_>>(),
+        vec![
+            EntryRef::Comment("Free text before"),
+            EntryRef::CommentBlock("A block"),
+        ]
+    );
+    // The comments are written back in place.
+    let comments = Bibtex::parse(&bibtex.to_bibtex_string()).unwrap();
+    assert_eq!(comments.comments(), bibtex.comments());
+    assert_eq!(comments.comment_blocks(), bibtex.comment_blocks());
+
+    let mut entries = ExpandingIter::new(Bibtex::iter_entries(bib_str));
+    assert_eq!(
+        entries.nth(1),
+        Some(Ok(ExpandedEntry::CommentBlock("A block".into())))
+    );
+}
+
+#[test]
+fn test_merge() {
+    let mut bibtex = Bibtex::parse(
+        "@comment{First}
+        @string{ a = {A} }
+        @string{ b = {B} }
+        @misc{ first, title = a }",
+    )
+    .unwrap();
+    let other = Bibtex::parse(
+        "@string{ a = {New A} }
+        @string{ b = {B} }
+        @string{ c = {C} }
+        @misc{ second, title = a }",
+    )
+    .unwrap();
+
+    assert_eq!(bibtex.merge(other), vec!["a"]);
+    assert_eq!(bibtex.variables()["a"], "New A");
+    assert_eq!(bibtex.variables()["c"], "C");
+    assert_eq!(bibtex.comment_blocks(), &["First"]);
+    assert_eq!(
+        bibtex.get("second").unwrap().get_tag("title"),
+        Some("New A")
+    );
+    assert_eq!(bibtex.entries_in_order().count(), 6);
+
+    let duplicate = Bibtex::parse("@misc{ first, title = {Other} }").unwrap();
+    assert!(matches!(
+        bibtex.try_merge(duplicate),
+        Err(BibtexError::DuplicateCitationKey { key, first: Some(_), duplicate: Some(_) })
+            if key == "first"
+    ));
+    assert_eq!(bibtex.bibliographies().len(), 2);
+}
+
+#[test]
+fn test_merge_policies() {
+    let personal = || Bibtex::parse(&read_file("samples/personal.bib")).unwrap();
+    let group = || Bibtex::parse(&read_file("samples/group.bib")).unwrap();
+    let keys = |bibtex: &Bibtex| {
+        bibtex
+            .bibliographies()
+            .iter()
+            .map(|b| b.citation_key().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let mut bibtex = personal();
+    bibtex.merge_with(group(), MergePolicy::KeepSelf).unwrap();
+    assert_eq!(keys(&bibtex), ["knuth1984", "notes", "report"]);
+    let knuth = bibtex.get("knuth1984").unwrap();
+    assert_eq!(knuth.get_tag("pages"), Some("1--483"));
+    assert_eq!(knuth.get_tag("publisher"), Some("Personal Publisher"));
+    assert_eq!(bibtex.variables()["pub"], "Personal Publisher");
+    assert_eq!(bibtex.variables()["group"], "The Group");
+    assert_eq!(
+        bibtex.preambles(),
+        ["\\newcommand{\\personal}{}", "\\newcommand{\\group}{}"]
+    );
+    assert_eq!(bibtex.comments().len(), 2);
+
+    let mut bibtex = personal();
+    bibtex.merge_with(group(), MergePolicy::KeepOther).unwrap();
+    assert_eq!(keys(&bibtex), ["notes", "knuth1984", "report"]);
+    let knuth = bibtex.get("knuth1984").unwrap();
+    assert_eq!(knuth.get_tag("pages"), Some("1--496"));
+    assert_eq!(knuth.get_tag("publisher"), Some("Group Publisher"));
+    assert_eq!(bibtex.variables()["pub"], "Group Publisher");
+    assert_eq!(bibtex.preambles().len(), 2);
+
+    let mut bibtex = personal();
+    assert!(matches!(
+        bibtex.merge_with(group(), MergePolicy::Error),
+        Err(BibtexError::DuplicateCitationKey { key, first: Some(_), duplicate: Some(_) })
+            if key == "knuth1984"
+    ));
+    assert_eq!(bibtex, personal());
+
+    let report = || {
+        let mut group = group();
+        group.remove_bibliography("knuth1984");
+        group
+    };
+    assert_eq!(
+        bibtex.merge_with(report(), MergePolicy::Error),
+        Err(BibtexError::ConflictingStringVariable("pub".into()))
+    );
+    assert_eq!(bibtex, personal());
+
+    let mut bibtex = Bibtex::parse("@string{ pub = \"Group Publisher\" }").unwrap();
+    bibtex.merge_with(report(), MergePolicy::Error).unwrap();
+    assert_eq!(keys(&bibtex), ["report"]);
+}
+
+#[test]
+fn test_parse_lossy() {
+    let bib_str = "@string{ name = {Name} }
+        @misc{ first, title = {Unclosed }
+        @misc{ second, title = name }
+        @misc{ third, title = , @misc{ inline }
+        @misc{ fourth, title = unknown # { } # name }";
+    let (bibtex, errors) = Bibtex::parse_lossy(bib_str);
+
+    let keys = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.citation_key())
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["second", "fourth"]);
+    assert_eq!(
+        bibtex.get("fourth").unwrap().get_tag("title"),
+        Some("unknownName")
+    );
+    assert_eq!(
+        errors,
+        vec![
+            BibtexError::UnclosedBracket {
+                entry: "first".into(),
+                line: 2,
+                column: 14
+            },
+            BibtexError::InvalidEntry {
+                entry: "third".into(),
+                line: 4,
+                column: 9
+            },
+            BibtexError::StringVariableNotFound {
+                var: "unknown".into(),
+                used_in: VariableUse::Tag {
+                    citation_key: "fourth".into(),
+                    field: "title".into(),
+                },
+            },
+        ]
+    );
+
+    let (bibtex, errors) = Bibtex::parse_lossy(&read_file("samples/test.bib"));
+    assert_eq!(errors, vec![]);
+    assert_eq!(
+        bibtex,
+        Bibtex::parse(&read_file("samples/test.bib")).unwrap()
+    );
+}
+
+#[test]
+fn test_parse_lossy_with() {
+    let bib_str = "@misc{ key, title = {A  title} }
+        @misc{ key, title = lncs # { 42}, note = unknown }
+        @misc{ other,
+            title = {Title}
+            note = {Note}
+        }";
+    let options = ParseOptions::new()
+        .constant("lncs", "LNCS")
+        .normalize_whitespace(true)
+        .duplicate_citation_keys(DuplicatePolicy::KeepLast);
+    let (bibtex, errors) = Bibtex::parse_lossy_with(bib_str, &options);
+
+    assert_eq!(bibtex.bibliographies().len(), 1);
+    let biblio = bibtex.get("key").unwrap();
+    assert_eq!(biblio.get_tag("title"), Some("LNCS42"));
+    assert_eq!(biblio.get_tag("note"), Some("unknown"));
+    assert_eq!(
+        errors,
+        vec![
+            BibtexError::InvalidEntry {
+                entry: "other".into(),
+                line: 3,
+                column: 9
+            },
+            BibtexError::StringVariableNotFound {
+                var: "unknown".into(),
+                used_in: VariableUse::Tag {
+                    citation_key: "key".into(),
+                    field: "note".into(),
+                },
+            },
+        ]
+    );
+
+    let options = options
+        .lenient_commas(true)
+        .duplicate_citation_keys(DuplicatePolicy::Error);
+    let (bibtex, errors) = Bibtex::parse_lossy_with(bib_str, &options);
+    assert_eq!(bibtex.bibliographies().len(), 3);
+    assert_eq!(bibtex.get("key").unwrap().get_tag("title"), Some("A title"));
+    assert!(matches!(
+        &errors[..],
+        [
+            BibtexError::StringVariableNotFound { .. },
+            BibtexError::DuplicateCitationKey { key, .. },
+        ] if key == "key"
+    ));
+}
+
+#[test]
+fn test_location() {
+    let bib_str = "@misc{ first, note = {é} }\r\n\
+        \r\n\
+        héhé @misc{ second, note = {ü} }\r\n\
+        \t@misc{ third, note = {3} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let locations = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.location().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        locations,
+        vec![
+            SourceLocation {
+                line: 1,
+                column: 1,
+                offset: 0
+            },
+            SourceLocation {
+                line: 3,
+                column: 6,
+                offset: 38
+            },
+            SourceLocation {
+                line: 4,
+                column: 2,
+                offset: 69
+            },
+        ]
+    );
+    assert_eq!(&bib_str[locations[1].offset..][..6], "@misc{");
+
+    let built = BibtexBuilder::new()
+        .add_bibliography("misc".into(), "key".into(), vec![])
+        .build()
+        .unwrap();
+    assert_eq!(built.bibliographies()[0].location(), None);
+}
+
+#[test]
+fn test_source_span() {
+    let bib_str = "@string{ name = {Name} }
+        @misc{ first,
+            title = {Multi {line}
+            title}
+        }
+        @book{ second, author = name }  trailing text";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let spans = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| {
+            let (start, end) = b.source_span().unwrap();
+            &bib_str[start..end]
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![
+            "@misc{ first,
+            title = {Multi {line}
+            title}
+        }",
+            "@book{ second, author = name }"
+        ]
+    );
+
+    let (bibtex, _) = Bibtex::parse_lossy("@misc{ a, title = = }\n@misc{ b, title = b }");
+    let (start, end) = bibtex.get("b").unwrap().source_span().unwrap();
+    assert_eq!((start, end), (22, 43));
+
+    let built = BibtexBuilder::new()
+        .add_bibliography("misc".into(), "key".into(), vec![])
+        .build()
+        .unwrap();
+    assert_eq!(built.bibliographies()[0].source_span(), None);
+}
+
+#[test]
+fn test_display() {
+    let bibtex = Bibtex::parse(
+        "@string{ acm = {ACM} }
+        @Article{ key, Journal = {J. } # acm, title = {A {Title}} }",
+    )
+    .unwrap();
+    let biblio = bibtex.get("key").unwrap();
+
+    assert_eq!(
+        biblio.to_string(),
+        "@Article{key,\n    Journal = {J.ACM},\n    title = {A {Title}}\n}"
+    );
+    let reparsed = Bibtex::parse(&biblio.to_string()).unwrap();
+    assert_eq!(reparsed.get("key").unwrap(), biblio);
+
+    let raw = biblio.raw_tag("journal").unwrap();
+    assert_eq!(raw[0].to_string(), "J.");
+    assert_eq!(raw[1].to_string(), "acm");
+}
+
+#[test]
+fn test_raw_tags() {
+    let mut bibtex = Bibtex::parse(
+        "@string{ acm = {ACM} }
+        @misc{ key, Journal = {J. } # acm, year = 2020 }",
+    )
+    .unwrap();
+    let biblio = bibtex.get_mut("key").unwrap();
+
+    assert_eq!(biblio.get_tag("journal"), Some("J.ACM"));
+    assert_eq!(
+        biblio.raw_tags()["journal"],
+        vec![
+            StringValueType::Str("J.".into()),
+            StringValueType::Abbreviation("acm".into())
+        ]
+    );
+    assert_eq!(
+        biblio.raw_tags()["year"],
+        vec![StringValueType::Str("2020".into())]
+    );
+    assert_eq!(
+        biblio.raw_tag("JOURNAL"),
+        Some(&biblio.raw_tags()["journal"][..])
+    );
+    assert_eq!(biblio.raw_tag("missing"), None);
+
+    biblio.insert_tag("journal", "Other".into()).unwrap();
+    assert_eq!(
+        biblio.raw_tags()["journal"],
+        vec![StringValueType::Str("Other".into())]
+    );
+    biblio.remove_tag("year");
+    assert!(!biblio.raw_tags().contains_key("year"));
+}
+
+#[test]
+fn test_entry_type_case_is_kept() {
+    let bib_str = "@Article{ key, Title = {T} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    let biblio = &bibtex.bibliographies()[0];
+
+    assert_eq!(biblio.entry_type(), "Article");
+    assert_eq!(biblio.entry_type_enum(), EntryType::Article);
+    assert_eq!(biblio.tags()["title"], "T");
+    assert_eq!(
+        bibtex.to_bibtex_string(),
+        "@Article{key,\n    Title = {T}\n}\n"
+    );
+}
+
+#[test]
+fn test_parenthesized_entries() {
+    let bib_str = "@preamble(\"Preamble\")
+        @string(name = \"Name\")
+        @comment(A comment)
+        @article(first, author = name, title = {A (nested) title})
+        @misc{ second, note = \"Note\" }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    assert_eq!(bibtex.preambles(), ["Preamble"]);
+    assert_eq!(bibtex.comment_blocks(), ["A comment"]);
+    assert_eq!(bibtex.variables()["name"], "Name");
+    assert_eq!(bibtex.bibliographies().len(), 2);
+    let first = bibtex.get("first").unwrap();
+    assert_eq!(first.tags()["author"], "Name");
+    assert_eq!(first.tags()["title"], "A (nested) title");
+    assert_eq!(bibtex.get("second").unwrap().tags()["note"], "Note");
+
+    assert_eq!(
+        Bibtex::parse("@misc(key, title = {Title}}"),
+        Err(BibtexError::UnclosedBracket {
+            entry: "key".into(),
+            line: 1,
+            column: 6
+        })
+    );
+}
+
+#[test]
+fn test_missing_required_fields() {
+    let bib_str = "@article{ first, author = {A}, title = {T} }
+        @book{ second, editor = {E}, title = {T}, publisher = {P}, year = 2020 }
+        @dataset{ third, title = {T} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    assert_eq!(
+        bibtex.get("first").unwrap().missing_required_fields(),
+        vec!["journal", "year"]
+    );
+    assert!(bibtex
+        .get("third")
+        .unwrap()
+        .missing_required_fields()
+        .is_empty());
+    assert_eq!(
+        bibtex.validate(),
+        vec![("first".to_string(), vec!["journal", "year"])]
+    );
+}
+
+#[test]
+fn test_normalize_whitespace() {
+    let bib_str = "@misc{ key,
+        Title = {A long
+                 title},
+        URL = {a  b},
+        note = \"A  note\"
+    }";
+    let options = ParseOptions::new().normalize_whitespace(true);
+    let bibtex = Bibtex::parse_with(bib_str, &options).unwrap();
+    let biblio = bibtex.get("key").unwrap();
+    assert_eq!(biblio.get_tag("title"), Some("A long title"));
+    assert_eq!(biblio.get_tag("url"), Some("a  b"));
+    assert_eq!(biblio.get_tag("note"), Some("A note"));
+
+    let options = options.verbatim_fields(&["Note"]);
+    let bibtex = Bibtex::parse_with(bib_str, &options).unwrap();
+    let biblio = bibtex.get("key").unwrap();
+    assert_eq!(biblio.get_tag("url"), Some("a b"));
+    assert_eq!(biblio.get_tag("note"), Some("A  note"));
+
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    assert!(bibtex
+        .get("key")
+        .unwrap()
+        .get_tag("title")
+        .unwrap()
+        .contains('\n'));
+}
+
+#[test]
+fn test_keywords() {
+    let bib_str =
+        "@misc{ a, Keywords = { Parsing; BibTeX,, {machine learning, deep} ;{A}, {B} and {C} } }
+        @misc{ b, keywords = {} }
+        @misc{ c, title = {T} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    assert_eq!(
+        bibtex.get("a").unwrap().keywords(),
+        [
+            "Parsing",
+            "BibTeX",
+            "machine learning, deep",
+            "A",
+            "{B} and {C}"
+        ]
+    );
+    assert!(bibtex.get("b").unwrap().keywords().is_empty());
+    assert!(bibtex.get("c").unwrap().keywords().is_empty());
+    assert_eq!(bibtex.entries_with_keyword(" PARSING ").count(), 1);
+    assert_eq!(bibtex.entries_with_keyword("machine learning").count(), 0);
+}
+
+#[test]
+fn test_borrowed_views() {
+    let bib_str = "@string{ Name = {Name} }
+        @misc{ key, Author = name, title = {T} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    let biblio = bibtex.get("key").unwrap();
+
+    assert_eq!(
+        biblio.iter_tags().collect::<Vec<_>>(),
+        [("author", "Name"), ("title", "T")]
+    );
+    assert_eq!(
+        bibtex.iter_variables().collect::<Vec<_>>(),
+        [("Name", "Name")]
+    );
+}
+
+#[test]
+fn test_sort_bibliographies() {
+    let bib_str = "@misc{ c, year = 2020, month = mar }
+        @comment{ A comment }
+        @misc{ a, year = {In press} }
+        @misc{ b, year = 2019 }
+        @misc{ a, date = {2020-01-05} }
+        @misc{ d, year = 2020 }";
+    let mut bibtex = Bibtex::parse(bib_str).unwrap();
+    let keys = |bibtex: &Bibtex| {
+        bibtex
+            .bibliographies()
+            .iter()
+            .map(|b| b.citation_key().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    bibtex.sort_by_year();
+    assert_eq!(keys(&bibtex), ["b", "a", "c", "d", "a"]);
+    assert_eq!(bibtex.get("a").unwrap().get_tag("date"), Some("2020-01-05"));
+
+    bibtex.sort_by_key();
+    assert_eq!(keys(&bibtex), ["a", "a", "b", "c", "d"]);
+    assert_eq!(bibtex.get("a").unwrap().get_tag("date"), Some("2020-01-05"));
+    assert!(matches!(
+        bibtex.entries_in_order().nth(1),
+        Some(EntryRef::CommentBlock("A comment"))
+    ));
+
+    bibtex.sort_by(|a, b| b.year().cmp(&a.year()));
+    assert_eq!(keys(&bibtex), ["a", "c", "d", "b", "a"]);
+    assert_eq!(bibtex.get("b").unwrap().year(), Some(2019));
+}
+
+#[test]
+fn test_sort_by_field() {
+    let bib_str = "@misc{ a, author = {Smith}, volume = 10 }
+        @misc{ b, author = {Doe}, volume = 9 }
+        @misc{ c, volume = { 10 } }
+        @misc{ d, author = {Doe} }";
+    let mut bibtex = Bibtex::parse(bib_str).unwrap();
+    let keys = |bibtex: &Bibtex| {
+        bibtex
+            .bibliographies()
+            .iter()
+            .map(|b| b.citation_key().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    bibtex.sort_by_field("author");
+    assert_eq!(keys(&bibtex), ["b", "d", "a", "c"]);
+    bibtex.sort_by_field("volume");
+    assert_eq!(keys(&bibtex), ["b", "a", "c", "d"]);
+    assert_eq!(bibtex.get("c").unwrap().get_tag("volume"), Some("10"));
+
+    bibtex
+        .get_mut("d")
+        .unwrap()
+        .insert_tag("volume", "IV".into())
+        .unwrap();
+    bibtex.sort_by_field("volume");
+    assert_eq!(keys(&bibtex), ["a", "c", "b", "d"]);
+}
+
+#[test]
+fn test_max_nesting_depth() {
+    let deep = format!("{}{}", "{".repeat(100_000), "}".repeat(100_000));
+    let bib_str = format!(
+        "@misc{{ first, title = {{T}} }}\n@misc{{ deep, title = {} }}\n@misc{{ last, title = {{T}} }}",
+        deep
+    );
+    let err = || BibtexError::NestingTooDeep {
+        entry: "deep".into(),
+        line: 2,
+        column: 122,
+        max_depth: DEFAULT_MAX_NESTING_DEPTH,
+    };
+    assert_eq!(Bibtex::parse(&bib_str), Err(err()));
+    let entries: Vec<_> = Bibtex::parse_reader(bib_str.as_bytes()).collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1], Err(err()));
+    assert_eq!(Bibtex::raw_parse(&bib_str), Err(err()));
+
+    let (bibtex, errors) = Bibtex::parse_lossy(&bib_str);
+    assert_eq!(errors, [err()]);
+    assert_eq!(bibtex.bibliographies().len(), 2);
+
+    let bib_str = "@misc( key, title = {{A} {B}}, note = \"{{C}}\" )";
+    let options = ParseOptions::new().max_nesting_depth(2);
+    assert!(Bibtex::parse_with(bib_str, &options).is_ok());
+    let options = ParseOptions::new().max_nesting_depth(1);
+    assert_eq!(
+        Bibtex::parse_with(bib_str, &options),
+        Err(BibtexError::NestingTooDeep {
+            entry: "key".into(),
+            line: 1,
+            column: 22,
+            max_depth: 1
+        })
+    );
+    let err = || BibtexError::NestingTooDeep {
+        entry: "key".into(),
+        line: 1,
+        column: 22,
+        max_depth: 1,
+    };
+    let entries: Vec<_> = Bibtex::iter_entries_with(bib_str, &options).collect();
+    assert_eq!(entries, [Err(err())]);
+    let entries: Vec<_> = Bibtex::parse_reader_with(bib_str.as_bytes(), &options).collect();
+    assert_eq!(entries, [Err(err())]);
+    let options = ParseOptions::new().max_nesting_depth(2);
+    assert!(Bibtex::parse_reader_with(bib_str.as_bytes(), &options).all(|e| e.is_ok()));
+}
+
+#[test]
+fn test_counts() {
+    let bibtex = Bibtex::parse(&read_file("samples/test.bib")).unwrap();
+    assert_eq!(bibtex.entry_count(), 3);
+    assert_eq!(
+        bibtex.entry_type_counts().values().sum::<usize>(),
+        bibtex.entry_count()
+    );
+    assert_eq!(bibtex.variable_count(), bibtex.variables().len());
+
+    let bibtex = Bibtex::parse(
+        "@string{ a = {A} } @string{ A = {Again} } @string{ b = {B} }
+        @MISC{ x, title = a } @misc{ y, title = b }",
+    )
+    .unwrap();
+    assert_eq!(bibtex.variable_count(), 2);
+    let counts = bibtex.entry_type_counts();
+    assert_eq!(counts.len(), 1);
+    assert_eq!(counts["misc"], 2);
+}
+
+#[test]
+fn test_iterate_and_is_empty() {
+    let bibtex = Bibtex::parse(&read_file("samples/test.bib")).unwrap();
+    let mut keys = vec![];
+    for biblio in &bibtex {
+        keys.push(biblio.citation_key());
+    }
+    assert_eq!(keys, ["einstein", "latexcompanion", "knuthwebsite"]);
+    assert_eq!(bibtex.iter().count(), bibtex.entry_count());
+    assert!(!bibtex.is_empty());
+
+    assert!(Bibtex::default().is_empty());
+    let bibtex = Bibtex::parse("% Only a comment").unwrap();
+    assert_eq!(bibtex.iter().next(), None);
+    assert!(!bibtex.is_empty());
+    let mut bibtex = Bibtex::parse("@misc{ key, title = {Title} }").unwrap();
+    bibtex.retain(|_| false);
+    assert!(bibtex.is_empty());
+}
+
+#[test]
+fn test_find_duplicates() {
+    let bib_str = "@article{ smith2020, title = {Deep {Learning}}, doi = {10.1000/ABC} }
+        @comment{ Between }
+        @article{ jones, title = {Deep learning}, doi = {10.1000/other} }
+        @misc{ Smith20, title = {deep   learning}, year = 2020, note = {N} }
+        @misc{ untitled, year = 1 }
+        @misc{ untitled2, year = 1 }
+        @article{ smith-doi, doi = {DOI:10.1000/abc} }
+        @misc{ similar, title = {Deep learnings} }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let groups: Vec<Vec<&str>> = bibtex
+        .find_duplicates()
+        .iter()
+        .map(|group| group.iter().map(|b| b.citation_key()).collect())
+        .collect();
+    assert_eq!(groups, [vec!["smith2020", "Smith20", "smith-doi"]]);
+
+    let keys = |bibtex: &Bibtex| {
+        bibtex
+            .bibliographies()
+            .iter()
+            .map(|b| b.citation_key().to_string())
+            .collect::<Vec<_>>()
+    };
+    let mut first = Bibtex::parse(bib_str).unwrap();
+    let removed = first.dedup(DedupPolicy::KeepFirst);
+    assert_eq!(removed.len(), 2);
+    assert_eq!(
+        keys(&first),
+        ["smith2020", "jones", "untitled", "untitled2", "similar"]
+    );
+    assert!(matches!(
+        first.entries_in_order().nth(1),
+        Some(EntryRef::CommentBlock("Between"))
+    ));
+    assert!(first.find_duplicates().is_empty());
+
+    let mut last = Bibtex::parse(bib_str).unwrap();
+    last.dedup(DedupPolicy::KeepLast);
+    assert_eq!(
+        keys(&last),
+        ["jones", "untitled", "untitled2", "smith-doi", "similar"]
+    );
+
+    let mut most = Bibtex::parse(bib_str).unwrap();
+    most.dedup(DedupPolicy::KeepMostTags);
+    assert_eq!(
+        keys(&most),
+        ["jones", "Smith20", "untitled", "untitled2", "similar"]
+    );
+}
+
+#[test]
+fn test_variables_using_month_constants() {
+    let bib_str = "@string{ m = jan }
+        @string{ period = m # { to } # dec }
+        @string{ feb = {Not a month} }
+        @string{ override = feb }
+        @misc{ key, month = m, note = period # { } # override, title = jcp }";
+    let options = ParseOptions::new().constant("jcp", "J. Chem. Phys.");
+    let bibtex = Bibtex::parse_with(bib_str, &options).unwrap();
+
+    assert_eq!(bibtex.variable("m"), Some("January"));
+    assert_eq!(bibtex.variable("period"), Some("JanuarytoDecember"));
+    assert_eq!(bibtex.variable("override"), Some("Not a month"));
+    let biblio = bibtex.get("key").unwrap();
+    assert_eq!(biblio.month(), Some(Month::January));
+    assert_eq!(biblio.get_tag("title"), Some("J. Chem. Phys."));
+
+    let mut entries = ExpandingIter::new(Bibtex::iter_entries("@string{ m = jan }"));
+    assert_eq!(
+        entries.next(),
+        Some(Ok(ExpandedEntry::Variable("m".into(), "January".into())))
+    );
+}
+
+#[test]
+fn test_raw_entries() {
+    let entries = Bibtex::raw_parse(
+        "Free text
+        @preamble{ \"P\" }
+        @comment{ C }
+        @String{ Name = {A} # b }
+        @Misc{ Key, Title = name }",
+    )
+    .unwrap();
+
+    let types: Vec<_> = entries.iter().map(Entry::entry_type).collect();
+    assert_eq!(
+        types,
+        [
+            None,
+            Some("preamble"),
+            Some("comment"),
+            Some("string"),
+            Some("Misc")
+        ]
+    );
+    assert_eq!(
+        entries[3],
+        Entry::Variable(KeyValue::new(
+            "Name".into(),
+            vec![
+                StringValueType::Str("A".into()),
+                StringValueType::Abbreviation("b".into())
+            ]
+        ))
+    );
+    assert_eq!(entries[4].citation_key(), Some("Key"));
+    assert_eq!(
+        entries[4].tags(),
+        [KeyValue::new(
+            "Title".into(),
+            vec![StringValueType::Abbreviation("name".into())]
+        )]
+    );
+    assert_eq!(entries[3].citation_key(), None);
+    assert!(entries[3].tags().is_empty());
+}
+
+#[test]
+fn test_retain_and_filtered() {
+    let bib_str = "@preamble{ \"P\" }
+        @string{ Name = {Name} }
+        @misc{ first, author = Name }
+        @comment{ C }
+        @misc{ second, author = {Other} }
+        @misc{ third, author = Name }";
+    let bibtex = Bibtex::parse(bib_str).unwrap();
+    let cited = ["first", "third"];
+
+    let filtered = bibtex.filtered(|b| cited.contains(&b.citation_key()));
+    assert_eq!(bibtex.bibliographies().len(), 3);
+    assert_eq!(filtered.bibliographies().len(), 2);
+    assert!(filtered.get("second").is_none());
+    assert_eq!(
+        filtered.get("third").unwrap().get_tag("author"),
+        Some("Name")
+    );
+    assert_eq!(
+        filtered.to_bibtex_string(),
+        "@preamble{{P}}
+
+@string{Name = {Name}}
+
+@misc{first,
+    author = Name
+}
+
+@comment{C}
+
+@misc{third,
+    author = Name
+}
+"
+    );
+
+    let mut retained = Bibtex::parse(bib_str).unwrap();
+    retained.retain(|b| cited.contains(&b.citation_key()));
+    assert_eq!(retained, filtered);
+    assert_eq!(
+        Bibtex::parse(&retained.to_bibtex_string()).unwrap(),
+        retained
+    );
+
+    retained.retain(|_| false);
+    assert!(retained.bibliographies().is_empty());
+    assert_eq!(retained.variables().len(), 1);
+    assert_eq!(retained.entries_in_order().count(), 3);
+}
+
+#[test]
+fn test_final_commas() {
+    let entries = [
+        "@misc{ key, author = {X}, }",
+        "@misc{ key, author = {X},}",
+        "@misc{ key, author = {X}\n}",
+        "@misc{ key, author = {X}}",
+        "@misc{ key, author = \"X\"}",
+        "@misc{ key,\n    author = {X} ,\n}",
+        "@misc(key, author = {X},)",
+        "@misc(key, author = {X})",
+        "@string{ x = {X} } @misc{ key, author = x}",
+        "@string{ x = {X} } @misc{ key, author = x,}",
+    ];
+    for bib_str in entries {
+        let bibtex = Bibtex::parse(bib_str).unwrap_or_else(|e| panic!("{}: {}", bib_str, e));
+        assert_eq!(
+            bibtex.get("key").unwrap().tags_ordered(),
+            [("author".into(), "X".into())]
+        );
+    }
+
+    let bibtex = Bibtex::parse("@misc{ key, title = {T}, year = 2020}").unwrap();
+    assert_eq!(bibtex.get("key").unwrap().year(), Some(2020));
+    let bibtex = Bibtex::parse("@misc{ key, title = {T}, year = 2020,\n}").unwrap();
+    assert_eq!(bibtex.get("key").unwrap().year(), Some(2020));
+
+    let options = ParseOptions::new().lenient_commas(true);
+    for bib_str in [
+        "@misc{ key,\n title = {T}\n year = 2020 }",
+        "@misc{ key,\n title = {T}\n year = 2020,}",
+    ] {
+        let bibtex = Bibtex::parse_with(bib_str, &options).unwrap();
+        assert_eq!(bibtex.get("key").unwrap().year(), Some(2020));
+    }
+
+    for bib_str in ["@misc{key}", "@misc{ key,}", "@misc( key )"] {
+        let bibtex = Bibtex::parse(bib_str).unwrap();
+        assert!(bibtex.get("key").unwrap().tags_ordered().is_empty());
+    }
+
+    assert!(Bibtex::parse("@misc{ key, author = {X},, }").is_err());
+}
+
+#[test]
+fn test_generate_keys() {
+    let bib_str = "@book{ parent, editor = {M{\\\"u}ller, Hans}, title = {A Collection}, year = 2020 }
+        @incollection{ child, author = {John Smith and Jane Doe}, crossref = {parent}, date = {2020-05} }
+        @misc{ c, author = {Smith, Jane}, title = {The {Smith} method}, year = 2020 }
+        @misc{ d, title = {Anonymous}, year = {n.d.} }
+        @misc{ e, author = {{World Health Organization}} }";
+    let mut bibtex = Bibtex::parse(bib_str).unwrap();
+
+    let scheme = KeyScheme::new();
+    assert_eq!(
+        bibtex.get("parent").unwrap().generate_key(&scheme),
+        "muller2020"
+    );
+    assert_eq!(
+        bibtex
+            .get("c")
+            .unwrap()
+            .generate_key(&scheme.clone().title_word(true)),
+        "smith2020smith"
+    );
+    assert_eq!(
+        bibtex
+            .get("d")
+            .unwrap()
+            .generate_key(&scheme.clone().title_word(true)),
+        "anonymous"
+    );
+    assert_eq!(bibtex.get("d").unwrap().generate_key(&scheme), "item");
+    assert_eq!(
+        bibtex
+            .get("e")
+            .unwrap()
+            .generate_key(&scheme.clone().year(false)),
+        "worldhealthorganization"
+    );
+
+    let renamed = bibtex.regenerate_keys(&scheme);
+    let new_keys: Vec<_> = renamed.iter().map(|(_, new)| new.as_str()).collect();
+    assert_eq!(
+        new_keys,
+        [
+            "muller2020",
+            "smith2020",
+            "smith2020a",
+            "item",
+            "worldhealthorganization"
+        ]
+    );
+    assert_eq!(renamed[1].0, "child");
+    assert!(bibtex.get("child").is_none());
+    let child = bibtex.get("smith2020").unwrap();
+    assert_eq!(child.get_tag("crossref"), Some("muller2020"));
+    assert_eq!(
+        bibtex.get("smith2020a").unwrap().get_tag("author"),
+        Some("Smith, Jane")
+    );
 }
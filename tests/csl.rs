@@ -0,0 +1,202 @@
+#![cfg(feature = "csl")]
+
+extern crate nom_bibtex;
+extern crate serde_json;
+
+use nom_bibtex::error::BibtexError;
+use nom_bibtex::Bibtex;
+use serde_json::json;
+use std::fs::File;
+use std::io::prelude::*;
+
+fn read_file(filename: &str) -> String {
+    let mut file = File::open(filename).unwrap();
+    let mut bib_content = String::new();
+
+    file.read_to_string(&mut bib_content).unwrap();
+    bib_content
+}
+
+#[test]
+fn test_csl_json() {
+    let bibtex = Bibtex::parse(&read_file("samples/test.bib")).unwrap();
+    let items = bibtex.to_csl_json();
+
+    assert_eq!(
+        items[0],
+        json!({
+            "id": "einstein",
+            "type": "article-journal",
+            "author": [{ "family": "Einstein", "given": "Albert" }],
+            "title": "Zur Elektrodynamik bewegter Körper. (German) [On the electrodynamics of moving bodies]",
+            "container-title": "Annalen der Physik",
+            "volume": "322",
+            "issue": "10",
+            "page": "891-921",
+            "issued": { "date-parts": [[1905]] },
+            "DOI": "http://dx.doi.org/10.1002/andp.19053221004"
+        })
+    );
+    assert_eq!(items[1]["author"].as_array().unwrap().len(), 3);
+    assert_eq!(items[1]["publisher-place"], "Reading, Massachusetts");
+    assert_eq!(items[2]["type"], "document");
+}
+
+#[test]
+fn test_csl_json_fields() {
+    let bibtex = Bibtex::parse(
+        "@phdthesis{ key,
+            author = {Ludwig van Beethoven and King, Jr, Martin},
+            date = {2019-03/2019-06},
+            eventtitle = {Event},
+            note = {A note},
+            school = {School}
+        }
+        @misc{ other, author = {Prince}, year = {To appear} }",
+    )
+    .unwrap();
+
+    assert_eq!(
+        bibtex.get("key").unwrap().to_csl_json(),
+        json!({
+            "id": "key",
+            "type": "thesis",
+            "genre": "PhD thesis",
+            "author": [
+                { "family": "Beethoven", "given": "Ludwig", "non-dropping-particle": "van" },
+                { "family": "King", "given": "Martin", "suffix": "Jr" }
+            ],
+            "issued": { "date-parts": [[2019, 3], [2019, 6]] },
+            "note": "A note\neventtitle: Event",
+            "publisher": "School"
+        })
+    );
+    assert_eq!(
+        bibtex.get("other").unwrap().to_csl_json(),
+        json!({
+            "id": "other",
+            "type": "document",
+            "author": [{ "family": "Prince" }],
+            "issued": { "literal": "To appear" }
+        })
+    );
+}
+
+#[test]
+fn test_csl_json_round_trip() {
+    let bibtex = Bibtex::parse(&read_file("samples/test.bib")).unwrap();
+    let json = bibtex.to_csl_json().to_string();
+    let imported = Bibtex::from_csl_json(&json).unwrap();
+
+    assert_eq!(imported.bibliographies().len(), 3);
+    for biblio in bibtex.bibliographies() {
+        let other = imported.get(biblio.citation_key()).unwrap();
+        assert_eq!(other.entry_type(), biblio.entry_type());
+        assert_eq!(other.authors(), biblio.authors());
+        for key in [
+            "journal",
+            "volume",
+            "number",
+            "pages",
+            "year",
+            "doi",
+            "publisher",
+            "address",
+        ] {
+            assert_eq!(other.get_tag(key), biblio.get_tag(key), "{}", key);
+        }
+    }
+    assert_eq!(
+        imported.get("einstein").unwrap().get_tag("title"),
+        Some("Zur Elektrodynamik bewegter Körper. (German) [On the electrodynamics of moving bodies]")
+    );
+}
+
+#[test]
+fn test_from_csl_json() {
+    let json = json!([
+        {
+            "id": "key",
+            "type": "chapter",
+            "author": [
+                { "family": "Beethoven", "given": "Ludwig", "non-dropping-particle": "van" },
+                { "family": "King", "given": "Martin", "suffix": "Jr" },
+                { "literal": "The Team" }
+            ],
+            "container-title": "Collected works",
+            "issued": { "date-parts": [[2019, 3, 5]] },
+            "page": "10-20",
+            "unknown": "dropped"
+        },
+        {
+            "type": "thesis",
+            "genre": "Master's thesis",
+            "author": [{ "family": "Doe", "given": "Jane" }],
+            "publisher": "University",
+            "issued": { "date-parts": [["2020"]] }
+        },
+        { "id": "key", "type": "webpage", "issued": { "literal": "n.d." } },
+        { "id": 7, "type": "report", "editor": [{ "family": "Doe" }] }
+    ]);
+    let bibtex = Bibtex::from_csl_json(&json.to_string()).unwrap();
+
+    let biblio = &bibtex.bibliographies()[0];
+    assert_eq!(biblio.citation_key(), "key");
+    assert_eq!(biblio.entry_type(), "incollection");
+    assert_eq!(
+        biblio.get_tag("author"),
+        Some("van Beethoven, Ludwig and King, Jr, Martin and {The Team}")
+    );
+    assert_eq!(biblio.get_tag("booktitle"), Some("Collected works"));
+    assert_eq!(biblio.get_tag("year"), Some("2019"));
+    assert_eq!(biblio.get_tag("month"), Some("March"));
+    assert_eq!(biblio.get_tag("pages"), Some("10--20"));
+    assert!(!biblio.has_tag("unknown"));
+
+    let biblio = &bibtex.bibliographies()[1];
+    assert_eq!(biblio.citation_key(), "doe2020");
+    assert_eq!(biblio.entry_type(), "mastersthesis");
+    assert_eq!(biblio.get_tag("school"), Some("University"));
+
+    let biblio = &bibtex.bibliographies()[2];
+    assert_eq!(biblio.citation_key(), "item");
+    assert_eq!(biblio.entry_type(), "misc");
+    assert_eq!(biblio.get_tag("year"), Some("n.d."));
+
+    let biblio = &bibtex.bibliographies()[3];
+    assert_eq!(biblio.citation_key(), "7");
+    assert_eq!(biblio.entry_type(), "techreport");
+    assert_eq!(biblio.get_tag("editor"), Some("Doe"));
+
+    assert!(matches!(
+        Bibtex::from_csl_json("{}"),
+        Err(nom_bibtex::error::BibtexError::InvalidCslJson(_))
+    ));
+    assert!(Bibtex::from_csl_json("[").is_err());
+}
+
+#[test]
+fn test_from_csl_json_writes_bibtex() {
+    let json = json!([
+        { "id": "my key, x", "type": "book", "title": "Title", "issued": { "date-parts": [[2020]] } },
+        { "id": "{key}", "type": "book", "author": [{ "family": "Doe" }] },
+        { "id": "ok", "type": "book", "title": "{Nested} title" }
+    ]);
+    let bibtex = Bibtex::from_csl_json(&json.to_string()).unwrap();
+    let keys = bibtex
+        .bibliographies()
+        .iter()
+        .map(|b| b.citation_key())
+        .collect::<Vec<_>>();
+    assert_eq!(keys, ["2020", "doe", "ok"]);
+    assert_eq!(Bibtex::parse(&bibtex.to_bibtex_string()).unwrap(), bibtex);
+
+    let json = json!([{ "id": "a", "type": "book", "title": "Close } brace" }]);
+    assert_eq!(
+        Bibtex::from_csl_json(&json.to_string()),
+        Err(BibtexError::UnbalancedTagValue {
+            entry: "a".into(),
+            key: "title".into()
+        })
+    );
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "serde")]
+
+extern crate nom_bibtex;
+extern crate serde_json;
+
+use nom_bibtex::Bibtex;
+use std::fs::File;
+use std::io::prelude::*;
+
+fn read_file(filename: &str) -> String {
+    let mut file = File::open(filename).unwrap();
+    let mut bib_content = String::new();
+
+    file.read_to_string(&mut bib_content).unwrap();
+    bib_content
+}
+
+#[test]
+fn test_json_round_trip() {
+    let bib_str = read_file("samples/test.bib");
+    let bibtex = Bibtex::parse(&bib_str).unwrap();
+
+    let json = serde_json::to_string(&bibtex).unwrap();
+    let deserialized: Bibtex = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, bibtex);
+
+    let b0 = &deserialized.bibliographies()[0];
+    assert_eq!(b0.citation_key(), "einstein");
+    assert_eq!(b0.get_tag("author"), Some("Albert Einstein"));
+    assert_eq!(b0.tags_ordered()[0].0, "author");
+    assert_eq!(deserialized.variables()["donald"], "Donald Knuth");
+    assert_eq!(
+        deserialized.get("knuthwebsite").unwrap().get_tag("author"),
+        Some("Donald Knuth")
+    );
+}
+
+#[test]
+fn test_json_field_names() {
+    let bibtex = Bibtex::parse("@misc{ key, title = {T}, author = {A} }").unwrap();
+
+    let value = serde_json::to_value(&bibtex.bibliographies()[0]).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "entry_type": "misc",
+            "citation_key": "key",
+            "tags": { "title": "T", "author": "A" }
+        })
+    );
+}
+
+#[test]
+fn test_json_tags_are_normalized() {
+    let json = r#"{
+        "entry_type": "misc",
+        "citation_key": "key",
+        "tags": { "Title": "First", "author": "A", "TITLE": "Second" }
+    }"#;
+    let biblio: nom_bibtex::Bibliography = serde_json::from_str(json).unwrap();
+
+    assert_eq!(
+        biblio.tags_ordered(),
+        &[
+            ("title".to_string(), "Second".to_string()),
+            ("author".to_string(), "A".to_string())
+        ]
+    );
+    assert_eq!(biblio.original_key("title"), Some("Title"));
+}